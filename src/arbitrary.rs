@@ -0,0 +1,77 @@
+//! Provides an implementation of [`Arbitrary`] for [`ListOrderedMultimap`], for fuzzing code that uses multimaps.
+//!
+//! An arbitrary multimap is built by appending arbitrary key-value pairs in order, so the keys collide as often as the
+//! arbitrary keys do, and the values of different keys are interleaved arbitrarily. Key types with few possible values,
+//! such as `u8` or `bool`, therefore produce many keys with multiple values.
+//!
+//! # Examples
+//!
+//! ```
+//! use arbitrary::{Arbitrary, Unstructured};
+//! use ordered_multimap::ListOrderedMultimap;
+//!
+//! let mut unstructured = Unstructured::new(&[1, 1, 10, 1, 2, 20, 1, 1, 30]);
+//! let map: ListOrderedMultimap<u8, u8> = ListOrderedMultimap::arbitrary(&mut unstructured).unwrap();
+//!
+//! assert_eq!(map.get_all(&1).collect::<Vec<_>>(), [&10, &30]);
+//! assert_eq!(map.get_all(&2).collect::<Vec<_>>(), [&20]);
+//! ```
+
+use core::hash::{BuildHasher, Hash};
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::ListOrderedMultimap;
+
+impl<'a, Key, Value, State> Arbitrary<'a> for ListOrderedMultimap<Key, Value, State>
+where
+  Key: Arbitrary<'a> + Eq + Hash,
+  State: BuildHasher + Default,
+  Value: Arbitrary<'a>,
+{
+  fn arbitrary(unstructured: &mut Unstructured<'a>) -> Result<Self> {
+    unstructured.arbitrary_iter()?.collect()
+  }
+
+  fn arbitrary_take_rest(unstructured: Unstructured<'a>) -> Result<Self> {
+    unstructured.arbitrary_take_rest_iter()?.collect()
+  }
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use alloc::vec::Vec;
+
+  use super::*;
+
+  const DATA: &[u8] = &[1, 3, 7, 1, 3, 8, 1, 4, 9, 1, 3, 10, 0, 255];
+
+  #[test]
+  fn test_arbitrary() {
+    let map: ListOrderedMultimap<u8, u8> =
+      ListOrderedMultimap::arbitrary(&mut Unstructured::new(DATA)).unwrap();
+    let pairs = Vec::<(u8, u8)>::arbitrary(&mut Unstructured::new(DATA)).unwrap();
+
+    assert_eq!(map.values_len(), pairs.len());
+    assert!(map.iter().map(|(&key, &value)| (key, value)).eq(pairs));
+    assert_eq!(map.get_all(&3).collect::<Vec<_>>(), [&7, &8, &10]);
+  }
+
+  #[test]
+  fn test_arbitrary_empty() {
+    let map: ListOrderedMultimap<u8, u8> =
+      ListOrderedMultimap::arbitrary(&mut Unstructured::new(&[])).unwrap();
+    assert!(map.is_empty());
+  }
+
+  #[test]
+  fn test_arbitrary_take_rest() {
+    let map: ListOrderedMultimap<u8, u8> =
+      ListOrderedMultimap::arbitrary_take_rest(Unstructured::new(DATA)).unwrap();
+    let pairs = Vec::<(u8, u8)>::arbitrary_take_rest(Unstructured::new(DATA)).unwrap();
+
+    assert!(map.iter().map(|(&key, &value)| (key, value)).eq(pairs));
+  }
+}
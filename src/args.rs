@@ -0,0 +1,332 @@
+//! Provides parsing of command-line arguments and `KEY=VALUE` environment lists into multimaps.
+//!
+//! Wrapper tools often inspect a few options of a command line and forward the rest unchanged. Options such as
+//! `--include` or `-v` may be repeated, and their order relative to each other and to positional arguments can be
+//! significant, so [`parse_args`] keeps every option and positional argument as a key-value pair in global order.
+//!
+//! Arguments are parsed without knowing which options take a value:
+//!
+//!  - `--name=value` is the option `--name` with the value `value`.
+//!  - `--name` is the option `--name`. If the next argument is `-` or does not start with `-`, it is the value of the
+//!    option, otherwise the value is empty.
+//!  - `-abc` is a group of the options `-a`, `-b` and `-c` with empty values, except that the value of the last option
+//!    is taken from the next argument like for `--name`, or from after a `=` as in `-abc=value`.
+//!  - `--` ends the options, and all following arguments are positional. It is kept as the option `--` with an empty
+//!    value, so that positional arguments after it can be told apart from options.
+//!  - Every other argument that is not the value of an option is positional. Positional arguments have an empty key.
+//!
+//! The keys of options keep their leading dashes, so `-v` and `--v` are different keys. Arguments that are not valid
+//! Unicode are only split at `=` on Unix, and groups of short options that are not valid Unicode are not split.
+//!
+//! Since it is not known which options take a value, the multimap is a guess: `-ofile` is parsed as the options `-o`,
+//! `-f`, `-i`, `-l` and `-e`, and `--verbose input.c` as the option `--verbose` with the value `input.c`. The original
+//! arguments also cannot in general be recovered from the multimap, since an option without a value cannot be told
+//! apart from an option with an empty value such as `--name=`, and `--name value`, `--name=value` and the groups `-abc`
+//! and `-a -b -c` are parsed the same. [`parse_args`] therefore returns the arguments as given along with the
+//! multimap, and tools should forward those rather than arguments rebuilt from the multimap.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::ffi::OsStr;
+//!
+//! use ordered_multimap::args;
+//!
+//! let (map, args) = args::parse_args(["-I", "a", "--define=X", "input.c", "-I", "b", "--", "-o"]);
+//!
+//! let includes = map.get_all(OsStr::new("-I")).collect::<Vec<_>>();
+//! assert_eq!(includes, [OsStr::new("a"), OsStr::new("b")]);
+//! assert_eq!(map.get(OsStr::new("--define")).unwrap(), "X");
+//! assert!(map.contains_key(OsStr::new("--")));
+//!
+//! let positional = map.get_all(OsStr::new("")).collect::<Vec<_>>();
+//! assert_eq!(positional, [OsStr::new("input.c"), OsStr::new("-o")]);
+//!
+//! // The arguments to forward, unchanged.
+//! assert_eq!(args.len(), 8);
+//! assert_eq!(args[7], "-o");
+//! ```
+
+use alloc::vec::Vec;
+use std::ffi::{OsStr, OsString};
+
+use crate::ListOrderedMultimap;
+
+/// Parses command-line arguments into a multimap from each option to its values and from the empty key to the
+/// positional arguments, all in global order, and returns it together with the arguments as given.
+///
+/// The arguments must not include the name of the program, so the arguments of the current process are parsed with
+/// `parse_args(std::env::args_os().skip(1))`. See the [module documentation](self) for how arguments are parsed and
+/// why the arguments as given should be forwarded instead of the multimap.
+///
+/// # Examples
+///
+/// ```
+/// use std::ffi::OsStr;
+///
+/// use ordered_multimap::args::parse_args;
+///
+/// let (map, args) = parse_args(["-vx", "file", "--level", "--output", "-", "--", "input"]);
+///
+/// let mut iter = map.iter().map(|(key, value)| (key.to_str().unwrap(), value.to_str().unwrap()));
+/// assert_eq!(iter.next(), Some(("-v", "")));
+/// assert_eq!(iter.next(), Some(("-x", "file")));
+/// assert_eq!(iter.next(), Some(("--level", "")));
+/// assert_eq!(iter.next(), Some(("--output", "-")));
+/// assert_eq!(iter.next(), Some(("--", "")));
+/// assert_eq!(iter.next(), Some(("", "input")));
+/// assert_eq!(iter.next(), None);
+///
+/// assert_eq!(args, ["-vx", "file", "--level", "--output", "-", "--", "input"]);
+/// ```
+#[must_use]
+pub fn parse_args<Args>(args: Args) -> (ListOrderedMultimap<OsString, OsString>, Vec<OsString>)
+where
+  Args: IntoIterator,
+  Args::Item: Into<OsString>,
+{
+  let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+  let mut map = ListOrderedMultimap::new();
+  let mut iter = args.iter();
+  let mut pending = None;
+
+  for arg in iter.by_ref() {
+    let lossy = arg.to_string_lossy();
+
+    if lossy == "--" {
+      if let Some(key) = pending.take() {
+        let _ = map.append(key, OsString::new());
+      }
+
+      let _ = map.append(arg.clone(), OsString::new());
+      break;
+    }
+
+    if !lossy.starts_with('-') || lossy == "-" {
+      let key = pending.take().unwrap_or_default();
+      let _ = map.append(key, arg.clone());
+      continue;
+    }
+
+    if let Some(key) = pending.take() {
+      let _ = map.append(key, OsString::new());
+    }
+
+    let (options, value) = match split_at_equals(arg) {
+      Some((options, value)) => (options, Some(value)),
+      None => (arg.clone(), None),
+    };
+    let mut keys = split_options(options);
+    let last = keys.pop().expect("expected at least one option");
+
+    for key in keys {
+      let _ = map.append(key, OsString::new());
+    }
+
+    match value {
+      Some(value) => {
+        let _ = map.append(last, value);
+      }
+      None => pending = Some(last),
+    }
+  }
+
+  if let Some(key) = pending {
+    let _ = map.append(key, OsString::new());
+  }
+
+  for arg in iter {
+    let _ = map.append(OsString::new(), arg.clone());
+  }
+
+  (map, args)
+}
+
+/// Parses a list of `KEY=VALUE` environment variables, such as the output of `env` or the contents of
+/// `/proc/<pid>/environ`, into a multimap in global order.
+///
+/// Each variable is split at its first `=`, except that a leading `=` is part of the key, as in the `=C:=C:\` variables
+/// on Windows. A variable without `=` gets an empty value. Variables that occur more than once are kept as multiple
+/// values of the key.
+///
+/// # Examples
+///
+/// ```
+/// use std::ffi::OsStr;
+///
+/// use ordered_multimap::args::parse_env;
+///
+/// let map = parse_env(["PATH=/bin", "A=1=2", "PATH=/usr/bin", "=C:=C:\\"]);
+///
+/// let paths = map.get_all(OsStr::new("PATH")).collect::<Vec<_>>();
+/// assert_eq!(paths, [OsStr::new("/bin"), OsStr::new("/usr/bin")]);
+/// assert_eq!(map.get(OsStr::new("A")).unwrap(), "1=2");
+/// assert_eq!(map.get(OsStr::new("=C:")).unwrap(), "C:\\");
+/// ```
+#[must_use]
+pub fn parse_env<Vars>(vars: Vars) -> ListOrderedMultimap<OsString, OsString>
+where
+  Vars: IntoIterator,
+  Vars::Item: Into<OsString>,
+{
+  vars
+    .into_iter()
+    .map(|var| {
+      let var = var.into();
+      split_at_equals(&var).unwrap_or((var, OsString::new()))
+    })
+    .collect()
+}
+
+/// Splits the argument at the first `=` that is not its first character.
+///
+/// Arguments that are not valid Unicode are only split on Unix.
+fn split_at_equals(arg: &OsStr) -> Option<(OsString, OsString)> {
+  if let Some(arg) = arg.to_str() {
+    let (index, _) = arg.char_indices().skip(1).find(|&(_, c)| c == '=')?;
+    return Some((arg[..index].into(), arg[index + 1..].into()));
+  }
+
+  #[cfg(unix)]
+  {
+    use std::os::unix::ffi::OsStrExt;
+
+    let bytes = arg.as_bytes();
+    let index = bytes.iter().skip(1).position(|&byte| byte == b'=')? + 1;
+    Some((
+      OsStr::from_bytes(&bytes[..index]).into(),
+      OsStr::from_bytes(&bytes[index + 1..]).into(),
+    ))
+  }
+
+  #[cfg(not(unix))]
+  None
+}
+
+/// Splits a group of short options such as `-abc` into its options, or returns a long option unchanged.
+fn split_options(options: OsString) -> Vec<OsString> {
+  match options.to_str() {
+    Some(group) if !group.starts_with("--") && group.len() > 1 => group[1..]
+      .chars()
+      .map(|option| {
+        let mut key = OsString::from("-");
+        key.push(option.encode_utf8(&mut [0; 4]));
+        key
+      })
+      .collect(),
+    _ => vec![options],
+  }
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use super::*;
+
+  fn pairs(map: &ListOrderedMultimap<OsString, OsString>) -> Vec<(&str, &str)> {
+    map
+      .iter()
+      .map(|(key, value)| (key.to_str().unwrap(), value.to_str().unwrap()))
+      .collect()
+  }
+
+  #[test]
+  fn test_parse_args() {
+    let input = [
+      "a", "--flag", "b", "--flag=c", "--flag", "--empty=", "-xyz", "d", "-o=e=f", "-", "g", "-p",
+      "-",
+    ];
+    let (map, args) = parse_args(input);
+
+    assert_eq!(
+      pairs(&map),
+      [
+        ("", "a"),
+        ("--flag", "b"),
+        ("--flag", "c"),
+        ("--flag", ""),
+        ("--empty", ""),
+        ("-x", ""),
+        ("-y", ""),
+        ("-z", "d"),
+        ("-o", "e=f"),
+        ("", "-"),
+        ("", "g"),
+        ("-p", "-"),
+      ]
+    );
+    assert_eq!(args, input);
+  }
+
+  #[test]
+  fn test_parse_args_empty() {
+    let (map, args) = parse_args(Vec::<OsString>::new());
+    assert!(map.is_empty());
+    assert!(args.is_empty());
+  }
+
+  #[test]
+  fn test_parse_args_terminator() {
+    let input = ["--flag", "--", "--other", "--", "-x"];
+    let (map, args) = parse_args(input);
+
+    assert_eq!(
+      pairs(&map),
+      [
+        ("--flag", ""),
+        ("--", ""),
+        ("", "--other"),
+        ("", "--"),
+        ("", "-x")
+      ]
+    );
+    assert_eq!(args, input);
+
+    let (map, _) = parse_args(["--", "-o"]);
+    assert_eq!(pairs(&map), [("--", ""), ("", "-o")]);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_parse_args_non_unicode() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let invalid = |bytes: &[u8]| OsStr::from_bytes(bytes).to_os_string();
+    let (map, _) = parse_args([
+      invalid(b"--a\xff=b\xff"),
+      invalid(b"-x\xff"),
+      invalid(b"c\xff"),
+    ]);
+
+    let mut iter = map.iter();
+    assert_eq!(
+      iter.next(),
+      Some((&invalid(b"--a\xff"), &invalid(b"b\xff")))
+    );
+    assert_eq!(iter.next(), Some((&invalid(b"-x\xff"), &invalid(b"c\xff"))));
+    assert_eq!(iter.next(), None);
+  }
+
+  #[test]
+  fn test_parse_env() {
+    let map = parse_env(["A=1", "B", "A==2", "=C:=C:\\", "=", "C="]);
+
+    assert_eq!(
+      pairs(&map),
+      [
+        ("A", "1"),
+        ("B", ""),
+        ("A", "=2"),
+        ("=C:", "C:\\"),
+        ("=", ""),
+        ("C", ""),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_parse_env_empty() {
+    assert!(parse_env(Vec::<OsString>::new()).is_empty());
+  }
+}
@@ -0,0 +1,152 @@
+//! Provides import and export of CSV records grouped by a key column.
+//!
+//! [`ListOrderedMultimap::from_csv_reader`] groups the records of a CSV file by the value of one of their columns, for
+//! example the orders of each customer. The multimap keeps the global order of the records, so
+//! [`ListOrderedMultimap::write_csv`] writes them back in the order in which they were read, even after records have
+//! been looked up, modified or removed by key. The records are kept whole, including the key column.
+//!
+//! Both functions take a configured [`csv::Reader`] or [`csv::Writer`], so that delimiters, quoting and headers are
+//! controlled by the caller. The header record is not part of the multimap and has to be written separately.
+//!
+//! # Examples
+//!
+//! ```
+//! use csv::{Reader, StringRecord, Writer};
+//! use ordered_multimap::ListOrderedMultimap;
+//!
+//! let input = "order,customer,total\n1,alice,10\n2,bob,5\n3,alice,7\n";
+//! let mut reader = Reader::from_reader(input.as_bytes());
+//! let customer = reader.headers().unwrap().iter().position(|header| header == "customer").unwrap();
+//!
+//! let mut orders = ListOrderedMultimap::from_csv_reader(&mut reader, customer).unwrap();
+//! let alice = orders.get_all("alice").map(|record| &record[0]).collect::<Vec<_>>();
+//! assert_eq!(alice, ["1", "3"]);
+//!
+//! *orders.get_mut("bob").unwrap() = StringRecord::from(vec!["2", "bob", "6"]);
+//!
+//! let mut writer = Writer::from_writer(vec![]);
+//! writer.write_record(reader.headers().unwrap()).unwrap();
+//! orders.write_csv(&mut writer).unwrap();
+//! let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+//! assert_eq!(output, "order,customer,total\n1,alice,10\n2,bob,6\n3,alice,7\n");
+//! ```
+
+use alloc::{format, string::String};
+use std::io::{self, Read, Write};
+
+use csv::{Reader, StringRecord, Writer};
+
+use crate::ListOrderedMultimap;
+
+impl ListOrderedMultimap<String, StringRecord> {
+  /// Reads all records of the CSV reader into a multimap, keyed by the value of the column at index `key_column`. The
+  /// values of each key and the records overall are in the order in which they were read.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if reading a record fails or if a record has no column at index `key_column`.
+  pub fn from_csv_reader<R>(reader: &mut Reader<R>, key_column: usize) -> csv::Result<Self>
+  where
+    R: Read,
+  {
+    let mut map = ListOrderedMultimap::new();
+
+    for record in reader.records() {
+      let record = record?;
+      let Some(key) = record.get(key_column) else {
+        let line = record.position().map_or(0, csv::Position::line);
+        let message = format!("record on line {line} has no column {key_column}");
+        return Err(io::Error::new(io::ErrorKind::InvalidData, message).into());
+      };
+
+      let _ = map.append(key.into(), record);
+    }
+
+    Ok(map)
+  }
+}
+
+impl<Key, State> ListOrderedMultimap<Key, StringRecord, State> {
+  /// Writes all records to the CSV writer in global order.
+  ///
+  /// The header record is not written.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if writing a record fails.
+  pub fn write_csv<W>(&self, writer: &mut Writer<W>) -> csv::Result<()>
+  where
+    W: Write,
+  {
+    for record in self.values() {
+      writer.write_record(record)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+  }
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use csv::{ReaderBuilder, WriterBuilder};
+
+  use super::*;
+
+  fn read(input: &str) -> csv::Result<ListOrderedMultimap<String, StringRecord>> {
+    let mut reader = ReaderBuilder::new()
+      .has_headers(false)
+      .from_reader(input.as_bytes());
+    ListOrderedMultimap::from_csv_reader(&mut reader, 1)
+  }
+
+  fn write(map: &ListOrderedMultimap<String, StringRecord>) -> String {
+    let mut writer = WriterBuilder::new().from_writer(vec![]);
+    map.write_csv(&mut writer).unwrap();
+    String::from_utf8(writer.into_inner().unwrap()).unwrap()
+  }
+
+  #[test]
+  fn test_from_csv_reader() {
+    let map = read("1,a,x\n2,b,y\n3,a,\"z,z\"\n").unwrap();
+
+    assert_eq!(map.keys().collect::<Vec<_>>(), ["a", "b"]);
+    assert_eq!(
+      map
+        .get_all("a")
+        .map(|record| record.iter().collect::<Vec<_>>())
+        .collect::<Vec<_>>(),
+      [["1", "a", "x"], ["3", "a", "z,z"]]
+    );
+  }
+
+  #[test]
+  fn test_from_csv_reader_error() {
+    let mut reader = ReaderBuilder::new()
+      .flexible(true)
+      .has_headers(false)
+      .from_reader("1,a\n2\n".as_bytes());
+    let error = ListOrderedMultimap::from_csv_reader(&mut reader, 1).unwrap_err();
+    assert_eq!(error.to_string(), "record on line 2 has no column 1");
+
+    assert!(read("1,a\n2,b,c\n").is_err());
+  }
+
+  #[test]
+  fn test_write_csv() {
+    let input = "1,a,x\n2,b,y\n3,a,\"z,z\"\n4,c,w\n";
+    let mut map = read(input).unwrap();
+    assert_eq!(write(&map), input);
+
+    map.remove("c");
+    map.append("b".to_string(), StringRecord::from(vec!["5", "b", "v"]));
+    assert_eq!(write(&map), "1,a,x\n2,b,y\n3,a,\"z,z\"\n5,b,v\n");
+  }
+
+  #[test]
+  fn test_write_csv_empty() {
+    assert_eq!(write(&ListOrderedMultimap::new()), "");
+  }
+}
@@ -0,0 +1,882 @@
+//! Provides types related to computing the difference between two [`ListOrderedMultimap`]s.
+
+use alloc::{vec, vec::Vec};
+use core::hash::{BuildHasher, BuildHasherDefault, Hash};
+
+use hashbrown::HashMap;
+
+use crate::{list_ordered_multimap::FnvHasher, ListOrderedMultimap};
+
+/// A single operation of an edit script produced by [`ListOrderedMultimap::diff`].
+///
+/// Positions are indices into the global order of the multimap at the time the operation is applied, that is, after
+/// all previous operations of the script have been applied.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Edit<'map, Key, Value> {
+  /// The key-value pair is inserted such that it ends up at `position`.
+  Insert {
+    /// The position of the inserted key-value pair.
+    position: usize,
+
+    /// The key of the inserted key-value pair.
+    key: &'map Key,
+
+    /// The value of the inserted key-value pair.
+    value: &'map Value,
+  },
+
+  /// The key-value pair at `from` is removed and inserted again such that it ends up at `to`.
+  Move {
+    /// The position of the key-value pair before it is moved.
+    from: usize,
+
+    /// The position of the key-value pair after it is moved.
+    to: usize,
+
+    /// The key of the moved key-value pair.
+    key: &'map Key,
+
+    /// The value of the moved key-value pair.
+    value: &'map Value,
+  },
+
+  /// The key-value pair at `position` is removed.
+  Remove {
+    /// The position of the removed key-value pair.
+    position: usize,
+
+    /// The key of the removed key-value pair.
+    key: &'map Key,
+
+    /// The value of the removed key-value pair.
+    value: &'map Value,
+  },
+
+  /// The value of the key-value pair at `position` is changed in place.
+  Replace {
+    /// The position of the changed key-value pair.
+    position: usize,
+
+    /// The key of the changed key-value pair.
+    key: &'map Key,
+
+    /// The value before the change.
+    old_value: &'map Value,
+
+    /// The value after the change.
+    new_value: &'map Value,
+  },
+}
+
+impl<Key, Value> Clone for Edit<'_, Key, Value> {
+  fn clone(&self) -> Self {
+    *self
+  }
+}
+
+impl<Key, Value> Copy for Edit<'_, Key, Value> {}
+
+impl<Key, Value, State> ListOrderedMultimap<Key, Value, State>
+where
+  Key: Eq + Hash,
+  State: BuildHasher,
+  Value: PartialEq,
+{
+  /// Returns an edit script that transforms this multimap into `other`.
+  ///
+  /// The script consists of all [`Edit::Remove`] operations, followed by all [`Edit::Move`] operations, followed by all
+  /// [`Edit::Insert`] operations and lastly all [`Edit::Replace`] operations. Applying the operations in order to this
+  /// multimap results in a multimap equal to `other`, both in the global order and in the order of the values of each
+  /// key.
+  ///
+  /// Key-value pairs that are in both multimaps in the same relative order are left untouched. A removed and an
+  /// inserted key-value pair with the same key between the same untouched key-value pairs are reported as a replaced
+  /// value. A removed and an inserted key-value pair that are equal are reported as a move.
+  ///
+  /// The common key-value pairs at the start and the end of both multimaps are always left untouched. If more than 1024
+  /// key-value pairs in between would not be left untouched, the search for the untouched ones is given up. From then
+  /// on, none of the key-value pairs in between are left untouched: each of them is either replaced or moved when it
+  /// can be paired as described above, or else removed and inserted again, so the script is not minimal.
+  ///
+  /// Complexity: O((|V1| + |V2|) * D + M * log(|V1|) + P) where |V1| and |V2| are the number of values of both
+  /// multimaps, D is the number of key-value pairs that are not left untouched, but at most 1024, and M is the number of
+  /// moves. Removed and inserted key-value pairs are paired through buckets of the hashes of their keys, so P is
+  /// O(|V1| + |V2|) plus, for each key, the number of its removed key-value pairs times the number of its inserted
+  /// key-value pairs that are not replacements, as the values in a bucket are compared one by one. Once the search is
+  /// given up, D stays at 1024 but all key-value pairs between the common start and end are paired this way. The memory
+  /// used is O(|V1| + |V2| + D^2).
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::ListOrderedMultimap;
+  /// use ordered_multimap::diff::Edit;
+  ///
+  /// let mut old = ListOrderedMultimap::new();
+  /// old.append("a", 1);
+  /// old.append("b", 2);
+  /// old.append("c", 3);
+  ///
+  /// let mut new = ListOrderedMultimap::new();
+  /// new.append("a", 10);
+  /// new.append("c", 3);
+  /// new.append("b", 2);
+  /// new.append("a", 4);
+  ///
+  /// assert_eq!(
+  ///   old.diff(&new),
+  ///   [
+  ///     Edit::Move { from: 1, to: 2, key: &"b", value: &2 },
+  ///     Edit::Insert { position: 3, key: &"a", value: &4 },
+  ///     Edit::Replace { position: 0, key: &"a", old_value: &1, new_value: &10 },
+  ///   ]
+  /// );
+  /// ```
+  #[must_use]
+  pub fn diff<'map, OtherState>(
+    &'map self,
+    other: &'map ListOrderedMultimap<Key, Value, OtherState>,
+  ) -> Vec<Edit<'map, Key, Value>> {
+    let old: Vec<_> = self.iter().collect();
+    let new: Vec<_> = other.iter().collect();
+    let alignment = Alignment::new(&old, &new, |key| self.build_hasher.hash_one(key));
+    let mut edits = vec![];
+
+    // All removals first. The current order is then the surviving key-value pairs in their old order.
+    let mut current = vec![];
+    let mut removed = 0;
+
+    for (old_index, pairing) in alignment.old.iter().enumerate() {
+      match pairing {
+        Pairing::Removed => {
+          let (key, value) = old[old_index];
+          edits.push(Edit::Remove {
+            position: old_index - removed,
+            key,
+            value,
+          });
+          removed += 1;
+        }
+        Pairing::Kept(new_index) | Pairing::Replaced(new_index) => {
+          current.push((*new_index, true));
+        }
+        Pairing::Moved(new_index) => current.push((*new_index, false)),
+      }
+    }
+
+    // Moved key-value pairs are placed one at a time by ascending new position. The settled key-value pairs are always
+    // sorted by new position, so each moved key-value pair goes right before the first settled one that comes after it.
+    // As moved key-value pairs are placed by ascending new position, that is the first key-value pair that was settled
+    // from the start and comes after it, which is found with a cursor.
+    //
+    // Every key-value pair has a slot for where it starts, and every moved one also a slot for where it is placed. The
+    // slots are ordered such that the live slots are always in the current order: the moved key-value pairs placed
+    // before a settled one come right before its slot, by ascending new position. The number of live slots before a slot
+    // is its current position.
+    let mut moved: Vec<_> = current
+      .iter()
+      .enumerate()
+      .filter(|(_, &(_, settled))| !settled)
+      .map(|(from, &(new_index, _))| (new_index, from))
+      .collect();
+    moved.sort_unstable();
+
+    let mut targets = Vec::with_capacity(moved.len());
+    let mut target = 0;
+
+    for &(new_index, _) in &moved {
+      while target < current.len() && !(current[target].1 && current[target].0 > new_index) {
+        target += 1;
+      }
+
+      targets.push(target);
+    }
+
+    // The slot where the key-value pair at the given position starts.
+    let mut start_slots = Vec::with_capacity(current.len());
+    let mut placed_before = 0;
+
+    for position in 0..current.len() {
+      while placed_before < targets.len() && targets[placed_before] <= position {
+        placed_before += 1;
+      }
+
+      start_slots.push(position + placed_before);
+    }
+
+    let mut slots = Slots::new(current.len() + moved.len());
+
+    for &slot in &start_slots {
+      slots.insert(slot);
+    }
+
+    for (placed, (&(new_index, from), &target)) in moved.iter().zip(&targets).enumerate() {
+      let start_slot = start_slots[from];
+      let end_slot = target + placed;
+      let from = slots.position(start_slot);
+      slots.remove(start_slot);
+      let to = slots.position(end_slot);
+      slots.insert(end_slot);
+
+      let (key, value) = new[new_index];
+      edits.push(Edit::Move {
+        from,
+        to,
+        key,
+        value,
+      });
+    }
+
+    // All surviving key-value pairs are now in their new relative order, so inserting by ascending new position places
+    // every key-value pair at its final position.
+    for (new_index, old_index) in alignment.new.iter().enumerate() {
+      if old_index.is_none() {
+        let (key, value) = new[new_index];
+        edits.push(Edit::Insert {
+          position: new_index,
+          key,
+          value,
+        });
+      }
+    }
+
+    for (new_index, old_index) in alignment.new.iter().enumerate() {
+      if let Some(old_index) = old_index {
+        if let Pairing::Replaced(_) = alignment.old[*old_index] {
+          let (key, old_value) = old[*old_index];
+          let (_, new_value) = new[new_index];
+          edits.push(Edit::Replace {
+            position: new_index,
+            key,
+            old_value,
+            new_value,
+          });
+        }
+      }
+    }
+
+    edits
+  }
+}
+
+/// How a key-value pair of the old sequence relates to the new sequence.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Pairing {
+  /// The key-value pair is unchanged and keeps its relative order at the given new position.
+  Kept(usize),
+
+  /// The key-value pair was moved to the given new position.
+  Moved(usize),
+
+  /// The key-value pair is not in the new sequence.
+  Removed,
+
+  /// The value of the key-value pair was changed in place and is at the given new position.
+  Replaced(usize),
+}
+
+/// A pairing between the key-value pairs of an old and a new sequence.
+#[derive(Debug)]
+pub(crate) struct Alignment {
+  /// For each key-value pair of the new sequence, the position of the key-value pair in the old sequence it originates
+  /// from, if any.
+  pub(crate) new: Vec<Option<usize>>,
+
+  /// For each key-value pair of the old sequence, how it relates to the new sequence.
+  pub(crate) old: Vec<Pairing>,
+}
+
+impl Alignment {
+  /// Pairs the key-value pairs of the given sequences, using `hash_key` to hash their keys.
+  ///
+  /// The common subsequence found by [`longest_common_subsequence`] is kept. Of the remaining key-value pairs, a removed and an
+  /// inserted one with the same key between the same kept key-value pairs are paired as a replacement. Of the rest, a
+  /// removed and an inserted one that are equal are paired as a move.
+  #[must_use]
+  pub(crate) fn new<Key, Value, HashKey>(
+    old: &[(&Key, &Value)],
+    new: &[(&Key, &Value)],
+    hash_key: HashKey,
+  ) -> Alignment
+  where
+    HashKey: Fn(&Key) -> u64,
+    Key: Eq,
+    Value: PartialEq,
+  {
+    let mut alignment = Alignment {
+      new: vec![None; new.len()],
+      old: vec![Pairing::Removed; old.len()],
+    };
+
+    // The unpaired key-value pairs in each region between two kept key-value pairs, identified by the number of kept
+    // key-value pairs before them.
+    let mut removed: Vec<(usize, usize)> = vec![];
+    let mut inserted: Vec<(usize, usize)> = vec![];
+    let (mut old_index, mut new_index) = (0, 0);
+    let common = longest_common_subsequence(old, new);
+
+    for (region, &(old_end, new_end)) in common
+      .iter()
+      .chain(Some(&(old.len(), new.len())))
+      .enumerate()
+    {
+      removed.extend((old_index..old_end).map(|index| (region, index)));
+      inserted.extend((new_index..new_end).map(|index| (region, index)));
+
+      if old_end < old.len() {
+        alignment.old[old_end] = Pairing::Kept(new_end);
+        alignment.new[new_end] = Some(old_end);
+      }
+
+      old_index = old_end + 1;
+      new_index = new_end + 1;
+    }
+
+    // The inserted key-value pairs of each region by the hash of their key, in ascending new position, along with the
+    // number of them that can no longer be used as replacements.
+    let mut replacements: Buckets<(usize, u64), (usize, Vec<usize>)> = Buckets::default();
+
+    for &(region, new_index) in &inserted {
+      let key_hash = hash_key(new[new_index].0);
+      replacements
+        .entry((region, key_hash))
+        .or_default()
+        .1
+        .push(new_index);
+    }
+
+    // Replacements must not cross each other, otherwise the replaced key-value pairs would not keep their relative
+    // order. As the removed key-value pairs of a region are paired in ascending old position, inserted ones before the
+    // last replacement of the region can be passed over for good.
+    let mut last_replacement = None;
+
+    for &(region, old_index) in &removed {
+      let key = old[old_index].0;
+      let Some((used, candidates)) = replacements.get_mut(&(region, hash_key(key))) else {
+        continue;
+      };
+
+      if let Some((last_region, last_index)) = last_replacement {
+        if last_region == region {
+          while *used < candidates.len() && candidates[*used] <= last_index {
+            *used += 1;
+          }
+        }
+      }
+
+      let replacement = candidates[*used..]
+        .iter()
+        .position(|&new_index| new[new_index].0 == key);
+
+      if let Some(offset) = replacement {
+        let new_index = candidates[*used + offset];
+        *used += offset + 1;
+        alignment.old[old_index] = Pairing::Replaced(new_index);
+        alignment.new[new_index] = Some(old_index);
+        last_replacement = Some((region, new_index));
+      }
+    }
+
+    // The inserted key-value pairs that are not replacements by the hash of their key, in ascending new position.
+    let mut moves: Buckets<u64, Vec<usize>> = Buckets::default();
+
+    for &(_, new_index) in &inserted {
+      if alignment.new[new_index].is_none() {
+        moves
+          .entry(hash_key(new[new_index].0))
+          .or_default()
+          .push(new_index);
+      }
+    }
+
+    for &(_, old_index) in &removed {
+      if alignment.old[old_index] != Pairing::Removed {
+        continue;
+      }
+
+      let Some(candidates) = moves.get_mut(&hash_key(old[old_index].0)) else {
+        continue;
+      };
+
+      let moved = candidates
+        .iter()
+        .position(|&new_index| old[old_index] == new[new_index]);
+
+      if let Some(position) = moved {
+        let new_index = candidates.remove(position);
+        alignment.old[old_index] = Pairing::Moved(new_index);
+        alignment.new[new_index] = Some(old_index);
+      }
+    }
+
+    alignment
+  }
+}
+
+/// Key-value pairs grouped by the hash of their key, which was already computed with the hasher of a multimap.
+type Buckets<Bucket, Candidates> = HashMap<Bucket, Candidates, BuildHasherDefault<FnvHasher>>;
+
+/// A set of slots that keeps track of how many of them are occupied before a given slot.
+///
+/// This is a Fenwick tree over the occupancy of the slots, so all operations take O(log n) time where n is the number
+/// of slots.
+#[derive(Debug)]
+struct Slots {
+  /// The Fenwick tree, where the element at `index` counts the occupied slots in `index - (index & -index)..index`.
+  counts: Vec<usize>,
+}
+
+impl Slots {
+  /// Creates a set of the given number of unoccupied slots.
+  #[must_use]
+  fn new(length: usize) -> Slots {
+    Slots {
+      counts: vec![0; length + 1],
+    }
+  }
+
+  /// Occupies the given slot.
+  fn insert(&mut self, slot: usize) {
+    let mut index = slot + 1;
+
+    while index < self.counts.len() {
+      self.counts[index] += 1;
+      index += index & index.wrapping_neg();
+    }
+  }
+
+  /// Returns the number of occupied slots before the given slot.
+  #[must_use]
+  fn position(&self, slot: usize) -> usize {
+    let mut index = slot;
+    let mut position = 0;
+
+    while index > 0 {
+      position += self.counts[index];
+      index -= index & index.wrapping_neg();
+    }
+
+    position
+  }
+
+  /// Frees the given slot, which must be occupied.
+  fn remove(&mut self, slot: usize) {
+    let mut index = slot + 1;
+
+    while index < self.counts.len() {
+      self.counts[index] -= 1;
+      index += index & index.wrapping_neg();
+    }
+  }
+}
+
+/// The maximum number of differences for which [`myers`] searches for a longest common subsequence. Beyond it, the
+/// search would take too much time and memory, and no common subsequence is used at all.
+const MAX_DIFFERENCES: isize = 1024;
+
+/// Returns the positions of the elements of a common subsequence of both sequences in ascending order.
+///
+/// This is Myers' O((N + M) * D) difference algorithm where D is the number of elements that are not part of the
+/// longest common subsequence. The common prefix and suffix are always kept. If the remaining elements have more than
+/// [`MAX_DIFFERENCES`] differences, none of them are kept, so the subsequence is only the longest if D is at most
+/// [`MAX_DIFFERENCES`].
+#[must_use]
+fn longest_common_subsequence<Element>(old: &[Element], new: &[Element]) -> Vec<(usize, usize)>
+where
+  Element: PartialEq,
+{
+  let prefix = old
+    .iter()
+    .zip(new)
+    .take_while(|(old, new)| old == new)
+    .count();
+  let suffix = old[prefix..]
+    .iter()
+    .rev()
+    .zip(new[prefix..].iter().rev())
+    .take_while(|(old, new)| old == new)
+    .count();
+  let old_middle = &old[prefix..old.len() - suffix];
+  let new_middle = &new[prefix..new.len() - suffix];
+
+  let mut common: Vec<_> = (0..prefix).map(|index| (index, index)).collect();
+  common.extend(
+    myers(old_middle, new_middle)
+      .into_iter()
+      .map(|(old_index, new_index)| (old_index + prefix, new_index + prefix)),
+  );
+  common.extend((0..suffix).map(|index| (old.len() - suffix + index, new.len() - suffix + index)));
+  common
+}
+
+/// Myers' difference algorithm, see [`longest_common_subsequence`].
+#[must_use]
+fn myers<Element>(old: &[Element], new: &[Element]) -> Vec<(usize, usize)>
+where
+  Element: PartialEq,
+{
+  let (old_length, new_length) = (old.len() as isize, new.len() as isize);
+  let max = old_length + new_length;
+  let mut furthest = vec![0isize; 2 * max as usize + 2];
+  let mut trace = vec![];
+  let mut found = false;
+
+  // For each number of differences `d`, `furthest[k + max]` is the furthest position in the old sequence reached on
+  // diagonal `k`. Round `d` only reads the diagonals `-(d - 1)..=d - 1` written by the previous round, so only those are
+  // kept for backtracking, which bounds the memory by O(D^2).
+  'search: for d in 0..=max.min(MAX_DIFFERENCES) {
+    trace.push(furthest[(max - d + 1).min(max) as usize..(max + d) as usize].to_vec());
+
+    for k in (-d..=d).step_by(2) {
+      let index = (k + max) as usize;
+      let mut x = if k == -d || (k != d && furthest[index - 1] < furthest[index + 1]) {
+        furthest[index + 1]
+      } else {
+        furthest[index - 1] + 1
+      };
+      let mut y = x - k;
+
+      while x < old_length && y < new_length && old[x as usize] == new[y as usize] {
+        x += 1;
+        y += 1;
+      }
+
+      furthest[index] = x;
+
+      if x >= old_length && y >= new_length {
+        found = true;
+        break 'search;
+      }
+    }
+  }
+
+  if !found {
+    return vec![];
+  }
+
+  let mut common = vec![];
+  let (mut x, mut y) = (old_length, new_length);
+
+  for (d, diagonals) in trace.iter().enumerate().rev() {
+    let d = d as isize;
+    let k = x - y;
+    let (previous_x, previous_y) = if d == 0 {
+      (0, 0)
+    } else {
+      let furthest = |k: isize| diagonals[(k + d - 1) as usize];
+      let previous_k = if k == -d || (k != d && furthest(k - 1) < furthest(k + 1)) {
+        k + 1
+      } else {
+        k - 1
+      };
+      let previous_x = furthest(previous_k);
+      (previous_x, previous_x - previous_k)
+    };
+
+    while x > previous_x && y > previous_y {
+      x -= 1;
+      y -= 1;
+      common.push((x as usize, y as usize));
+    }
+
+    if d > 0 {
+      x = previous_x;
+      y = previous_y;
+    }
+  }
+
+  common.reverse();
+  common
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use super::*;
+
+  /// Applies the edit script to the key-value pairs of the multimap.
+  fn apply<'map>(
+    map: &'map ListOrderedMultimap<char, u32>,
+    edits: &[Edit<'map, char, u32>],
+  ) -> Vec<(&'map char, &'map u32)> {
+    let mut pairs: Vec<_> = map.iter().collect();
+
+    for edit in edits {
+      match *edit {
+        Edit::Insert {
+          position,
+          key,
+          value,
+        } => pairs.insert(position, (key, value)),
+        Edit::Move {
+          from,
+          to,
+          key,
+          value,
+        } => {
+          assert_eq!(pairs.remove(from), (key, value));
+          pairs.insert(to, (key, value));
+        }
+        Edit::Remove {
+          position,
+          key,
+          value,
+        } => assert_eq!(pairs.remove(position), (key, value)),
+        Edit::Replace {
+          position,
+          key,
+          old_value,
+          new_value,
+        } => {
+          assert_eq!(pairs[position], (key, old_value));
+          pairs[position].1 = new_value;
+        }
+      }
+    }
+
+    pairs
+  }
+
+  fn map(pairs: &[(char, u32)]) -> ListOrderedMultimap<char, u32> {
+    pairs.iter().copied().collect()
+  }
+
+  #[test]
+  fn test_edit_clone() {
+    let edit: Edit<'_, char, u32> = Edit::Remove {
+      position: 0,
+      key: &'a',
+      value: &1,
+    };
+
+    #[allow(clippy::clone_on_copy)]
+    let clone = edit.clone();
+    assert_eq!(clone, edit);
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_diff_empty() {
+    let empty = map(&[]);
+    let full = map(&[('a', 1), ('b', 2), ('a', 3)]);
+
+    assert!(empty.diff(&empty).is_empty());
+    assert!(full.diff(&full).is_empty());
+    assert_eq!(
+      full.diff(&empty),
+      [
+        Edit::Remove {
+          position: 0,
+          key: &'a',
+          value: &1
+        },
+        Edit::Remove {
+          position: 0,
+          key: &'b',
+          value: &2
+        },
+        Edit::Remove {
+          position: 0,
+          key: &'a',
+          value: &3
+        },
+      ]
+    );
+    assert_eq!(
+      empty.diff(&full),
+      [
+        Edit::Insert {
+          position: 0,
+          key: &'a',
+          value: &1
+        },
+        Edit::Insert {
+          position: 1,
+          key: &'b',
+          value: &2
+        },
+        Edit::Insert {
+          position: 2,
+          key: &'a',
+          value: &3
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_diff_move() {
+    let old = map(&[('a', 1), ('b', 2), ('a', 3)]);
+    let new = map(&[('a', 3), ('a', 1), ('b', 2)]);
+    let edits = old.diff(&new);
+
+    assert_eq!(
+      edits,
+      [Edit::Move {
+        from: 2,
+        to: 0,
+        key: &'a',
+        value: &3
+      }]
+    );
+    assert_eq!(apply(&old, &edits), new.iter().collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_diff_replace() {
+    let old = map(&[('a', 1), ('b', 2), ('a', 3)]);
+    let new = map(&[('a', 1), ('b', 20), ('a', 30)]);
+    let edits = old.diff(&new);
+
+    assert_eq!(
+      edits,
+      [
+        Edit::Replace {
+          position: 1,
+          key: &'b',
+          old_value: &2,
+          new_value: &20
+        },
+        Edit::Replace {
+          position: 2,
+          key: &'a',
+          old_value: &3,
+          new_value: &30
+        },
+      ]
+    );
+    assert_eq!(apply(&old, &edits), new.iter().collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_diff_roundtrip() {
+    type Pairs = &'static [(char, u32)];
+
+    let cases: &[(Pairs, Pairs)] = &[
+      (
+        &[('a', 1), ('b', 2), ('c', 3)],
+        &[('c', 3), ('b', 2), ('a', 1)],
+      ),
+      (
+        &[('a', 1), ('b', 2), ('a', 3), ('c', 4), ('b', 5)],
+        &[('b', 5), ('a', 3), ('d', 6), ('a', 1), ('c', 7)],
+      ),
+      (
+        &[('x', 1), ('x', 1), ('y', 2), ('x', 1)],
+        &[('y', 2), ('x', 1), ('y', 3), ('x', 1)],
+      ),
+      (
+        &[('a', 1), ('b', 2), ('c', 3), ('d', 4), ('e', 5)],
+        &[('e', 5), ('d', 4), ('z', 0), ('c', 3), ('b', 2), ('a', 1)],
+      ),
+      (&[('a', 1)], &[('b', 1)]),
+    ];
+
+    for &(old, new) in cases {
+      let old = map(old);
+      let new = map(new);
+
+      assert_eq!(apply(&old, &old.diff(&new)), new.iter().collect::<Vec<_>>());
+      assert_eq!(apply(&new, &new.diff(&old)), old.iter().collect::<Vec<_>>());
+    }
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_diff_disjoint() {
+    let old = (0..10_000)
+      .map(|value| ('a', value))
+      .collect::<ListOrderedMultimap<_, _>>();
+    let new = (0..10_000)
+      .map(|value| ('b', value))
+      .collect::<ListOrderedMultimap<_, _>>();
+    let edits = old.diff(&new);
+
+    assert_eq!(edits.len(), 20_000);
+    assert_eq!(apply(&old, &edits), new.iter().collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_diff_moves_past_max_differences() {
+    let old = (0..4_000)
+      .map(|value| (char::from_u32(u32::from('a') + value).unwrap(), value))
+      .collect::<ListOrderedMultimap<_, _>>();
+    let new = old
+      .iter()
+      .rev()
+      .map(|(&key, &value)| (key, value))
+      .collect::<ListOrderedMultimap<_, _>>();
+    let edits = old.diff(&new);
+
+    assert!(!edits
+      .iter()
+      .any(|edit| matches!(edit, Edit::Insert { .. } | Edit::Remove { .. })));
+    assert_eq!(apply(&old, &edits), new.iter().collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn test_alignment_hash_collisions() {
+    let old = map(&[('a', 1), ('b', 2), ('c', 3), ('a', 4), ('d', 5), ('b', 6)]);
+    let new = map(&[('b', 6), ('a', 10), ('c', 3), ('e', 7), ('b', 20), ('a', 1)]);
+    let old: Vec<_> = old.iter().collect();
+    let new: Vec<_> = new.iter().collect();
+
+    let hashed = Alignment::new(&old, &new, |&key| u64::from(key));
+    let colliding = Alignment::new(&old, &new, |_| 0);
+
+    assert_eq!(hashed.old, colliding.old);
+    assert_eq!(hashed.new, colliding.new);
+  }
+
+  #[test]
+  fn test_slots() {
+    let mut slots = Slots::new(5);
+    slots.insert(1);
+    slots.insert(3);
+    slots.insert(4);
+
+    assert_eq!(slots.position(0), 0);
+    assert_eq!(slots.position(2), 1);
+    assert_eq!(slots.position(4), 2);
+    assert_eq!(slots.position(5), 3);
+
+    slots.remove(3);
+    slots.insert(0);
+    assert_eq!(slots.position(4), 2);
+    assert_eq!(slots.position(5), 3);
+  }
+
+  #[test]
+  fn test_longest_common_subsequence() {
+    assert_eq!(
+      longest_common_subsequence(&[1, 2, 3, 4, 5], &[1, 3, 4, 6, 5]),
+      [(0, 0), (2, 1), (3, 2), (4, 4)]
+    );
+    assert_eq!(
+      longest_common_subsequence(&[1, 2, 3], &[4, 5, 6]),
+      Vec::<(usize, usize)>::new()
+    );
+    assert_eq!(longest_common_subsequence::<u32>(&[], &[]), []);
+    assert_eq!(
+      longest_common_subsequence(&[1, 2, 1, 2], &[2, 1, 2, 1]).len(),
+      3
+    );
+  }
+
+  #[test]
+  fn test_longest_common_subsequence_max_differences() {
+    // The sequences only have their first element of the old sequence in common, as the last one of the new sequence.
+    let sequences = |length: usize| {
+      let old = (0..length).collect::<Vec<_>>();
+      let mut new = (length..2 * length - 1).collect::<Vec<_>>();
+      new.push(0);
+      (old, new)
+    };
+
+    let length = MAX_DIFFERENCES as usize / 2;
+    let (old, new) = sequences(length);
+    assert_eq!(longest_common_subsequence(&old, &new), [(0, length - 1)]);
+
+    let (old, new) = sequences(length + 2);
+    assert_eq!(longest_common_subsequence(&old, &new), []);
+  }
+}
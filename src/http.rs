@@ -0,0 +1,202 @@
+//! Provides conversions between [`ListOrderedMultimap`] and the [`HeaderMap`] of the `http` crate.
+//!
+//! A [`HeaderMap`] keeps the order of the values of each header name, but not the order of the values across different
+//! header names. A [`ListOrderedMultimap`] keeps both, so it is suited for headers whose relative order matters, for
+//! example when forwarding or signing them. The conversions lose as little as possible:
+//!
+//!  - Converting a [`HeaderMap`] into a [`ListOrderedMultimap`] keeps everything the [`HeaderMap`] has. The values of each
+//!    header name are kept in order, and the header names are in the order in which the [`HeaderMap`] iterates over
+//!    them. The values of a header name are adjacent in the result, since a [`HeaderMap`] has no global interleaving.
+//!  - Converting a [`ListOrderedMultimap`] into a [`HeaderMap`] keeps the values of each header name in order but loses
+//!    the global interleaving of different header names. Converting the [`HeaderMap`] back groups the values of each
+//!    header name together. This conversion fails with [`MaxSizeReached`] if the [`HeaderMap`] cannot hold all values.
+//!
+//! Borrowing a [`HeaderMap`] as a `ListOrderedMultimap<&HeaderName, &T>` avoids cloning the headers, and converting
+//! from a `&ListOrderedMultimap` clones them into a new [`HeaderMap`] while leaving the multimap intact.
+//!
+//! # Examples
+//!
+//! ```
+//! use http::{header, HeaderMap, HeaderValue};
+//! use ordered_multimap::ListOrderedMultimap;
+//!
+//! let mut headers = ListOrderedMultimap::new();
+//! headers.append(header::VIA, HeaderValue::from_static("1.1 first"));
+//! headers.append(header::HOST, HeaderValue::from_static("example.com"));
+//! headers.append(header::VIA, HeaderValue::from_static("1.1 second"));
+//!
+//! let header_map = HeaderMap::try_from(&headers).unwrap();
+//! let via = header_map.get_all(header::VIA).iter().collect::<Vec<_>>();
+//! assert_eq!(via, ["1.1 first", "1.1 second"]);
+//!
+//! let converted: ListOrderedMultimap<_, _> = ListOrderedMultimap::from(header_map);
+//! assert_eq!(converted.keys().collect::<Vec<_>>(), [header::VIA, header::HOST]);
+//! ```
+
+use core::hash::BuildHasher;
+
+use http::{header::MaxSizeReached, HeaderMap, HeaderName};
+
+use crate::ListOrderedMultimap;
+
+impl<T, S> From<HeaderMap<T>> for ListOrderedMultimap<HeaderName, T, S>
+where
+  S: BuildHasher + Default,
+{
+  fn from(header_map: HeaderMap<T>) -> Self {
+    let mut map = ListOrderedMultimap::with_capacity_and_hasher(
+      header_map.keys_len(),
+      header_map.len(),
+      S::default(),
+    );
+    let mut previous_name = None;
+
+    for (name, value) in header_map {
+      // The name is only yielded for the first value of each header name.
+      if let Some(name) = name {
+        previous_name = Some(name);
+      }
+
+      let _ = map.append(previous_name.clone().unwrap(), value);
+    }
+
+    map
+  }
+}
+
+impl<'a, T, S> From<&'a HeaderMap<T>> for ListOrderedMultimap<&'a HeaderName, &'a T, S>
+where
+  S: BuildHasher + Default,
+{
+  fn from(header_map: &'a HeaderMap<T>) -> Self {
+    let mut map = ListOrderedMultimap::with_capacity_and_hasher(
+      header_map.keys_len(),
+      header_map.len(),
+      S::default(),
+    );
+
+    for (name, value) in header_map {
+      let _ = map.append(name, value);
+    }
+
+    map
+  }
+}
+
+impl<T, S> TryFrom<ListOrderedMultimap<HeaderName, T, S>> for HeaderMap<T> {
+  type Error = MaxSizeReached;
+
+  fn try_from(map: ListOrderedMultimap<HeaderName, T, S>) -> Result<Self, Self::Error> {
+    let mut header_map = HeaderMap::try_with_capacity(map.keys_len())?;
+
+    for (name, value) in map {
+      let _ = header_map.try_append(name, value)?;
+    }
+
+    Ok(header_map)
+  }
+}
+
+impl<T, S> TryFrom<&ListOrderedMultimap<HeaderName, T, S>> for HeaderMap<T>
+where
+  T: Clone,
+{
+  type Error = MaxSizeReached;
+
+  fn try_from(map: &ListOrderedMultimap<HeaderName, T, S>) -> Result<Self, Self::Error> {
+    let mut header_map = HeaderMap::try_with_capacity(map.keys_len())?;
+
+    for (name, value) in map {
+      let _ = header_map.try_append(name.clone(), value.clone())?;
+    }
+
+    Ok(header_map)
+  }
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use http::{header, HeaderValue};
+
+  use super::*;
+
+  fn headers() -> ListOrderedMultimap<HeaderName, HeaderValue> {
+    let mut map = ListOrderedMultimap::new();
+    map.append(header::VIA, HeaderValue::from_static("1"));
+    map.append(header::HOST, HeaderValue::from_static("2"));
+    map.append(header::VIA, HeaderValue::from_static("3"));
+    map.append(header::ACCEPT, HeaderValue::from_static("4"));
+    map
+  }
+
+  #[test]
+  fn test_from_header_map() {
+    let header_map = HeaderMap::try_from(headers()).unwrap();
+    let map: ListOrderedMultimap<_, _> = ListOrderedMultimap::from(header_map);
+
+    assert_eq!(
+      map
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.to_str().unwrap()))
+        .collect::<Vec<_>>(),
+      [("via", "1"), ("via", "3"), ("host", "2"), ("accept", "4")]
+    );
+  }
+
+  #[test]
+  fn test_from_header_map_empty() {
+    let map: ListOrderedMultimap<_, HeaderValue> = ListOrderedMultimap::from(HeaderMap::new());
+    assert!(map.is_empty());
+  }
+
+  #[test]
+  fn test_from_header_map_ref() {
+    let header_map = HeaderMap::try_from(&headers()).unwrap();
+    let map: ListOrderedMultimap<_, _> = ListOrderedMultimap::from(&header_map);
+
+    assert_eq!(
+      map
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.to_str().unwrap()))
+        .collect::<Vec<_>>(),
+      [("via", "1"), ("via", "3"), ("host", "2"), ("accept", "4")]
+    );
+  }
+
+  #[test]
+  fn test_try_from_list_ordered_multimap() {
+    let header_map = HeaderMap::try_from(headers()).unwrap();
+
+    assert_eq!(header_map.keys_len(), 3);
+    assert_eq!(header_map.len(), 4);
+    assert_eq!(
+      header_map.get_all(header::VIA).iter().collect::<Vec<_>>(),
+      ["1", "3"]
+    );
+    assert_eq!(header_map.get(header::HOST).unwrap(), "2");
+    assert_eq!(header_map.get(header::ACCEPT).unwrap(), "4");
+  }
+
+  #[test]
+  fn test_try_from_list_ordered_multimap_max_size() {
+    let map: ListOrderedMultimap<_, _> = (0..1 << 15)
+      .map(|index| {
+        let name = HeaderName::try_from(format!("x-{index}")).unwrap();
+        (name, HeaderValue::from_static(""))
+      })
+      .collect();
+
+    assert!(HeaderMap::try_from(&map).is_err());
+    assert!(HeaderMap::try_from(map).is_err());
+  }
+
+  #[test]
+  fn test_try_from_list_ordered_multimap_ref() {
+    let map = headers();
+    let header_map = HeaderMap::try_from(&map).unwrap();
+
+    assert_eq!(header_map, HeaderMap::try_from(map).unwrap());
+  }
+}
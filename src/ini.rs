@@ -0,0 +1,811 @@
+//! Provides parsing and writing of INI files that keeps duplicate sections, duplicate keys and the original order.
+//!
+//! An INI file is parsed into an [`Ini`], a multimap from each [`Section`] to a multimap of its key-value pairs. Entries
+//! before the first section header belong to the global section `None`. A section whose header occurs more than once is
+//! kept as multiple values of the section, and a key that occurs more than once in a section is kept as multiple values
+//! of the key.
+//!
+//! Comments and blank lines are not part of the [`Ini`]. They are kept in a [`Trivia`] side-table, which also remembers
+//! how each section header and entry was formatted, so that writing the parsed file reproduces it exactly. After the
+//! [`Ini`] has been modified, [`write()`] keeps the trivia and formatting of the sections and entries that still exist:
+//!
+//!  - Comments and blank lines stay before the section header or entry that followed them.
+//!  - An entry whose value changed keeps its indentation and the spacing around its `=`.
+//!  - New section headers and entries are written as `[section]` and `key = value`.
+//!  - The trivia of removed section headers and entries is dropped.
+//!
+//! Sections and entries are identified by their name and the number of previous occurrences of that name, for example
+//! the second `path` entry of the first `[core]` section. Removing one occurrence therefore moves the trivia of the
+//! following occurrences of the same name.
+//!
+//! Lines starting with `;` or `#` are comments. Whitespace around section names, keys and values is ignored, and
+//! everything after the first `=` of an entry is its value. The global section is always written first, since its
+//! entries cannot follow a section header.
+//!
+//! # Examples
+//!
+//! ```
+//! use ordered_multimap::ini;
+//!
+//! let input = "; remotes\n[remote]\nurl = a\n\n[remote]\nurl = b\n";
+//! let (mut ini, trivia) = ini::parse(input).unwrap();
+//!
+//! let remotes = ini.get_all(&Some("remote".to_string())).collect::<Vec<_>>();
+//! assert_eq!(remotes.len(), 2);
+//! assert_eq!(remotes[1].get("url").unwrap(), "b");
+//! assert_eq!(ini::to_string(&ini, &trivia).unwrap(), input);
+//!
+//! ini
+//!   .get_mut(&Some("remote".to_string()))
+//!   .unwrap()
+//!   .append("fetch".to_string(), "all".to_string());
+//! assert_eq!(
+//!   ini::to_string(&ini, &trivia).unwrap(),
+//!   "; remotes\n[remote]\nurl = a\nfetch = all\n\n[remote]\nurl = b\n"
+//! );
+//! ```
+
+use alloc::{
+  format,
+  string::{String, ToString},
+  vec,
+};
+use core::{
+  fmt::{self, Display, Formatter, Write},
+  mem,
+};
+use std::collections::HashMap;
+
+use crate::ListOrderedMultimap;
+
+/// The name of a section of an INI file, or `None` for the global section before the first section header.
+pub type Section = Option<String>;
+
+/// The sections of an INI file, each with its key-value pairs.
+pub type Ini = ListOrderedMultimap<Section, ListOrderedMultimap<String, String>>;
+
+/// Comments, blank lines and the original formatting of an INI file, which are not part of its [`Ini`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Trivia {
+  /// Whether the lines of the file were separated by `\r\n` instead of `\n`.
+  crlf: bool,
+
+  /// Whether the file ended with a line break.
+  final_line_break: bool,
+
+  /// The comments and blank lines before each section header or entry.
+  lines: ListOrderedMultimap<Anchor, String>,
+
+  /// The original line of each section header and entry.
+  originals: HashMap<Anchor, String>,
+}
+
+impl Trivia {
+  /// Creates trivia without comments, blank lines or formatting, as for a new file.
+  #[must_use]
+  pub fn new() -> Self {
+    Trivia {
+      crlf: false,
+      final_line_break: true,
+      lines: ListOrderedMultimap::new(),
+      originals: HashMap::new(),
+    }
+  }
+}
+
+impl Default for Trivia {
+  fn default() -> Self {
+    Trivia::new()
+  }
+}
+
+/// Identifies a section header or entry by its name and the number of previous occurrences of that name.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum Anchor {
+  /// The end of the file.
+  End,
+
+  /// An entry of a section.
+  Entry {
+    /// The section of the entry.
+    section: Section,
+
+    /// The number of previous occurrences of the section.
+    section_occurrence: usize,
+
+    /// The key of the entry.
+    key: String,
+
+    /// The number of previous occurrences of the key in the section.
+    key_occurrence: usize,
+  },
+
+  /// The header of a section.
+  Section {
+    /// The section.
+    section: Section,
+
+    /// The number of previous occurrences of the section.
+    occurrence: usize,
+  },
+}
+
+/// The error returned when parsing an INI file fails.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ParseError {
+  /// The line number of the invalid line, starting at 1.
+  line: usize,
+
+  /// The kind of error.
+  kind: ParseErrorKind,
+}
+
+impl ParseError {
+  /// Returns the kind of error.
+  #[must_use]
+  pub fn kind(&self) -> ParseErrorKind {
+    self.kind
+  }
+
+  /// Returns the line number of the invalid line, starting at 1.
+  #[must_use]
+  pub fn line(&self) -> usize {
+    self.line
+  }
+}
+
+impl Display for ParseError {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    write!(formatter, "invalid INI line {}: ", self.line)?;
+
+    match self.kind {
+      ParseErrorKind::MissingSeparator => write!(formatter, "expected `=` in entry"),
+      ParseErrorKind::UnterminatedSectionHeader => {
+        write!(formatter, "expected `]` after section name")
+      }
+    }
+  }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The kind of a [`ParseError`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ParseErrorKind {
+  /// A line that is not a comment, blank or a section header does not contain `=`.
+  MissingSeparator,
+
+  /// A section header does not end with `]`.
+  UnterminatedSectionHeader,
+}
+
+/// The error returned when writing an INI file fails.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct WriteError {
+  /// The index of the section in the global order of the [`Ini`], starting at 0, if the error is about a section.
+  section: Option<usize>,
+
+  /// The index of the entry in its section, starting at 0, if the error is about an entry.
+  entry: Option<usize>,
+
+  /// The kind of error.
+  kind: WriteErrorKind,
+}
+
+impl WriteError {
+  /// Returns the index of the entry in its section, starting at 0, or `None` if the error is about a section name or
+  /// the writer failed.
+  #[must_use]
+  pub fn entry(&self) -> Option<usize> {
+    self.entry
+  }
+
+  /// Returns the kind of error.
+  #[must_use]
+  pub fn kind(&self) -> WriteErrorKind {
+    self.kind
+  }
+
+  /// Returns the index of the section in the global order of the [`Ini`], starting at 0, or `None` if the writer
+  /// failed.
+  #[must_use]
+  pub fn section(&self) -> Option<usize> {
+    self.section
+  }
+}
+
+impl Display for WriteError {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    match (self.section, self.entry) {
+      (Some(section), Some(entry)) => {
+        write!(
+          formatter,
+          "cannot write INI entry {entry} of section {section}: "
+        )?;
+      }
+      (Some(section), None) => write!(formatter, "cannot write INI section {section}: ")?,
+      _ => write!(formatter, "cannot write INI file: ")?,
+    }
+
+    match self.kind {
+      WriteErrorKind::InvalidKey => write!(
+        formatter,
+        "key contains `=`, a line break or surrounding whitespace, or starts with `;`, `#` or `[`"
+      ),
+      WriteErrorKind::InvalidSectionName => write!(
+        formatter,
+        "section name contains `]`, a line break or surrounding whitespace"
+      ),
+      WriteErrorKind::InvalidValue => write!(
+        formatter,
+        "value contains a line break or surrounding whitespace"
+      ),
+      WriteErrorKind::Writer => write!(formatter, "writer failed"),
+    }
+  }
+}
+
+impl std::error::Error for WriteError {}
+
+/// The kind of a [`WriteError`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum WriteErrorKind {
+  /// The key contains `=`, `\r` or `\n`, starts with `;`, `#` or `[`, or starts or ends with whitespace. It would be
+  /// parsed as a different key, a comment or a section header.
+  InvalidKey,
+
+  /// The section name contains `]`, `\r` or `\n`, or starts or ends with whitespace. It would be parsed as a different
+  /// section name or end the header and inject other lines.
+  InvalidSectionName,
+
+  /// The value contains `\r` or `\n`, or starts or ends with whitespace. A line break would end the entry and could
+  /// inject other sections or entries, and the whitespace would be lost when parsing.
+  InvalidValue,
+
+  /// The writer returned an error.
+  Writer,
+}
+
+/// A parsed line of an INI file.
+enum Line<'a> {
+  /// A section header with the section name.
+  Header(&'a str),
+
+  /// An entry with its key, value and the byte offset of the value in the line.
+  Entry(&'a str, &'a str, usize),
+
+  /// A comment or blank line.
+  Trivia,
+}
+
+impl<'a> Line<'a> {
+  /// Parses a line without its line break.
+  fn parse(line: &'a str) -> Result<Self, ParseErrorKind> {
+    let trimmed = line.trim();
+
+    if trimmed.is_empty() || trimmed.starts_with(';') || trimmed.starts_with('#') {
+      Ok(Line::Trivia)
+    } else if let Some(header) = trimmed.strip_prefix('[') {
+      header
+        .strip_suffix(']')
+        .map(|name| Line::Header(name.trim()))
+        .ok_or(ParseErrorKind::UnterminatedSectionHeader)
+    } else {
+      let (key, value) = line
+        .split_once('=')
+        .ok_or(ParseErrorKind::MissingSeparator)?;
+      let value_start = line.len() - value.trim_start().len();
+      Ok(Line::Entry(key.trim(), value.trim(), value_start))
+    }
+  }
+}
+
+/// Counts the occurrences of sections and keys to create [`Anchor`]s.
+#[derive(Default)]
+struct Occurrences {
+  /// The number of occurrences of each key in the current section.
+  keys: HashMap<String, usize>,
+
+  /// The current section.
+  section: Section,
+
+  /// The number of previous occurrences of the current section.
+  section_occurrence: usize,
+
+  /// The number of occurrences of each section.
+  sections: HashMap<Section, usize>,
+}
+
+impl Occurrences {
+  /// Returns the anchor of the next entry with the given key in the current section.
+  fn entry_anchor(&mut self, key: &str) -> Anchor {
+    let occurrence = self.keys.entry(key.to_string()).or_default();
+    let key_occurrence = *occurrence;
+    *occurrence += 1;
+
+    Anchor::Entry {
+      section: self.section.clone(),
+      section_occurrence: self.section_occurrence,
+      key: key.to_string(),
+      key_occurrence,
+    }
+  }
+
+  /// Returns the anchor of the header of the current section.
+  fn section_anchor(&self) -> Anchor {
+    Anchor::Section {
+      section: self.section.clone(),
+      occurrence: self.section_occurrence,
+    }
+  }
+
+  /// Starts the next occurrence of the given section.
+  fn start_section(&mut self, section: &Section) {
+    let occurrence = self.sections.entry(section.clone()).or_default();
+    self.section.clone_from(section);
+    self.section_occurrence = *occurrence;
+    self.keys.clear();
+    *occurrence += 1;
+  }
+}
+
+/// Writes lines separated by line breaks.
+struct LineWriter<'writer, W> {
+  /// Whether no line has been written yet.
+  first: bool,
+
+  /// The line break between lines.
+  line_break: &'static str,
+
+  /// The writer to write the lines to.
+  writer: &'writer mut W,
+}
+
+impl<W> LineWriter<'_, W>
+where
+  W: Write,
+{
+  /// Writes a line, preceded by a line break unless it is the first line.
+  fn line(&mut self, line: &str) -> fmt::Result {
+    if !mem::take(&mut self.first) {
+      self.writer.write_str(self.line_break)?;
+    }
+
+    self.writer.write_str(line)
+  }
+
+  /// Writes the comments and blank lines before the anchor.
+  fn trivia(&mut self, trivia: &Trivia, anchor: &Anchor) -> fmt::Result {
+    for line in trivia.lines.get_all(anchor) {
+      self.line(line)?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Parses an INI file into its sections and the [`Trivia`] needed to write it back unchanged.
+///
+/// # Errors
+///
+/// Returns an error for a section header without a closing `]` and for a line that is neither a comment, blank, a
+/// section header nor an entry with `=`.
+///
+/// # Examples
+///
+/// ```
+/// use ordered_multimap::ini;
+///
+/// let (ini, _) = ini::parse("name = global\n[a]\nx = 1\nx = 2\n").unwrap();
+///
+/// assert_eq!(ini.get(&None).unwrap().get("name").unwrap(), "global");
+/// assert_eq!(
+///   ini
+///     .get(&Some("a".to_string()))
+///     .unwrap()
+///     .get_all("x")
+///     .collect::<Vec<_>>(),
+///   ["1", "2"]
+/// );
+///
+/// let error = ini::parse("[a]\nx\n").unwrap_err();
+/// assert_eq!(error.line(), 2);
+/// ```
+pub fn parse(input: &str) -> Result<(Ini, Trivia), ParseError> {
+  let mut ini = Ini::new();
+  let mut trivia = Trivia::new();
+  let mut occurrences = Occurrences::default();
+  let mut pending = vec![];
+
+  occurrences.start_section(&None);
+
+  for (index, line) in input.lines().enumerate() {
+    let parsed = Line::parse(line).map_err(|kind| ParseError {
+      line: index + 1,
+      kind,
+    })?;
+
+    let anchor = match parsed {
+      Line::Entry(key, value, _) => {
+        if ini.is_empty() {
+          let _ = ini.append(None, ListOrderedMultimap::new());
+        }
+
+        let (_, entries) = ini.back_mut().unwrap();
+        let _ = entries.append(key.to_string(), value.to_string());
+        occurrences.entry_anchor(key)
+      }
+      Line::Header(name) => {
+        let section = Some(name.to_string());
+        occurrences.start_section(&section);
+        let _ = ini.append(section, ListOrderedMultimap::new());
+        occurrences.section_anchor()
+      }
+      Line::Trivia => {
+        pending.push(line);
+        continue;
+      }
+    };
+
+    for line in pending.drain(..) {
+      let _ = trivia.lines.append(anchor.clone(), line.to_string());
+    }
+
+    mem::drop(trivia.originals.insert(anchor, line.to_string()));
+  }
+
+  for line in pending {
+    let _ = trivia.lines.append(Anchor::End, line.to_string());
+  }
+
+  trivia.crlf = input.contains("\r\n");
+  trivia.final_line_break = input.is_empty() || input.ends_with('\n');
+  Ok((ini, trivia))
+}
+
+/// Writes the sections in the order of the multimap, using the [`Trivia`] of the parsed file for comments, blank lines
+/// and formatting.
+///
+/// All section names, keys and values are checked before anything is written, so that parsing the output yields the
+/// same [`Ini`].
+///
+/// # Errors
+///
+/// Returns an error if a section name, key or value cannot be written such that it is parsed back unchanged, see
+/// [`WriteErrorKind`], or if the writer fails.
+///
+/// # Examples
+///
+/// ```
+/// use ordered_multimap::{ini, ListOrderedMultimap};
+///
+/// let mut ini = ini::Ini::new();
+/// let mut section = ListOrderedMultimap::new();
+/// section.append("x".to_string(), "1".to_string());
+/// ini.append(Some("a".to_string()), section);
+///
+/// let mut output = String::new();
+/// ini::write(&mut output, &ini, &ini::Trivia::new()).unwrap();
+/// assert_eq!(output, "[a]\nx = 1\n");
+///
+/// ini.append(
+///   Some("b".to_string()),
+///   ListOrderedMultimap::from_iter([("y".to_string(), "2\n[evil]".to_string())]),
+/// );
+/// assert!(ini::write(&mut String::new(), &ini, &ini::Trivia::new()).is_err());
+/// ```
+pub fn write<W>(writer: &mut W, ini: &Ini, trivia: &Trivia) -> Result<(), WriteError>
+where
+  W: Write,
+{
+  check(ini)?;
+  write_lines(writer, ini, trivia).map_err(|_| WriteError {
+    section: None,
+    entry: None,
+    kind: WriteErrorKind::Writer,
+  })
+}
+
+/// Checks that all section names, keys and values are parsed back unchanged when written.
+fn check(ini: &Ini) -> Result<(), WriteError> {
+  for (section_index, (section, entries)) in ini.iter().enumerate() {
+    if let Some(name) = section {
+      if name.contains([']', '\r', '\n']) || name.trim() != name {
+        return Err(WriteError {
+          section: Some(section_index),
+          entry: None,
+          kind: WriteErrorKind::InvalidSectionName,
+        });
+      }
+    }
+
+    for (entry_index, (key, value)) in entries.iter().enumerate() {
+      let kind =
+        if key.contains(['=', '\r', '\n']) || key.starts_with([';', '#', '[']) || key.trim() != key
+        {
+          WriteErrorKind::InvalidKey
+        } else if value.contains(['\r', '\n']) || value.trim() != value {
+          WriteErrorKind::InvalidValue
+        } else {
+          continue;
+        };
+
+      return Err(WriteError {
+        section: Some(section_index),
+        entry: Some(entry_index),
+        kind,
+      });
+    }
+  }
+
+  Ok(())
+}
+
+/// Writes the sections after they have been checked.
+fn write_lines<W>(writer: &mut W, ini: &Ini, trivia: &Trivia) -> fmt::Result
+where
+  W: Write,
+{
+  let mut lines = LineWriter {
+    first: true,
+    line_break: if trivia.crlf { "\r\n" } else { "\n" },
+    writer,
+  };
+  let mut occurrences = Occurrences::default();
+  let global = ini.iter().filter(|(section, _)| section.is_none());
+  let named = ini.iter().filter(|(section, _)| section.is_some());
+
+  for (section, entries) in global.chain(named) {
+    occurrences.start_section(section);
+
+    if let Some(name) = section {
+      let anchor = occurrences.section_anchor();
+      lines.trivia(trivia, &anchor)?;
+
+      match trivia.originals.get(&anchor) {
+        Some(original) if matches!(Line::parse(original), Ok(Line::Header(original_name)) if original_name == name) =>
+        {
+          lines.line(original)?;
+        }
+        _ => lines.line(&format!("[{name}]"))?,
+      }
+    }
+
+    for (key, value) in entries {
+      let anchor = occurrences.entry_anchor(key);
+      lines.trivia(trivia, &anchor)?;
+
+      match trivia
+        .originals
+        .get(&anchor)
+        .map(|original| (original, Line::parse(original)))
+      {
+        Some((original, Ok(Line::Entry(original_key, original_value, value_start))))
+          if original_key == key =>
+        {
+          if original_value == value {
+            lines.line(original)?;
+          } else {
+            lines.line(&format!("{}{value}", &original[..value_start]))?;
+          }
+        }
+        _ => lines.line(&format!("{key} = {value}"))?,
+      }
+    }
+  }
+
+  lines.trivia(trivia, &Anchor::End)?;
+
+  if trivia.final_line_break && !lines.first {
+    lines.writer.write_str(lines.line_break)?;
+  }
+
+  Ok(())
+}
+
+/// Writes the sections in the order of the multimap into a string, see [`write()`].
+///
+/// # Errors
+///
+/// Returns an error if a section name, key or value cannot be written such that it is parsed back unchanged, see
+/// [`WriteErrorKind`].
+pub fn to_string(ini: &Ini, trivia: &Trivia) -> Result<String, WriteError> {
+  let mut output = String::new();
+  write(&mut output, ini, trivia)?;
+  Ok(output)
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use super::*;
+
+  fn section(name: &str) -> Section {
+    Some(name.to_string())
+  }
+
+  #[test]
+  fn test_parse() {
+    let (ini, _) = parse("a = 1\n[s]\nb=2\nb = 3 \n[t]\n[s]\n c = x = y\n").unwrap();
+
+    let sections = ini
+      .iter()
+      .map(|(section, entries)| {
+        let entries = entries
+          .iter()
+          .map(|(key, value)| (key.as_str(), value.as_str()))
+          .collect::<Vec<_>>();
+        (section.as_deref(), entries)
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(
+      sections,
+      [
+        (None, vec![("a", "1")]),
+        (Some("s"), vec![("b", "2"), ("b", "3")]),
+        (Some("t"), vec![]),
+        (Some("s"), vec![("c", "x = y")]),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_parse_empty() {
+    let (ini, trivia) = parse("").unwrap();
+    assert!(ini.is_empty());
+    assert_eq!(to_string(&ini, &trivia).unwrap(), "");
+  }
+
+  #[test]
+  fn test_parse_error() {
+    let error = parse("[s]\n; comment\nkey\n").unwrap_err();
+    assert_eq!(error.line(), 3);
+    assert_eq!(error.kind(), ParseErrorKind::MissingSeparator);
+    assert_eq!(
+      error.to_string(),
+      "invalid INI line 3: expected `=` in entry"
+    );
+
+    let error = parse("[s").unwrap_err();
+    assert_eq!(error.line(), 1);
+    assert_eq!(error.kind(), ParseErrorKind::UnterminatedSectionHeader);
+    assert_eq!(
+      error.to_string(),
+      "invalid INI line 1: expected `]` after section name"
+    );
+  }
+
+  #[test]
+  fn test_round_trip() {
+    let inputs = [
+      "; header comment\n\nname=global\n\n# section\n[ first ]\n  key   =  value  \nkey=\n\n[second]\n; trailing\n",
+      "[a]\r\nx = 1\r\n\r\n[a]\r\nx = 2",
+      "; only a comment",
+      "\n\n",
+    ];
+
+    for input in inputs {
+      let (ini, trivia) = parse(input).unwrap();
+      assert_eq!(to_string(&ini, &trivia).unwrap(), input);
+    }
+  }
+
+  #[test]
+  fn test_write_modified() {
+    let input = "; global\nname = x\n\n[s]\n; first\n  a =  1\n; second\na = 2\n\n[s]\n; other\na = 3\n; end\n";
+    let (mut ini, trivia) = parse(input).unwrap();
+
+    let first = ini.get_mut(&section("s")).unwrap();
+    *first.get_mut("a").unwrap() = "10".to_string();
+    first.append("b".to_string(), "new".to_string());
+    ini.get_mut(&None).unwrap().remove("name");
+    ini.append(section("t"), ListOrderedMultimap::new());
+
+    assert_eq!(
+      to_string(&ini, &trivia).unwrap(),
+      "\n[s]\n; first\n  a =  10\n; second\na = 2\nb = new\n\n[s]\n; other\na = 3\n[t]\n; end\n"
+    );
+  }
+
+  #[test]
+  fn test_write_global_first() {
+    let mut ini = Ini::new();
+    let mut entries = ListOrderedMultimap::new();
+    entries.append("a".to_string(), "1".to_string());
+    ini.append(section("s"), entries.clone());
+    ini.append(None, entries);
+
+    assert_eq!(
+      to_string(&ini, &Trivia::new()).unwrap(),
+      "a = 1\n[s]\na = 1\n"
+    );
+  }
+
+  #[test]
+  fn test_write_renamed() {
+    let (mut ini, trivia) = parse("[ s ]\n a = 1\n").unwrap();
+    let entries = ini.remove(&section("s")).unwrap();
+    let mut renamed = ListOrderedMultimap::new();
+    renamed.append("b".to_string(), entries.get("a").unwrap().clone());
+    ini.append(section("s"), renamed);
+
+    assert_eq!(to_string(&ini, &trivia).unwrap(), "[ s ]\nb = 1\n");
+
+    let (ini, _) = parse("[ s ]\n a = 1\n").unwrap();
+    let mut renamed = Ini::new();
+    renamed.append(section("u"), ini.get(&section("s")).unwrap().clone());
+    assert_eq!(to_string(&renamed, &trivia).unwrap(), "[u]\na = 1\n");
+  }
+
+  #[test]
+  fn test_write_error() {
+    let cases = [
+      (None, "x", "1\n[evil]\nx = 1", WriteErrorKind::InvalidValue),
+      (None, "x", " 1", WriteErrorKind::InvalidValue),
+      (None, "x", "1 ", WriteErrorKind::InvalidValue),
+      (section("s"), "x = y", "1", WriteErrorKind::InvalidKey),
+      (section("s"), "; x", "1", WriteErrorKind::InvalidKey),
+      (section("s"), "# x", "1", WriteErrorKind::InvalidKey),
+      (section("s"), "[x]", "1", WriteErrorKind::InvalidKey),
+      (section("s"), "x\ny", "1", WriteErrorKind::InvalidKey),
+      (section("s"), " x", "1", WriteErrorKind::InvalidKey),
+      (section("s]"), "x", "1", WriteErrorKind::InvalidSectionName),
+      (
+        section("s\n[evil"),
+        "x",
+        "1",
+        WriteErrorKind::InvalidSectionName,
+      ),
+      (section(" s"), "x", "1", WriteErrorKind::InvalidSectionName),
+    ];
+
+    for (name, key, value, kind) in cases {
+      let (mut ini, trivia) = parse("a = 1\n[t]\nb = 2\n").unwrap();
+      let mut entries = ListOrderedMultimap::new();
+      entries.append("c".to_string(), "3".to_string());
+      entries.append(key.to_string(), value.to_string());
+      ini.append(name, entries);
+
+      let mut output = String::new();
+      let error = write(&mut output, &ini, &trivia).unwrap_err();
+      assert_eq!(error.kind(), kind);
+      assert_eq!(error.section(), Some(2));
+      assert_eq!(
+        error.entry(),
+        (kind != WriteErrorKind::InvalidSectionName).then_some(1)
+      );
+      assert_eq!(output, "");
+      assert_eq!(to_string(&ini, &trivia), Err(error));
+    }
+
+    let mut ini = Ini::new();
+    ini.append(section("s]"), ListOrderedMultimap::new());
+    assert_eq!(
+      to_string(&ini, &Trivia::new()).unwrap_err().to_string(),
+      "cannot write INI section 0: section name contains `]`, a line break or surrounding whitespace"
+    );
+
+    let mut entries = ListOrderedMultimap::new();
+    entries.append("x".to_string(), "1\n".to_string());
+    let ini = Ini::from_iter([(None, entries)]);
+    assert_eq!(
+      to_string(&ini, &Trivia::new()).unwrap_err().to_string(),
+      "cannot write INI entry 0 of section 0: value contains a line break or surrounding whitespace"
+    );
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+      fn write_str(&mut self, _: &str) -> fmt::Result {
+        Err(fmt::Error)
+      }
+    }
+
+    let (ini, trivia) = parse("a = 1\n").unwrap();
+    let error = write(&mut FailingWriter, &ini, &trivia).unwrap_err();
+    assert_eq!(error.kind(), WriteErrorKind::Writer);
+    assert_eq!(error.section(), None);
+    assert_eq!(error.to_string(), "cannot write INI file: writer failed");
+  }
+}
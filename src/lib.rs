@@ -8,9 +8,42 @@
 
 extern crate alloc;
 
+pub mod diff;
 pub mod list_ordered_multimap;
+pub mod merge;
+pub mod observer;
+pub mod patch;
 
 pub use self::list_ordered_multimap::ListOrderedMultimap;
 
+#[cfg(feature = "arbitrary")]
+pub mod arbitrary;
+#[cfg(feature = "args")]
+pub mod args;
+#[cfg(feature = "csv")]
+pub mod csv;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "ini")]
+pub mod ini;
+#[cfg(feature = "multipart")]
+pub mod multipart;
+#[cfg(feature = "proptest")]
+pub mod proptest;
+#[cfg(feature = "rayon")]
+pub mod rayon;
+#[cfg(feature = "rfc5322")]
+pub mod rfc5322;
 #[cfg(feature = "serde")]
-mod serde;
+pub mod serde;
+#[cfg(feature = "urlencoded")]
+pub mod urlencoded;
+
+#[cfg(all(test, not(feature = "serde")))]
+use postcard as _;
+#[cfg(all(test, not(feature = "serde")))]
+use serde as _;
+#[cfg(all(test, not(feature = "serde")))]
+use serde_json as _;
+#[cfg(all(test, not(feature = "serde")))]
+use serde_test as _;
@@ -2,22 +2,28 @@
 
 #![allow(unsafe_code)]
 
-use alloc::vec;
+use alloc::{sync::Arc, vec, vec::Vec};
 use core::{
   borrow::Borrow,
+  cmp::Ordering,
   fmt::{self, Debug, Formatter},
-  hash::{BuildHasher, Hash, Hasher},
+  hash::{BuildHasher, BuildHasherDefault, Hash, Hasher},
   iter::FusedIterator,
   marker::PhantomData,
   mem,
+  ops::{Deref, DerefMut},
+  ptr,
+  sync::atomic::{self, AtomicPtr},
 };
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
 
 use dlv_list::{
   Index, IntoIter as VecListIntoIter, Iter as VecListIter, IterMut as VecListIterMut, VecList,
 };
 use hashbrown::{
   hash_map::{RawEntryMut, RawOccupiedEntryMut},
-  HashMap,
+  HashMap, HashSet,
 };
 
 /// A random state to use for the hashmap in the multimap.
@@ -69,22 +75,39 @@ impl BuildHasher for RandomState {
 /// # Allocations
 ///
 /// Allocations may be performed on any key-value insertion.
+///
+/// # Sharing
+///
+/// The keys, values and internal index are stored behind an [`Arc`], so cloning a multimap is O(1) and the clones share
+/// their storage. The storage is copied on the first mutation of a multimap whose storage is shared, which costs
+/// O(|K| + |V|) once, after which the multimap owns its storage again. Methods that take `&mut self` count as mutations
+/// even if they end up not changing anything. As with an [`Arc`], a multimap is only [`Send`] and [`Sync`] if its keys
+/// and values are both [`Send`] and [`Sync`].
+///
+/// Cloning records the function that copies the storage in the shared allocation, so Clippy's `mutable_key_type` lint
+/// considers multimaps to have interior mutability when they are used as keys. This does not affect hashing or
+/// equality.
+///
+/// ```
+/// use ordered_multimap::ListOrderedMultimap;
+///
+/// let mut map = ListOrderedMultimap::new();
+/// map.append("key", "value1");
+///
+/// let snapshot = map.clone();
+/// map.append("key", "value2");
+///
+/// assert_eq!(snapshot.entry_len(&"key"), 1);
+/// assert_eq!(map.entry_len(&"key"), 2);
+/// ```
 pub struct ListOrderedMultimap<Key, Value, State = RandomState> {
   /// The hasher builder that constructs new hashers for hashing keys. We have to keep this separate from the hashmap
   /// itself as we need to be able to access it when the hashmap keys are reallocated due to changes. We cannot use the
   /// hash of the actual keys in the map as those hashes are not representative.
   pub(crate) build_hasher: State,
 
-  /// The list of the keys in the multimap. This is ordered by time of insertion.
-  pub(crate) keys: VecList<Key>,
-
-  /// The map from indices of keys to the indices of their values in the value list. The list of the indices is ordered
-  /// by time of insertion. We never use hasher of the hashmap explicitly here, we instead use
-  /// [`ListOrderedMultimap::build_hasher`].
-  pub(crate) map: HashMap<Index<Key>, MapEntry<Key, Value>, DummyState>,
-
-  /// The list of the values in the multimap. This is ordered by time of insertion.
-  pub(crate) values: VecList<ValueEntry<Key, Value>>,
+  /// The keys, values and the index between them, which may be shared with clones of this multimap.
+  pub(crate) storage: SharedStorage<Key, Value>,
 }
 
 #[cfg(feature = "std")]
@@ -104,9 +127,7 @@ impl<Key, Value> ListOrderedMultimap<Key, Value, RandomState> {
   pub fn new() -> ListOrderedMultimap<Key, Value, RandomState> {
     ListOrderedMultimap {
       build_hasher: RandomState::new(),
-      keys: VecList::new(),
-      map: HashMap::with_hasher(DummyState),
-      values: VecList::new(),
+      storage: SharedStorage::new(Storage::with_capacity(0, 0)),
     }
   }
 
@@ -135,9 +156,7 @@ impl<Key, Value> ListOrderedMultimap<Key, Value, RandomState> {
   ) -> ListOrderedMultimap<Key, Value, RandomState> {
     ListOrderedMultimap {
       build_hasher: RandomState::new(),
-      keys: VecList::with_capacity(key_capacity),
-      map: HashMap::with_capacity_and_hasher(key_capacity, DummyState),
-      values: VecList::with_capacity(value_capacity),
+      storage: SharedStorage::new(Storage::with_capacity(key_capacity, value_capacity)),
     }
   }
 }
@@ -175,9 +194,7 @@ where
   ) -> ListOrderedMultimap<Key, Value, State> {
     ListOrderedMultimap {
       build_hasher: state,
-      keys: VecList::with_capacity(key_capacity),
-      map: HashMap::with_capacity_and_hasher(key_capacity, DummyState),
-      values: VecList::with_capacity(value_capacity),
+      storage: SharedStorage::new(Storage::with_capacity(key_capacity, value_capacity)),
     }
   }
 
@@ -201,9 +218,7 @@ where
   pub fn with_hasher(state: State) -> ListOrderedMultimap<Key, Value, State> {
     ListOrderedMultimap {
       build_hasher: state,
-      keys: VecList::new(),
-      map: HashMap::with_hasher(DummyState),
-      values: VecList::new(),
+      storage: SharedStorage::new(Storage::with_capacity(0, 0)),
     }
   }
 }
@@ -268,9 +283,15 @@ impl<Key, Value, State> ListOrderedMultimap<Key, Value, State> {
   /// assert_eq!(map.values_len(), 0);
   /// ```
   pub fn clear(&mut self) {
-    self.keys.clear();
-    self.map.clear();
-    self.values.clear();
+    if self.storage.is_shared() {
+      self.storage = SharedStorage::new(Storage::with_capacity(0, 0));
+      return;
+    }
+
+    let storage = self.storage.get_mut();
+    storage.keys.clear();
+    storage.map.clear();
+    storage.values.clear();
   }
 
   /// Returns an immutable reference to the first key-value pair in the multimap
@@ -313,6 +334,45 @@ impl<Key, Value, State> ListOrderedMultimap<Key, Value, State> {
     self.iter_mut().next()
   }
 
+  /// Feeds the key-value pairs of the multimap in order into the given hasher.
+  ///
+  /// This is what the [`Hash`] implementation of the multimap does, and is consistent with its [`PartialEq`]
+  /// implementation, which takes the interleaving of the values of different keys into account. See
+  /// [`ListOrderedMultimap::hash_per_key`] and [`ListOrderedMultimap::hash_unordered`] for the other equality modes.
+  ///
+  /// Complexity: O(|V|)
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use std::collections::hash_map::DefaultHasher;
+  /// use std::hash::Hasher;
+  ///
+  /// use ordered_multimap::ListOrderedMultimap;
+  ///
+  /// let map: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("a", 3)].into_iter().collect();
+  /// let other: ListOrderedMultimap<_, _> = [("b", 2), ("a", 1), ("a", 3)].into_iter().collect();
+  ///
+  /// let mut hasher = DefaultHasher::new();
+  /// map.hash_interleaved(&mut hasher);
+  /// let mut other_hasher = DefaultHasher::new();
+  /// other.hash_interleaved(&mut other_hasher);
+  /// assert_ne!(hasher.finish(), other_hasher.finish());
+  /// ```
+  pub fn hash_interleaved<H>(&self, state: &mut H)
+  where
+    H: Hasher,
+    Key: Hash,
+    Value: Hash,
+  {
+    state.write_usize(self.values_len());
+
+    for (key, value) in self.iter() {
+      key.hash(state);
+      value.hash(state);
+    }
+  }
+
   /// Returns a reference to the multimap's [`BuildHasher`].
   ///
   /// # Examples
@@ -346,7 +406,7 @@ impl<Key, Value, State> ListOrderedMultimap<Key, Value, State> {
   /// ```
   #[must_use]
   pub fn is_empty(&self) -> bool {
-    self.keys.is_empty()
+    self.storage.keys.is_empty()
   }
 
   /// Returns an iterator that yields immutable references to all key-value pairs in the multimap by insertion order.
@@ -373,8 +433,8 @@ impl<Key, Value, State> ListOrderedMultimap<Key, Value, State> {
   #[must_use]
   pub fn iter(&self) -> Iter<'_, Key, Value> {
     Iter {
-      keys: &self.keys,
-      iter: self.values.iter(),
+      keys: &self.storage.keys,
+      iter: self.storage.values.iter(),
     }
   }
 
@@ -409,9 +469,10 @@ impl<Key, Value, State> ListOrderedMultimap<Key, Value, State> {
   /// ```
   #[must_use]
   pub fn iter_mut(&mut self) -> IterMut<'_, Key, Value> {
+    let storage = self.storage.get_mut();
     IterMut {
-      keys: &self.keys,
-      iter: self.values.iter_mut(),
+      keys: &storage.keys,
+      iter: storage.values.iter_mut(),
     }
   }
 
@@ -438,7 +499,7 @@ impl<Key, Value, State> ListOrderedMultimap<Key, Value, State> {
   /// ```
   #[must_use]
   pub fn keys(&self) -> Keys<'_, Key> {
-    Keys(self.keys.iter())
+    Keys(self.storage.keys.iter())
   }
 
   /// Returns the number of keys the multimap can hold without reallocating.
@@ -458,7 +519,7 @@ impl<Key, Value, State> ListOrderedMultimap<Key, Value, State> {
   /// ```
   #[must_use]
   pub fn keys_capacity(&self) -> usize {
-    self.keys.capacity()
+    self.storage.keys.capacity()
   }
 
   /// Returns the number of keys in the multimap.
@@ -478,7 +539,7 @@ impl<Key, Value, State> ListOrderedMultimap<Key, Value, State> {
   /// ```
   #[must_use]
   pub fn keys_len(&self) -> usize {
-    self.keys.len()
+    self.storage.keys.len()
   }
 
   /// Returns an iterator that yields immutable references to keys and all associated values with those keys as separate
@@ -506,10 +567,10 @@ impl<Key, Value, State> ListOrderedMultimap<Key, Value, State> {
   pub fn pairs(&self) -> KeyValues<'_, Key, Value, State> {
     KeyValues {
       build_hasher: &self.build_hasher,
-      keys: &self.keys,
-      iter: self.keys.iter(),
-      map: &self.map,
-      values: &self.values,
+      keys: &self.storage.keys,
+      iter: self.storage.keys.iter(),
+      map: &self.storage.map,
+      values: &self.storage.values,
     }
   }
 
@@ -537,12 +598,13 @@ impl<Key, Value, State> ListOrderedMultimap<Key, Value, State> {
   /// ```
   #[must_use]
   pub fn pairs_mut(&mut self) -> KeyValuesMut<'_, Key, Value, State> {
+    let storage = self.storage.get_mut();
     KeyValuesMut {
       build_hasher: &self.build_hasher,
-      keys: &self.keys,
-      iter: self.keys.iter(),
-      map: &self.map,
-      values: &mut self.values,
+      keys: &storage.keys,
+      iter: storage.keys.iter(),
+      map: &storage.map,
+      values: &mut storage.values,
     }
   }
 
@@ -567,7 +629,8 @@ impl<Key, Value, State> ListOrderedMultimap<Key, Value, State> {
   /// assert!(map.values_capacity() >= 11);
   /// ```
   pub fn reserve_values(&mut self, additional_capacity: usize) {
-    self.values.reserve(additional_capacity);
+    let storage = self.storage.get_mut();
+    storage.values.reserve(additional_capacity);
   }
 
   /// Returns an iterator that yields immutable references to all values in the multimap by insertion order.
@@ -593,7 +656,7 @@ impl<Key, Value, State> ListOrderedMultimap<Key, Value, State> {
   /// ```
   #[must_use]
   pub fn values(&self) -> Values<'_, Key, Value> {
-    Values(self.values.iter())
+    Values(self.storage.values.iter())
   }
 
   /// Returns an iterator that yields mutable references to all values in the multimap by insertion order.
@@ -625,7 +688,8 @@ impl<Key, Value, State> ListOrderedMultimap<Key, Value, State> {
   /// ```
   #[must_use]
   pub fn values_mut(&mut self) -> ValuesMut<'_, Key, Value> {
-    ValuesMut(self.values.iter_mut())
+    let storage = self.storage.get_mut();
+    ValuesMut(storage.values.iter_mut())
   }
 
   /// Returns the number of values the multimap can hold without reallocating.
@@ -645,7 +709,7 @@ impl<Key, Value, State> ListOrderedMultimap<Key, Value, State> {
   /// ```
   #[must_use]
   pub fn values_capacity(&self) -> usize {
-    self.values.capacity()
+    self.storage.values.capacity()
   }
 
   /// Returns the total number of values in the multimap across all keys.
@@ -666,7 +730,7 @@ impl<Key, Value, State> ListOrderedMultimap<Key, Value, State> {
   /// ```
   #[must_use]
   pub fn values_len(&self) -> usize {
-    self.values.len()
+    self.storage.values.len()
   }
 }
 
@@ -698,8 +762,9 @@ where
   /// assert_eq!(map.values_len(), 2);
   /// ```
   pub fn append(&mut self, key: Key, value: Value) -> bool {
+    let storage = self.storage.get_mut();
     let hash = self.build_hasher.hash_one(&key);
-    let entry = raw_entry_mut(&self.keys, &mut self.map, hash, &key);
+    let entry = raw_entry_mut(&storage.keys, &mut storage.map, hash, &key);
 
     match entry {
       RawEntryMut::Occupied(mut entry) => {
@@ -707,8 +772,8 @@ where
         let mut value_entry = ValueEntry::new(*key_index, value);
         let map_entry = entry.get_mut();
         value_entry.previous_index = Some(map_entry.tail_index);
-        let index = self.values.push_back(value_entry);
-        self
+        let index = storage.values.push_back(value_entry);
+        storage
           .values
           .get_mut(map_entry.tail_index)
           .unwrap()
@@ -717,10 +782,10 @@ where
         true
       }
       RawEntryMut::Vacant(entry) => {
-        let key_index = self.keys.push_back(key);
+        let key_index = storage.keys.push_back(key);
         let value_entry = ValueEntry::new(key_index, value);
-        let index = self.values.push_back(value_entry);
-        let keys = &self.keys;
+        let index = storage.values.push_back(value_entry);
+        let keys = &storage.keys;
         let _ = entry.insert_with_hasher(hash, key_index, MapEntry::new(index), |&key_index| {
           let key = keys.get(key_index).unwrap();
           self.build_hasher.hash_one(key)
@@ -751,7 +816,7 @@ where
     KeyQuery: ?Sized + Eq + Hash,
   {
     let hash = self.build_hasher.hash_one(key);
-    raw_entry(&self.keys, &self.map, hash, key).is_some()
+    raw_entry(&self.storage.keys, &self.storage.map, hash, key).is_some()
   }
 
   /// Returns whether the given key is in the multimap.
@@ -770,32 +835,56 @@ where
   /// ```
   #[must_use]
   pub fn entry(&mut self, key: Key) -> Entry<'_, Key, Value, State> {
+    let storage = self.storage.get_mut();
     let hash = self.build_hasher.hash_one(&key);
 
     // TODO: This ugliness arises from borrow checking issues which seems to happen when the vacant entry is created in
     // the match block further below for `Vacant` even though it should be perfectly safe. Is there a better way to do
     // this?
-    if !self.contains_key(&key) {
+    if raw_entry(&storage.keys, &storage.map, hash, &key).is_none() {
       Entry::Vacant(VacantEntry {
         build_hasher: &self.build_hasher,
         hash,
         key,
-        keys: &mut self.keys,
-        map: &mut self.map,
-        values: &mut self.values,
+        keys: &mut storage.keys,
+        map: &mut storage.map,
+        values: &mut storage.values,
       })
     } else {
-      match raw_entry_mut(&self.keys, &mut self.map, hash, &key) {
+      match raw_entry_mut(&storage.keys, &mut storage.map, hash, &key) {
         RawEntryMut::Occupied(entry) => Entry::Occupied(OccupiedEntry {
           entry,
-          keys: &mut self.keys,
-          values: &mut self.values,
+          keys: &mut storage.keys,
+          values: &mut storage.values,
         }),
         _ => panic!("expected occupied entry"),
       }
     }
   }
 
+  /// Returns the entry of the key at the given index in the key list, see [`OccupiedEntry::key_index`].
+  ///
+  /// Complexity: O(1)
+  ///
+  /// # Panics
+  ///
+  /// Panics if there is no key at the given index.
+  #[cfg(feature = "serde")]
+  pub(crate) fn occupied_entry(&mut self, key_index: Index<Key>) -> OccupiedEntry<'_, Key, Value> {
+    let storage = self.storage.get_mut();
+    let key = storage.keys.get(key_index).unwrap();
+    let hash = self.build_hasher.hash_one(key);
+
+    match raw_entry_mut(&storage.keys, &mut storage.map, hash, key) {
+      RawEntryMut::Occupied(entry) => OccupiedEntry {
+        entry,
+        keys: &mut storage.keys,
+        values: &mut storage.values,
+      },
+      _ => panic!("expected occupied entry"),
+    }
+  }
+
   /// Returns the number of values associated with a key.
   ///
   /// Complexity: O(1)
@@ -822,12 +911,110 @@ where
   {
     let hash = self.build_hasher.hash_one(key);
 
-    match raw_entry(&self.keys, &self.map, hash, key) {
+    match raw_entry(&self.storage.keys, &self.storage.map, hash, key) {
       Some((_, map_entry)) => map_entry.length,
       None => 0,
     }
   }
 
+  /// Returns whether both multimaps associate each key with the same values in the same order, regardless of how the
+  /// values of different keys are interleaved.
+  ///
+  /// See [`PerKeyEq`] for a wrapper that uses this comparison for [`PartialEq`] and [`Hash`].
+  ///
+  /// Complexity: O(|V|)
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::ListOrderedMultimap;
+  ///
+  /// let map: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("a", 3)].into_iter().collect();
+  /// let other: ListOrderedMultimap<_, _> = [("b", 2), ("a", 1), ("a", 3)].into_iter().collect();
+  /// assert!(map != other);
+  /// assert!(map.eq_per_key(&other));
+  ///
+  /// let other: ListOrderedMultimap<_, _> = [("a", 3), ("b", 2), ("a", 1)].into_iter().collect();
+  /// assert!(!map.eq_per_key(&other));
+  /// ```
+  #[must_use]
+  pub fn eq_per_key<OtherState>(&self, other: &ListOrderedMultimap<Key, Value, OtherState>) -> bool
+  where
+    OtherState: BuildHasher,
+    Value: PartialEq,
+  {
+    if self.keys_len() != other.keys_len() || self.values_len() != other.values_len() {
+      return false;
+    }
+
+    self.keys().all(|key| {
+      let other_values = other.get_all(key);
+      self.entry_len(key) == other_values.len() && self.get_all(key).eq(other_values)
+    })
+  }
+
+  /// Returns whether both multimaps contain the same key-value pairs, regardless of their order. Key-value pairs that
+  /// occur more than once must occur the same number of times in both multimaps.
+  ///
+  /// See [`UnorderedEq`] for a wrapper that uses this comparison for [`PartialEq`] and [`Hash`].
+  ///
+  /// Complexity: O(|V| * m) where m is the maximum number of values associated with a key
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::ListOrderedMultimap;
+  ///
+  /// let map: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("a", 3)].into_iter().collect();
+  /// let other: ListOrderedMultimap<_, _> = [("a", 3), ("b", 2), ("a", 1)].into_iter().collect();
+  /// assert!(!map.eq_per_key(&other));
+  /// assert!(map.eq_unordered(&other));
+  ///
+  /// let other: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("a", 1)].into_iter().collect();
+  /// assert!(!map.eq_unordered(&other));
+  /// ```
+  #[must_use]
+  pub fn eq_unordered<OtherState>(
+    &self,
+    other: &ListOrderedMultimap<Key, Value, OtherState>,
+  ) -> bool
+  where
+    OtherState: BuildHasher,
+    Value: PartialEq,
+  {
+    if self.keys_len() != other.keys_len() || self.values_len() != other.values_len() {
+      return false;
+    }
+
+    let mut matched = vec![];
+
+    self.keys().all(|key| {
+      let other_values = other.get_all(key);
+
+      if self.entry_len(key) != other_values.len() {
+        return false;
+      }
+
+      matched.clear();
+      matched.resize(other_values.len(), false);
+
+      self.get_all(key).all(|value| {
+        let position = other_values
+          .clone()
+          .zip(&matched)
+          .position(|(other_value, &matched)| !matched && value == other_value);
+
+        match position {
+          Some(position) => {
+            matched[position] = true;
+            true
+          }
+          None => false,
+        }
+      })
+    })
+  }
+
   /// Returns an immutable reference to the first value, by insertion order, associated with the given key, or `None` if
   /// the key is not in the multimap.
   ///
@@ -849,8 +1036,9 @@ where
     KeyQuery: ?Sized + Eq + Hash,
   {
     let hash = self.build_hasher.hash_one(key);
-    let (_, map_entry) = raw_entry(&self.keys, &self.map, hash, key)?;
+    let (_, map_entry) = raw_entry(&self.storage.keys, &self.storage.map, hash, key)?;
     self
+      .storage
       .values
       .get(map_entry.head_index)
       .map(|entry| &entry.value)
@@ -883,9 +1071,9 @@ where
   {
     let hash = self.build_hasher.hash_one(key);
 
-    match raw_entry(&self.keys, &self.map, hash, key) {
-      Some((_, map_entry)) => EntryValues::from_map_entry(&self.values, map_entry),
-      None => EntryValues::empty(&self.values),
+    match raw_entry(&self.storage.keys, &self.storage.map, hash, key) {
+      Some((_, map_entry)) => EntryValues::from_map_entry(&self.storage.values, map_entry),
+      None => EntryValues::empty(&self.storage.values),
     }
   }
 
@@ -919,11 +1107,12 @@ where
     Key: Borrow<KeyQuery>,
     KeyQuery: ?Sized + Eq + Hash,
   {
+    let storage = self.storage.get_mut();
     let hash = self.build_hasher.hash_one(key);
 
-    match raw_entry(&self.keys, &self.map, hash, key) {
-      Some((_, map_entry)) => EntryValuesMut::from_map_entry(&mut self.values, map_entry),
-      None => EntryValuesMut::empty(&mut self.values),
+    match raw_entry(&storage.keys, &storage.map, hash, key) {
+      Some((_, map_entry)) => EntryValuesMut::from_map_entry(&mut storage.values, map_entry),
+      None => EntryValuesMut::empty(&mut storage.values),
     }
   }
 
@@ -954,14 +1143,112 @@ where
     Key: Borrow<KeyQuery>,
     KeyQuery: ?Sized + Eq + Hash,
   {
+    let storage = self.storage.get_mut();
     let hash = self.build_hasher.hash_one(key);
-    let (_, map_entry) = raw_entry(&self.keys, &self.map, hash, key)?;
-    self
+    let (_, map_entry) = raw_entry(&storage.keys, &storage.map, hash, key)?;
+    storage
       .values
       .get_mut(map_entry.head_index)
       .map(|entry| &mut entry.value)
   }
 
+  /// Feeds the values of each key in order into the given hasher, regardless of how the values of different keys are
+  /// interleaved.
+  ///
+  /// This is consistent with [`ListOrderedMultimap::eq_per_key`] and is used by the [`Hash`] implementation of
+  /// [`PerKeyEq`].
+  ///
+  /// Each key is hashed with its values separately and the hashes are combined by adding them, so the combined hash is
+  /// not collision-resistant even if the given hasher is. With the `std` feature, the keys are hashed with a key that
+  /// is random for each process, so that collisions cannot be precomputed. Without it, they are hashed with a fixed
+  /// hash function, so this should not be used for multimaps built from untrusted input.
+  ///
+  /// Complexity: O(|V|)
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use std::collections::hash_map::DefaultHasher;
+  /// use std::hash::Hasher;
+  ///
+  /// use ordered_multimap::ListOrderedMultimap;
+  ///
+  /// let map: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("a", 3)].into_iter().collect();
+  /// let other: ListOrderedMultimap<_, _> = [("b", 2), ("a", 1), ("a", 3)].into_iter().collect();
+  ///
+  /// let mut hasher = DefaultHasher::new();
+  /// map.hash_per_key(&mut hasher);
+  /// let mut other_hasher = DefaultHasher::new();
+  /// other.hash_per_key(&mut other_hasher);
+  /// assert_eq!(hasher.finish(), other_hasher.finish());
+  /// ```
+  pub fn hash_per_key<H>(&self, state: &mut H)
+  where
+    H: Hasher,
+    Value: Hash,
+  {
+    // The keys are combined in a way that does not depend on their order.
+    let keys_hash = self.keys().fold(0u64, |keys_hash, key| {
+      let mut hasher = part_hasher();
+      key.hash(&mut hasher);
+
+      let values = self.get_all(key);
+      hasher.write_usize(values.len());
+      values.for_each(|value| value.hash(&mut hasher));
+
+      keys_hash.wrapping_add(hasher.finish())
+    });
+
+    state.write_usize(self.values_len());
+    state.write_u64(keys_hash);
+  }
+
+  /// Feeds the key-value pairs of the multimap into the given hasher, regardless of their order.
+  ///
+  /// This is consistent with [`ListOrderedMultimap::eq_unordered`] and is used by the [`Hash`] implementation of
+  /// [`UnorderedEq`].
+  ///
+  /// Each key-value pair is hashed separately and the hashes are combined by adding them, so the combined hash is not
+  /// collision-resistant even if the given hasher is. With the `std` feature, the key-value pairs are hashed with a key
+  /// that is random for each process, so that collisions cannot be precomputed. Without it, they are hashed with a
+  /// fixed hash function, so this should not be used for multimaps built from untrusted input.
+  ///
+  /// Complexity: O(|V|)
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use std::collections::hash_map::DefaultHasher;
+  /// use std::hash::Hasher;
+  ///
+  /// use ordered_multimap::ListOrderedMultimap;
+  ///
+  /// let map: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("a", 3)].into_iter().collect();
+  /// let other: ListOrderedMultimap<_, _> = [("a", 3), ("b", 2), ("a", 1)].into_iter().collect();
+  ///
+  /// let mut hasher = DefaultHasher::new();
+  /// map.hash_unordered(&mut hasher);
+  /// let mut other_hasher = DefaultHasher::new();
+  /// other.hash_unordered(&mut other_hasher);
+  /// assert_eq!(hasher.finish(), other_hasher.finish());
+  /// ```
+  pub fn hash_unordered<H>(&self, state: &mut H)
+  where
+    H: Hasher,
+    Value: Hash,
+  {
+    // The key-value pairs are combined in a way that does not depend on their order.
+    let pairs_hash = self.iter().fold(0u64, |pairs_hash, (key, value)| {
+      let mut hasher = part_hasher();
+      key.hash(&mut hasher);
+      value.hash(&mut hasher);
+      pairs_hash.wrapping_add(hasher.finish())
+    });
+
+    state.write_usize(self.values_len());
+    state.write_u64(pairs_hash);
+  }
+
   /// Inserts the key-value pair into the multimap and returns the first value, by insertion order, that was already
   /// associated with the key.
   ///
@@ -1029,29 +1316,30 @@ where
   /// assert_eq!(map.get(&"key"), Some(&"value3"));
   /// ```
   pub fn insert_all(&mut self, key: Key, value: Value) -> EntryValuesDrain<'_, Key, Value> {
+    let storage = self.storage.get_mut();
     let hash = self.build_hasher.hash_one(&key);
-    let entry = raw_entry_mut(&self.keys, &mut self.map, hash, &key);
+    let entry = raw_entry_mut(&storage.keys, &mut storage.map, hash, &key);
 
     match entry {
       RawEntryMut::Occupied(mut entry) => {
         let key_index = entry.key();
         let value_entry = ValueEntry::new(*key_index, value);
-        let index = self.values.push_back(value_entry);
+        let index = storage.values.push_back(value_entry);
         let map_entry = entry.get_mut();
-        let iter = EntryValuesDrain::from_map_entry(&mut self.values, map_entry);
+        let iter = EntryValuesDrain::from_map_entry(&mut storage.values, map_entry);
         map_entry.reset(index);
         iter
       }
       RawEntryMut::Vacant(entry) => {
-        let key_index = self.keys.push_back(key);
+        let key_index = storage.keys.push_back(key);
         let value_entry = ValueEntry::new(key_index, value);
-        let index = self.values.push_back(value_entry);
-        let keys = &self.keys;
+        let index = storage.values.push_back(value_entry);
+        let keys = &storage.keys;
         let _ = entry.insert_with_hasher(hash, key_index, MapEntry::new(index), |&key_index| {
           let key = keys.get(key_index).unwrap();
           self.build_hasher.hash_one(key)
         });
-        EntryValuesDrain::empty(&mut self.values)
+        EntryValuesDrain::empty(&mut storage.values)
       }
     }
   }
@@ -1099,26 +1387,27 @@ where
       "cannot pack multimap values lower than current length"
     );
 
-    let key_map = self.keys.pack_to(keys_minimum_capacity);
-    let value_map = self.values.pack_to(values_minimum_capacity);
+    let storage = self.storage.get_mut();
+    let key_map = storage.keys.pack_to(keys_minimum_capacity);
+    let value_map = storage.values.pack_to(values_minimum_capacity);
     let mut map = HashMap::with_capacity_and_hasher(keys_minimum_capacity, DummyState);
 
-    for value_entry in self.values.iter_mut() {
+    for value_entry in storage.values.iter_mut() {
       value_entry.key_index = key_map[&value_entry.key_index];
       value_entry.next_index = value_entry.next_index.map(|index| value_map[&index]);
       value_entry.previous_index = value_entry.previous_index.map(|index| value_map[&index]);
     }
 
-    for (key_index, mut map_entry) in self.map.drain() {
+    for (key_index, mut map_entry) in storage.map.drain() {
       map_entry.head_index = value_map[&map_entry.head_index];
       map_entry.tail_index = value_map[&map_entry.tail_index];
       let key_index = key_map[&key_index];
-      let key = self.keys.get(key_index).unwrap();
+      let key = storage.keys.get(key_index).unwrap();
       let hash = self.build_hasher.hash_one(key);
 
       match map.raw_entry_mut().from_hash(hash, |_| false) {
         RawEntryMut::Vacant(entry) => {
-          let keys = &self.keys;
+          let keys = &storage.keys;
           let _ = entry.insert_with_hasher(hash, key_index, map_entry, |&key_index| {
             let key = keys.get(key_index).unwrap();
             self.build_hasher.hash_one(key)
@@ -1128,7 +1417,7 @@ where
       }
     }
 
-    self.map = map;
+    storage.map = map;
   }
 
   /// Reorganizes the multimap to ensure maximum spatial locality and removes any excess key and value capacity.
@@ -1192,14 +1481,15 @@ where
   /// assert_eq!(&value, &"value1");
   /// ```
   pub fn pop_back(&mut self) -> Option<(KeyWrapper<'_, Key>, Value)> {
-    let value_entry = self.values.pop_back()?;
+    let storage = self.storage.get_mut();
+    let value_entry = storage.values.pop_back()?;
 
     let key_wrapper = match value_entry.previous_index {
       Some(previous_index) => {
-        let key = self.keys.get(value_entry.key_index).unwrap();
+        let key = storage.keys.get(value_entry.key_index).unwrap();
         let hash = self.build_hasher.hash_one(key);
 
-        let mut entry = match raw_entry_mut(&self.keys, &mut self.map, hash, key) {
+        let mut entry = match raw_entry_mut(&storage.keys, &mut storage.map, hash, key) {
           RawEntryMut::Occupied(entry) => entry,
           _ => panic!("expected occupied entry in internal map"),
         };
@@ -1207,16 +1497,16 @@ where
         map_entry.length -= 1;
         map_entry.tail_index = previous_index;
 
-        let previous_value_entry = self.values.get_mut(previous_index).unwrap();
+        let previous_value_entry = storage.values.get_mut(previous_index).unwrap();
         previous_value_entry.next_index = None;
 
         KeyWrapper::Borrowed(key)
       }
       None => {
-        let key = self.keys.remove(value_entry.key_index).unwrap();
+        let key = storage.keys.remove(value_entry.key_index).unwrap();
         let hash = self.build_hasher.hash_one(&key);
 
-        match raw_entry_mut_empty(&self.keys, &mut self.map, hash) {
+        match raw_entry_mut_empty(&storage.keys, &mut storage.map, hash) {
           RawEntryMut::Occupied(entry) => {
             let _ = entry.remove();
           }
@@ -1261,14 +1551,15 @@ where
   /// assert_eq!(&value, &"value2");
   /// ```
   pub fn pop_front(&mut self) -> Option<(KeyWrapper<'_, Key>, Value)> {
-    let value_entry = self.values.pop_front()?;
+    let storage = self.storage.get_mut();
+    let value_entry = storage.values.pop_front()?;
 
     let key_wrapper = match value_entry.next_index {
       Some(next_index) => {
-        let key = self.keys.get(value_entry.key_index).unwrap();
+        let key = storage.keys.get(value_entry.key_index).unwrap();
         let hash = self.build_hasher.hash_one(key);
 
-        let mut entry = match raw_entry_mut(&self.keys, &mut self.map, hash, key) {
+        let mut entry = match raw_entry_mut(&storage.keys, &mut storage.map, hash, key) {
           RawEntryMut::Occupied(entry) => entry,
           _ => panic!("expected occupied entry in internal map"),
         };
@@ -1276,16 +1567,16 @@ where
         map_entry.length -= 1;
         map_entry.head_index = next_index;
 
-        let next_value_entry = self.values.get_mut(next_index).unwrap();
+        let next_value_entry = storage.values.get_mut(next_index).unwrap();
         next_value_entry.previous_index = None;
 
         KeyWrapper::Borrowed(key)
       }
       None => {
-        let key = self.keys.remove(value_entry.key_index).unwrap();
+        let key = storage.keys.remove(value_entry.key_index).unwrap();
         let hash = self.build_hasher.hash_one(&key);
 
-        match raw_entry_mut_empty(&self.keys, &mut self.map, hash) {
+        match raw_entry_mut_empty(&storage.keys, &mut storage.map, hash) {
           RawEntryMut::Occupied(entry) => {
             let _ = entry.remove();
           }
@@ -1364,16 +1655,17 @@ where
     Key: Borrow<KeyQuery>,
     KeyQuery: ?Sized + Eq + Hash,
   {
+    let storage = self.storage.get_mut();
     let hash = self.build_hasher.hash_one(key);
-    let entry = raw_entry_mut(&self.keys, &mut self.map, hash, key);
+    let entry = raw_entry_mut(&storage.keys, &mut storage.map, hash, key);
 
     match entry {
       RawEntryMut::Occupied(entry) => {
         let (key_index, map_entry) = entry.remove_entry();
-        mem::drop(self.keys.remove(key_index).unwrap());
-        EntryValuesDrain::from_map_entry(&mut self.values, &map_entry)
+        mem::drop(storage.keys.remove(key_index).unwrap());
+        EntryValuesDrain::from_map_entry(&mut storage.values, &map_entry)
       }
-      RawEntryMut::Vacant(_) => EntryValuesDrain::empty(&mut self.values),
+      RawEntryMut::Vacant(_) => EntryValuesDrain::empty(&mut storage.values),
     }
   }
 
@@ -1450,14 +1742,15 @@ where
     Key: Borrow<KeyQuery>,
     KeyQuery: ?Sized + Eq + Hash,
   {
+    let storage = self.storage.get_mut();
     let hash = self.build_hasher.hash_one(key);
-    let entry = raw_entry_mut(&self.keys, &mut self.map, hash, key);
+    let entry = raw_entry_mut(&storage.keys, &mut storage.map, hash, key);
 
     match entry {
       RawEntryMut::Occupied(entry) => {
         let (key_index, map_entry) = entry.remove_entry();
-        let key = self.keys.remove(key_index).unwrap();
-        let iter = EntryValuesDrain::from_map_entry(&mut self.values, &map_entry);
+        let key = storage.keys.remove(key_index).unwrap();
+        let iter = EntryValuesDrain::from_map_entry(&mut storage.values, &map_entry);
         Some((key, iter))
       }
       _ => None,
@@ -1486,29 +1779,33 @@ where
   /// assert_eq!(map.get(&"key"), Some(&"value"));
   /// ```
   pub fn reserve_keys(&mut self, additional_capacity: usize) {
-    if self.keys.capacity() - self.keys.len() >= additional_capacity {
+    let storage = self.storage.get_mut();
+    if storage.keys.capacity() - storage.keys.len() >= additional_capacity {
       return;
     }
 
-    let capacity = self.map.capacity() + additional_capacity;
+    let capacity = storage.map.capacity() + additional_capacity;
     let mut map = HashMap::with_capacity_and_hasher(capacity, DummyState);
 
-    for (key_index, map_entry) in self.map.drain() {
-      let key = self.keys.get(key_index).unwrap();
+    for (key_index, map_entry) in storage.map.drain() {
+      let key = storage.keys.get(key_index).unwrap();
       let hash = self.build_hasher.hash_one(key);
-      let entry = match raw_entry_mut(&self.keys, &mut map, hash, key) {
+      let entry = match raw_entry_mut(&storage.keys, &mut map, hash, key) {
         RawEntryMut::Vacant(entry) => entry,
         _ => panic!("expected vacant entry"),
       };
       let _ = entry.insert_hashed_nocheck(hash, key_index, map_entry);
     }
 
-    self.keys.reserve(additional_capacity);
-    self.map = map;
+    storage.keys.reserve(additional_capacity);
+    storage.map = map;
   }
 
   /// Keeps all key-value pairs that satisfy the given predicate function.
   ///
+  /// The predicate is called exactly once for each key-value pair, in global order. A rejected key-value pair is
+  /// removed before the predicate is called for the next one, and a key is removed along with its last value.
+  ///
   /// Complexity: O(|V|) where |V| is the number of values
   ///
   /// # Examples
@@ -1530,140 +1827,684 @@ where
   /// assert_eq!(iter.next(), Some((&"key2", &5)));
   /// assert_eq!(iter.next(), None);
   /// ```
-  pub fn retain<Function>(&mut self, function: Function)
+  pub fn retain<Function>(&mut self, mut function: Function)
   where
     Function: FnMut(&Key, &mut Value) -> bool,
   {
-    ListOrderedMultimap::retain_helper(
-      &self.build_hasher,
-      &mut self.keys,
-      &mut self.map,
-      &mut self.values,
-      function,
-    );
-  }
+    let mut next_index = self.storage.get_mut().values.front_index();
 
-  /// Helper function for [`ListOrderedMultimap::retain`] to deal with borrowing issues.
-  fn retain_helper<'map, Function>(
-    build_hasher: &'map State,
-    keys: &'map mut VecList<Key>,
-    map: &'map mut HashMap<Index<Key>, MapEntry<Key, Value>, DummyState>,
-    values: &'map mut VecList<ValueEntry<Key, Value>>,
-    mut function: Function,
-  ) where
-    Function: FnMut(&Key, &mut Value) -> bool,
-  {
-    let mut post_updates = vec![];
+    while let Some(index) = next_index {
+      let storage = self.storage.get_mut();
+      next_index = storage.values.get_next_index(index);
+      let value_entry = storage.values.get_mut(index).unwrap();
+      let key = storage.keys.get(value_entry.key_index).unwrap();
 
-    values.retain(|value_entry| {
-      let key = keys.get(value_entry.key_index).unwrap();
+      if !function(key, &mut value_entry.value) {
+        mem::drop(self.remove_value(index).1);
+      }
+    }
+  }
 
-      if function(key, &mut value_entry.value) {
-        true
-      } else {
-        let hash = build_hasher.hash_one(key);
-        let mut entry = match raw_entry_mut(keys, map, hash, key) {
-          RawEntryMut::Occupied(entry) => entry,
-          _ => panic!("expected occupied entry in internal map"),
-        };
+  /// Returns an iterator that yields immutable references to the key-value pairs of the given keys. The order of the
+  /// yielded items is the global order of the multimap, so the view is a projection of the multimap onto the given keys.
+  ///
+  /// Keys that are not in the multimap are ignored, and keys that are given more than once are only yielded once.
+  ///
+  /// Complexity: O(m) to create the iterator, where m is the number of given keys. Iterating over the view takes O(n)
+  /// time in the worst case, where n is the number of values in the multimap.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::ListOrderedMultimap;
+  ///
+  /// let mut map = ListOrderedMultimap::new();
+  /// map.append("key1", "value1");
+  /// map.append("key2", "value2");
+  /// map.append("key3", "value3");
+  /// map.append("key1", "value4");
+  ///
+  /// let mut iter = map.view_for_keys(&["key3", "key1"]);
+  /// assert_eq!(iter.len(), 3);
+  /// assert_eq!(iter.next(), Some((&"key1", &"value1")));
+  /// assert_eq!(iter.next(), Some((&"key3", &"value3")));
+  /// assert_eq!(iter.next(), Some((&"key1", &"value4")));
+  /// assert_eq!(iter.next(), None);
+  /// ```
+  #[must_use]
+  pub fn view_for_keys<KeyQuery>(&self, keys: &[KeyQuery]) -> ViewForKeys<'_, Key, Value>
+  where
+    Key: Borrow<KeyQuery>,
+    KeyQuery: Eq + Hash,
+  {
+    let mut key_indices =
+      HashSet::with_capacity_and_hasher(keys.len(), BuildHasherDefault::default());
+    let mut remaining = 0;
 
-        if value_entry.previous_index.is_none() && value_entry.next_index.is_none() {
-          let _ = entry.remove();
-          mem::drop(keys.remove(value_entry.key_index));
-        } else {
-          let map_entry = entry.get_mut();
-          map_entry.length -= 1;
-
-          if let Some(previous_index) = value_entry.previous_index {
-            post_updates.push((previous_index, None, Some(value_entry.next_index)));
-          } else {
-            map_entry.head_index = value_entry.next_index.unwrap();
-          }
+    for key in keys {
+      let hash = self.build_hasher.hash_one(key);
 
-          if let Some(next_index) = value_entry.next_index {
-            post_updates.push((next_index, Some(value_entry.previous_index), None));
-          } else {
-            map_entry.tail_index = value_entry.previous_index.unwrap();
-          }
+      if let Some((&key_index, map_entry)) =
+        raw_entry(&self.storage.keys, &self.storage.map, hash, key)
+      {
+        if key_indices.insert(key_index) {
+          remaining += map_entry.length;
         }
-
-        false
-      }
-    });
-
-    for (index, new_previous_index, new_next_index) in post_updates {
-      let value_entry = values.get_mut(index).unwrap();
-
-      if let Some(new_previous_index) = new_previous_index {
-        value_entry.previous_index = new_previous_index;
       }
+    }
 
-      if let Some(new_next_index) = new_next_index {
-        value_entry.next_index = new_next_index;
-      }
+    ViewForKeys {
+      iter: self.storage.values.iter(),
+      key_indices,
+      keys: &self.storage.keys,
+      remaining,
     }
   }
 }
 
-impl<Key, Value, State> Debug for ListOrderedMultimap<Key, Value, State>
-where
-  Key: Debug,
-  Value: Debug,
-{
-  fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
-    formatter.debug_map().entries(self.iter()).finish()
-  }
-}
+#[cfg(feature = "serde")]
+impl<Key, Value, State> ListOrderedMultimap<Key, Value, State> {
+  /// Returns an iterator that yields, for each value in the global order, the position of its key in the order of
+  /// [`ListOrderedMultimap::keys`].
+  ///
+  /// Complexity: O(|K| + |V|)
+  pub(crate) fn key_ordinals(&self) -> impl Iterator<Item = usize> + '_ {
+    let ordinals: HashMap<_, _, BuildHasherDefault<FnvHasher>> = self
+      .storage
+      .keys
+      .indices()
+      .enumerate()
+      .map(|(ordinal, key_index)| (key_index, ordinal))
+      .collect();
 
-#[cfg(feature = "std")]
-impl<Key, Value> Default for ListOrderedMultimap<Key, Value, RandomState> {
-  fn default() -> Self {
-    Self::new()
+    self
+      .storage
+      .values
+      .iter()
+      .map(move |entry| ordinals[&entry.key_index])
   }
 }
 
-impl<Key, Value, State> Eq for ListOrderedMultimap<Key, Value, State>
-where
-  Key: Eq,
-  Value: PartialEq,
-{
-}
-
-impl<Key, Value, State> Extend<(Key, Value)> for ListOrderedMultimap<Key, Value, State>
+impl<Key, Value, State> ListOrderedMultimap<Key, Value, State>
 where
   Key: Eq + Hash,
   State: BuildHasher,
 {
-  fn extend<Iter>(&mut self, iter: Iter)
-  where
-    Iter: IntoIterator<Item = (Key, Value)>,
-  {
-    let iter = iter.into_iter();
-    self.reserve_values(iter.size_hint().0);
-
-    for (key, value) in iter {
+  /// Inserts the key-value pair such that it ends up at the given position in the global order. The value is placed
+  /// among the other values of the key according to the global order. If the key is not already in the multimap, it is
+  /// ordered after all other keys, as with [`ListOrderedMultimap::append`].
+  ///
+  /// Complexity: O(min(p, |V| - p)) where p is the position and |V| is the number of values, plus the distance to the
+  /// previous value of the key.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the position is greater than the number of values.
+  pub(crate) fn insert_at(&mut self, position: usize, key: Key, value: Value) {
+    if position == self.values_len() {
       let _ = self.append(key, value);
+      return;
     }
-  }
-}
 
-impl<'a, Key, Value, State> Extend<(&'a Key, &'a Value)> for ListOrderedMultimap<Key, Value, State>
-where
-  Key: Copy + Eq + Hash,
-  Value: Copy,
-  State: BuildHasher,
-{
-  fn extend<Iter>(&mut self, iter: Iter)
-  where
-    Iter: IntoIterator<Item = (&'a Key, &'a Value)>,
-  {
-    self.extend(iter.into_iter().map(|(&key, &value)| (key, value)));
+    let target_index = self.value_index(position).unwrap();
+    let storage = self.storage.get_mut();
+    let hash = self.build_hasher.hash_one(&key);
+
+    match raw_entry_mut(&storage.keys, &mut storage.map, hash, &key) {
+      RawEntryMut::Occupied(entry) => {
+        let value_entry = ValueEntry::new(*entry.key(), value);
+        let index = storage.values.insert_before(target_index, value_entry);
+        self.link_value(index);
+      }
+      RawEntryMut::Vacant(entry) => {
+        let key_index = storage.keys.push_back(key);
+        let value_entry = ValueEntry::new(key_index, value);
+        let index = storage.values.insert_before(target_index, value_entry);
+        let keys = &storage.keys;
+        let _ = entry.insert_with_hasher(hash, key_index, MapEntry::new(index), |&key_index| {
+          let key = keys.get(key_index).unwrap();
+          self.build_hasher.hash_one(key)
+        });
+      }
+    }
   }
-}
 
-impl<Key, Value, State> FromIterator<(Key, Value)> for ListOrderedMultimap<Key, Value, State>
-where
-  Key: Eq + Hash,
+  /// Moves the key-value pair at position `from` in the global order such that it ends up at position `to`. The order
+  /// of the keys is unchanged.
+  ///
+  /// Complexity: O(min(p, |V| - p)) for both positions p where |V| is the number of values, plus the distance to the
+  /// previous value of the key.
+  ///
+  /// # Panics
+  ///
+  /// Panics if either position is not less than the number of values.
+  pub(crate) fn move_value(&mut self, from: usize, to: usize) {
+    let index = self.value_index(from).unwrap();
+    let target_index = self.value_index(to).unwrap();
+
+    if from == to {
+      return;
+    }
+
+    let _ = self.unlink_value(index);
+    let storage = self.storage.get_mut();
+
+    if from < to {
+      storage.values.move_after(index, target_index);
+    } else {
+      storage.values.move_before(index, target_index);
+    }
+
+    self.link_value(index);
+  }
+
+  /// Returns the key and a mutable reference to the value at the given position in the global order.
+  ///
+  /// Complexity: O(min(p, |V| - p)) where p is the position and |V| is the number of values.
+  pub(crate) fn get_at_mut(&mut self, position: usize) -> Option<(&Key, &mut Value)> {
+    let index = self.value_index(position)?;
+    let storage = self.storage.get_mut();
+    let value_entry = storage.values.get_mut(index).unwrap();
+    let key = storage.keys.get(value_entry.key_index).unwrap();
+    Some((key, &mut value_entry.value))
+  }
+
+  /// Removes the key-value pair at the given position in the global order and returns the value.
+  ///
+  /// If this was the only value of the key, the key is removed as well.
+  ///
+  /// Complexity: O(min(p, |V| - p)) where p is the position and |V| is the number of values.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the position is not less than the number of values.
+  pub(crate) fn remove_at(&mut self, position: usize) -> Value {
+    let index = self.value_index(position).unwrap();
+    self.remove_value(index).1
+  }
+
+  /// Removes the value at the given position among the values of the given key and returns it along with what is
+  /// needed to put it back with [`ListOrderedMultimap::restore`].
+  ///
+  /// If this was the only value of the key, the key is removed as well.
+  ///
+  /// Complexity: O(min(n, m - n) + min(p, |V| - p)) where n is the given position, m is the number of values of the
+  /// key, p is the position of the value in the global order and |V| is the number of values.
+  pub(crate) fn remove_nth<KeyQuery>(
+    &mut self,
+    key: &KeyQuery,
+    nth: usize,
+  ) -> Option<RemovedValue<Key, Value>>
+  where
+    Key: Borrow<KeyQuery>,
+    KeyQuery: ?Sized + Eq + Hash,
+  {
+    let hash = self.build_hasher.hash_one(key);
+    let (key_index, map_entry) = raw_entry(&self.storage.keys, &self.storage.map, hash, key)?;
+    let key_index = *key_index;
+
+    if nth >= map_entry.length {
+      return None;
+    }
+
+    let values = &self.storage.values;
+    let mut index;
+
+    if nth < map_entry.length / 2 {
+      index = map_entry.head_index;
+
+      for _ in 0..nth {
+        index = values.get(index).unwrap().next_index.unwrap();
+      }
+    } else {
+      index = map_entry.tail_index;
+
+      for _ in nth + 1..map_entry.length {
+        index = values.get(index).unwrap().previous_index.unwrap();
+      }
+    }
+
+    let position = self.value_position(index);
+    let next_key_index = self.storage.keys.get_next_index(key_index);
+    let (key, value) = self.remove_value(index);
+
+    Some(RemovedValue {
+      position,
+      key_index,
+      key: key.map(|key| (key, next_key_index)),
+      value,
+    })
+  }
+
+  /// Puts back a key-value pair removed by [`ListOrderedMultimap::remove_nth`] and returns the index the key has now.
+  ///
+  /// If the key was removed as well, it is inserted before the key it preceded, which must be in the multimap again, or
+  /// at the end if there was none. Otherwise, the key must still be in the multimap at the given index.
+  ///
+  /// Complexity: O(min(p, |V| - p)) where p is the position and |V| is the number of values, plus the distance to the
+  /// previous value of the key.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the position is greater than the number of values.
+  pub(crate) fn restore(&mut self, removed: RemovedValue<Key, Value>) -> Index<Key> {
+    let target_index = if removed.position == self.values_len() {
+      None
+    } else {
+      Some(self.value_index(removed.position).unwrap())
+    };
+    let storage = self.storage.get_mut();
+
+    let key_index = match removed.key {
+      Some((key, next_key_index)) => {
+        let hash = self.build_hasher.hash_one(&key);
+
+        match raw_entry_mut(&storage.keys, &mut storage.map, hash, &key) {
+          RawEntryMut::Vacant(entry) => {
+            let key_index = match next_key_index {
+              Some(next_key_index) => storage.keys.insert_before(next_key_index, key),
+              None => storage.keys.push_back(key),
+            };
+            let value_entry = ValueEntry::new(key_index, removed.value);
+            let index = match target_index {
+              Some(target_index) => storage.values.insert_before(target_index, value_entry),
+              None => storage.values.push_back(value_entry),
+            };
+            let keys = &storage.keys;
+            let _ = entry.insert_with_hasher(hash, key_index, MapEntry::new(index), |&key_index| {
+              let key = keys.get(key_index).unwrap();
+              self.build_hasher.hash_one(key)
+            });
+            return key_index;
+          }
+          _ => panic!("expected vacant entry in internal map"),
+        }
+      }
+      None => removed.key_index,
+    };
+
+    let value_entry = ValueEntry::new(key_index, removed.value);
+    let index = match target_index {
+      Some(target_index) => storage.values.insert_before(target_index, value_entry),
+      None => storage.values.push_back(value_entry),
+    };
+    self.link_value(index);
+    key_index
+  }
+
+  /// Returns the index of the value at the given position in the global order, walking from the nearer end.
+  ///
+  /// Complexity: O(min(p, |V| - p)) where p is the position and |V| is the number of values.
+  fn value_index(&self, position: usize) -> Option<Index<ValueEntry<Key, Value>>> {
+    let length = self.values_len();
+
+    if position < length / 2 {
+      self.storage.values.indices().nth(position)
+    } else {
+      let back_position = length.checked_sub(position + 1)?;
+      self.storage.values.indices().nth_back(back_position)
+    }
+  }
+
+  /// Returns the position in the global order of the value at the given index, walking towards both ends at once.
+  ///
+  /// Complexity: O(min(p, |V| - p)) where p is the position and |V| is the number of values.
+  fn value_position(&self, index: Index<ValueEntry<Key, Value>>) -> usize {
+    let values = &self.storage.values;
+    let mut backward_index = index;
+    let mut forward_index = index;
+    let mut steps = 0;
+
+    loop {
+      match values.get_previous_index(backward_index) {
+        Some(index) => backward_index = index,
+        None => return steps,
+      }
+
+      match values.get_next_index(forward_index) {
+        Some(index) => forward_index = index,
+        None => return values.len() - 1 - steps,
+      }
+
+      steps += 1;
+    }
+  }
+
+  /// Keeps all key-value pairs that satisfy the given predicate function like [`ListOrderedMultimap::retain`], and then
+  /// calls `removed` with every removed key-value pair in global order. By then, the multimap no longer contains any of
+  /// the removed pairs.
+  ///
+  /// Complexity: O(|V|) where |V| is the number of values.
+  pub(crate) fn retain_then<Function, Removed>(
+    &mut self,
+    mut function: Function,
+    mut removed: Removed,
+  ) where
+    Function: FnMut(&Key, &mut Value) -> bool,
+    Removed: FnMut(&Key, &Value),
+  {
+    let storage = self.storage.get_mut();
+    let mut indices = Vec::new();
+    let mut next_index = storage.values.front_index();
+
+    while let Some(index) = next_index {
+      next_index = storage.values.get_next_index(index);
+      let value_entry = storage.values.get_mut(index).unwrap();
+      let key = storage.keys.get(value_entry.key_index).unwrap();
+
+      if !function(key, &mut value_entry.value) {
+        indices.push(index);
+      }
+    }
+
+    let pairs: Vec<_> = indices
+      .into_iter()
+      .map(|index| {
+        let key_index = self.storage.values.get(index).unwrap().key_index;
+        let (key, value) = self.remove_value(index);
+        (key_index, key, value)
+      })
+      .collect();
+
+    // Keys that still have values are looked up by their index, which is unaffected by the removals. Removed keys were
+    // returned by the removal of the last of their values.
+    let removed_keys: HashMap<_, _, BuildHasherDefault<FnvHasher>> = pairs
+      .iter()
+      .filter_map(|(key_index, key, _)| Some((*key_index, key.as_ref()?)))
+      .collect();
+
+    for (key_index, _, value) in &pairs {
+      let key = match self.storage.keys.get(*key_index) {
+        Some(key) => key,
+        None => removed_keys[key_index],
+      };
+      removed(key, value);
+    }
+  }
+
+  /// Removes the value at the given index from the global order and from the list of values of its key, and returns
+  /// it.
+  ///
+  /// If this was the only value of the key, the key is removed as well and returned along with the value.
+  ///
+  /// Complexity: O(1)
+  fn remove_value(&mut self, index: Index<ValueEntry<Key, Value>>) -> (Option<Key>, Value) {
+    let key_removed = self.unlink_value(index);
+    let storage = self.storage.get_mut();
+
+    if key_removed {
+      let value_entry = storage.values.remove(index).unwrap();
+      let key = storage.keys.remove(value_entry.key_index).unwrap();
+      let hash = self.build_hasher.hash_one(&key);
+
+      match raw_entry_mut_empty(&storage.keys, &mut storage.map, hash) {
+        RawEntryMut::Occupied(entry) => {
+          let _ = entry.remove();
+        }
+        _ => panic!("expected occupied entry in internal map"),
+      }
+
+      (Some(key), value_entry.value)
+    } else {
+      (None, storage.values.remove(index).unwrap().value)
+    }
+  }
+
+  /// Links the value at the given index into the list of values of its key according to its position in the global
+  /// order.
+  fn link_value(&mut self, index: Index<ValueEntry<Key, Value>>) {
+    let storage = self.storage.get_mut();
+    let key_index = storage.values.get(index).unwrap().key_index;
+    let mut previous_index = storage.values.get_previous_index(index);
+
+    while let Some(current_index) = previous_index {
+      if storage.values.get(current_index).unwrap().key_index == key_index {
+        break;
+      }
+
+      previous_index = storage.values.get_previous_index(current_index);
+    }
+
+    let key = storage.keys.get(key_index).unwrap();
+    let hash = self.build_hasher.hash_one(key);
+    let mut entry = match raw_entry_mut(&storage.keys, &mut storage.map, hash, key) {
+      RawEntryMut::Occupied(entry) => entry,
+      _ => panic!("expected occupied entry in internal map"),
+    };
+    let map_entry = entry.get_mut();
+
+    if map_entry.length == 0 {
+      map_entry.reset(index);
+      let value_entry = storage.values.get_mut(index).unwrap();
+      value_entry.previous_index = None;
+      value_entry.next_index = None;
+      return;
+    }
+
+    let next_index = match previous_index {
+      Some(previous_index) => storage.values.get(previous_index).unwrap().next_index,
+      None => Some(map_entry.head_index),
+    };
+
+    let value_entry = storage.values.get_mut(index).unwrap();
+    value_entry.previous_index = previous_index;
+    value_entry.next_index = next_index;
+    map_entry.length += 1;
+
+    match previous_index {
+      Some(previous_index) => {
+        storage.values.get_mut(previous_index).unwrap().next_index = Some(index)
+      }
+      None => map_entry.head_index = index,
+    }
+
+    match next_index {
+      Some(next_index) => storage.values.get_mut(next_index).unwrap().previous_index = Some(index),
+      None => map_entry.tail_index = index,
+    }
+  }
+
+  /// Unlinks the value at the given index from the list of values of its key, but leaves it in the global order.
+  ///
+  /// Returns whether the key no longer has any values. In that case, the internal map entry of the key must either be
+  /// removed or the value must be linked again.
+  fn unlink_value(&mut self, index: Index<ValueEntry<Key, Value>>) -> bool {
+    let storage = self.storage.get_mut();
+    let value_entry = storage.values.get(index).unwrap();
+    let previous_index = value_entry.previous_index;
+    let next_index = value_entry.next_index;
+    let key = storage.keys.get(value_entry.key_index).unwrap();
+    let hash = self.build_hasher.hash_one(key);
+    let mut entry = match raw_entry_mut(&storage.keys, &mut storage.map, hash, key) {
+      RawEntryMut::Occupied(entry) => entry,
+      _ => panic!("expected occupied entry in internal map"),
+    };
+    let map_entry = entry.get_mut();
+    map_entry.length -= 1;
+
+    match previous_index {
+      Some(previous_index) => {
+        storage.values.get_mut(previous_index).unwrap().next_index = next_index
+      }
+      None => {
+        if let Some(next_index) = next_index {
+          map_entry.head_index = next_index;
+        }
+      }
+    }
+
+    match next_index {
+      Some(next_index) => {
+        storage.values.get_mut(next_index).unwrap().previous_index = previous_index
+      }
+      None => {
+        if let Some(previous_index) = previous_index {
+          map_entry.tail_index = previous_index;
+        }
+      }
+    }
+
+    map_entry.length == 0
+  }
+}
+
+impl<Key, Value, State> ListOrderedMultimap<Key, Value, State>
+where
+  Key: Clone,
+  State: Clone,
+  Value: Clone,
+{
+  /// Starts a transaction on the multimap.
+  ///
+  /// All changes made through the returned [`Transaction`] are undone when it is dropped, unless
+  /// [`Transaction::commit`] is called first. Rolling back restores the exact previous state of the multimap, including
+  /// the global order of all values and the order of the values of each key.
+  ///
+  /// Rolling back works by restoring a snapshot of the multimap, which is why `Key`, `Value` and `State` must implement
+  /// [`Clone`]. No undo log is kept.
+  ///
+  /// Complexity: O(1) to start the transaction, as the snapshot shares the storage of the multimap. The first change
+  /// made through the transaction copies all keys and values, which takes O(|K| + |V|) time and memory where |K| is the
+  /// number of keys and |V| is the number of values, no matter how small the change is. A transaction that changes
+  /// nothing copies nothing.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::ListOrderedMultimap;
+  ///
+  /// let mut map = ListOrderedMultimap::new();
+  /// map.insert("key", "value1");
+  ///
+  /// let mut transaction = map.begin();
+  /// transaction.append("key", "value2");
+  /// transaction.remove(&"key");
+  /// transaction.rollback();
+  ///
+  /// assert_eq!(map.get(&"key"), Some(&"value1"));
+  /// assert_eq!(map.entry_len(&"key"), 1);
+  /// ```
+  #[must_use]
+  pub fn begin(&mut self) -> Transaction<'_, Key, Value, State> {
+    Transaction {
+      snapshot: Some(self.clone()),
+      map: self,
+    }
+  }
+
+  /// Runs the given function in a transaction on the multimap.
+  ///
+  /// If the function returns `Ok`, the changes it made are committed. If it returns `Err` or panics, the multimap is
+  /// restored to its exact previous state. See [`ListOrderedMultimap::begin`], including for the [`Clone`] bounds.
+  ///
+  /// Complexity: O(|K| + |V|) if the function changes the multimap, as the first change copies it, in addition to the
+  /// cost of the function itself.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::ListOrderedMultimap;
+  ///
+  /// let mut map = ListOrderedMultimap::new();
+  /// map.insert("key1", 1);
+  /// map.insert("key2", 2);
+  ///
+  /// let result: Result<(), &str> = map.transaction(|map| {
+  ///   map.append("key1", 3);
+  ///   map.remove(&"key2");
+  ///   Err("validation failed")
+  /// });
+  /// assert_eq!(result, Err("validation failed"));
+  ///
+  /// let mut iter = map.iter();
+  /// assert_eq!(iter.next(), Some((&"key1", &1)));
+  /// assert_eq!(iter.next(), Some((&"key2", &2)));
+  /// assert_eq!(iter.next(), None);
+  ///
+  /// let result: Result<(), &str> = map.transaction(|map| {
+  ///   map.append("key1", 3);
+  ///   Ok(())
+  /// });
+  /// assert_eq!(result, Ok(()));
+  /// assert_eq!(map.entry_len(&"key1"), 2);
+  /// ```
+  pub fn transaction<Function, Output, Error>(
+    &mut self,
+    function: Function,
+  ) -> Result<Output, Error>
+  where
+    Function: FnOnce(&mut Transaction<'_, Key, Value, State>) -> Result<Output, Error>,
+  {
+    let mut transaction = self.begin();
+    let result = function(&mut transaction);
+
+    if result.is_ok() {
+      transaction.commit();
+    }
+
+    result
+  }
+}
+
+impl<Key, Value, State> Debug for ListOrderedMultimap<Key, Value, State>
+where
+  Key: Debug,
+  Value: Debug,
+{
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    formatter.debug_map().entries(self.iter()).finish()
+  }
+}
+
+#[cfg(feature = "std")]
+impl<Key, Value> Default for ListOrderedMultimap<Key, Value, RandomState> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<Key, Value, State> Eq for ListOrderedMultimap<Key, Value, State>
+where
+  Key: Eq,
+  Value: PartialEq,
+{
+}
+
+impl<Key, Value, State> Extend<(Key, Value)> for ListOrderedMultimap<Key, Value, State>
+where
+  Key: Eq + Hash,
+  State: BuildHasher,
+{
+  fn extend<Iter>(&mut self, iter: Iter)
+  where
+    Iter: IntoIterator<Item = (Key, Value)>,
+  {
+    let iter = iter.into_iter();
+    self.reserve_values(iter.size_hint().0);
+
+    for (key, value) in iter {
+      let _ = self.append(key, value);
+    }
+  }
+}
+
+impl<'a, Key, Value, State> Extend<(&'a Key, &'a Value)> for ListOrderedMultimap<Key, Value, State>
+where
+  Key: Copy + Eq + Hash,
+  Value: Copy,
+  State: BuildHasher,
+{
+  fn extend<Iter>(&mut self, iter: Iter)
+  where
+    Iter: IntoIterator<Item = (&'a Key, &'a Value)>,
+  {
+    self.extend(iter.into_iter().map(|(&key, &value)| (key, value)));
+  }
+}
+
+impl<Key, Value, State> FromIterator<(Key, Value)> for ListOrderedMultimap<Key, Value, State>
+where
+  Key: Eq + Hash,
   State: BuildHasher + Default,
 {
   fn from_iter<Iter>(iter: Iter) -> Self
@@ -1676,6 +2517,19 @@ where
   }
 }
 
+impl<Key, Value, State> Hash for ListOrderedMultimap<Key, Value, State>
+where
+  Key: Hash,
+  Value: Hash,
+{
+  fn hash<H>(&self, state: &mut H)
+  where
+    H: Hasher,
+  {
+    self.hash_interleaved(state);
+  }
+}
+
 impl<Key, Value, State> IntoIterator for ListOrderedMultimap<Key, Value, State>
 where
   Key: Clone,
@@ -1684,9 +2538,10 @@ where
   type Item = (Key, Value);
 
   fn into_iter(self) -> Self::IntoIter {
+    let storage = self.storage.into_inner();
     IntoIter {
-      keys: self.keys,
-      iter: self.values.into_iter(),
+      keys: storage.keys,
+      iter: storage.values.into_iter(),
     }
   }
 }
@@ -1709,6 +2564,16 @@ impl<'map, Key, Value, State> IntoIterator for &'map mut ListOrderedMultimap<Key
   }
 }
 
+impl<Key, Value, State> Ord for ListOrderedMultimap<Key, Value, State>
+where
+  Key: Ord,
+  Value: Ord,
+{
+  fn cmp(&self, other: &ListOrderedMultimap<Key, Value, State>) -> Ordering {
+    self.iter().cmp(other.iter())
+  }
+}
+
 impl<Key, Value, State> PartialEq for ListOrderedMultimap<Key, Value, State>
 where
   Key: PartialEq,
@@ -1723,6 +2588,16 @@ where
   }
 }
 
+impl<Key, Value, State> PartialOrd for ListOrderedMultimap<Key, Value, State>
+where
+  Key: PartialOrd,
+  Value: PartialOrd,
+{
+  fn partial_cmp(&self, other: &ListOrderedMultimap<Key, Value, State>) -> Option<Ordering> {
+    self.iter().partial_cmp(other.iter())
+  }
+}
+
 /// A wrapper around a key that is either borrowed or owned.
 ///
 /// This type is similar to [`std::borrow::Cow`] but does not require a [`Clone`] trait bound on the key.
@@ -1808,60 +2683,200 @@ pub(crate) struct MapEntry<Key, Value> {
   /// The number of values for this entry.
   length: usize,
 
-  /// The index of the last value for this entry.
-  tail_index: Index<ValueEntry<Key, Value>>,
-}
+  /// The index of the last value for this entry.
+  tail_index: Index<ValueEntry<Key, Value>>,
+}
+
+impl<Key, Value> MapEntry<Key, Value> {
+  /// Convenience function for adding a new value to the entry.
+  pub fn append(&mut self, index: Index<ValueEntry<Key, Value>>) {
+    self.length += 1;
+    self.tail_index = index;
+  }
+
+  /// Convenience function for creating a new multimap entry.
+  #[must_use]
+  pub fn new(index: Index<ValueEntry<Key, Value>>) -> Self {
+    MapEntry {
+      head_index: index,
+      length: 1,
+      tail_index: index,
+    }
+  }
+
+  /// Convenience function for resetting the entry to contain only one value.
+  pub fn reset(&mut self, index: Index<ValueEntry<Key, Value>>) {
+    self.head_index = index;
+    self.length = 1;
+    self.tail_index = index;
+  }
+}
+
+/// A key-value pair removed by [`ListOrderedMultimap::remove_nth`] along with where it was.
+pub(crate) struct RemovedValue<Key, Value> {
+  /// The position of the pair in the global order.
+  pub(crate) position: usize,
+
+  /// The index the key had in the key list.
+  pub(crate) key_index: Index<Key>,
+
+  /// The key and the index of the key that followed it in the key list, if the key was removed as well.
+  pub(crate) key: Option<(Key, Option<Index<Key>>)>,
+
+  /// The removed value.
+  pub(crate) value: Value,
+}
+
+/// The value entry that is contained within the internal values list.
+#[derive(Clone)]
+pub(crate) struct ValueEntry<Key, Value> {
+  /// The index of the key in the key list for this entry.
+  key_index: Index<Key>,
+
+  /// The index of the next value with the same key.
+  next_index: Option<Index<ValueEntry<Key, Value>>>,
+
+  /// The index of the previous value with the same key.
+  previous_index: Option<Index<ValueEntry<Key, Value>>>,
+
+  /// The actual value stored in this entry.
+  value: Value,
+}
+
+impl<Key, Value> ValueEntry<Key, Value> {
+  /// Convenience function for creating a new value entry.
+  #[must_use]
+  pub fn new(key_index: Index<Key>, value: Value) -> Self {
+    ValueEntry {
+      key_index,
+      next_index: None,
+      previous_index: None,
+      value,
+    }
+  }
+}
+
+/// The storage of a multimap that is shared between its clones until one of them is mutated.
+pub(crate) struct SharedStorage<Key, Value>(Arc<Shared<Key, Value>>);
+
+/// The shared allocation behind [`SharedStorage`].
+struct Shared<Key, Value> {
+  /// The function that copies the storage when it is shared and about to be mutated, stored as a pointer to a
+  /// `fn(&Storage<Key, Value>) -> Storage<Key, Value>`. It is set when cloning the multimap, which is the only way for
+  /// the storage to become shared, so that mutating a multimap does not require [`Clone`] bounds.
+  copy: AtomicPtr<()>,
+
+  /// The storage itself.
+  storage: Storage<Key, Value>,
+}
+
+impl<Key, Value> Shared<Key, Value> {
+  /// Convenience function for creating the shared allocation with the given copy function.
+  #[must_use]
+  fn new(storage: Storage<Key, Value>, copy: *mut ()) -> Self {
+    Shared {
+      copy: AtomicPtr::new(copy),
+      storage,
+    }
+  }
+
+  /// Returns the function that copies the storage.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the storage has never been shared.
+  #[must_use]
+  fn copy(&self) -> fn(&Storage<Key, Value>) -> Storage<Key, Value> {
+    let copy = self.copy.load(atomic::Ordering::Acquire);
+    assert!(
+      !copy.is_null(),
+      "expected shared storage to have a copy function"
+    );
+
+    // SAFETY: The pointer is only ever set from a function pointer of this type in `SharedStorage::clone`.
+    unsafe { mem::transmute::<*mut (), fn(&Storage<Key, Value>) -> Storage<Key, Value>>(copy) }
+  }
+}
+
+impl<Key, Value> SharedStorage<Key, Value> {
+  /// Convenience function for creating storage that is not shared.
+  #[must_use]
+  pub fn new(storage: Storage<Key, Value>) -> Self {
+    SharedStorage(Arc::new(Shared::new(storage, ptr::null_mut())))
+  }
+
+  /// Returns a mutable reference to the storage, copying it first if it is shared.
+  pub fn get_mut(&mut self) -> &mut Storage<Key, Value> {
+    if Arc::get_mut(&mut self.0).is_none() {
+      let copy = self.0.copy();
+      self.0 = Arc::new(Shared::new(copy(&self.0.storage), copy as *mut ()));
+    }
 
-impl<Key, Value> MapEntry<Key, Value> {
-  /// Convenience function for adding a new value to the entry.
-  pub fn append(&mut self, index: Index<ValueEntry<Key, Value>>) {
-    self.length += 1;
-    self.tail_index = index;
+    &mut Arc::get_mut(&mut self.0).unwrap().storage
   }
 
-  /// Convenience function for creating a new multimap entry.
+  /// Returns the storage, copying it if it is shared.
   #[must_use]
-  pub fn new(index: Index<ValueEntry<Key, Value>>) -> Self {
-    MapEntry {
-      head_index: index,
-      length: 1,
-      tail_index: index,
+  pub fn into_inner(self) -> Storage<Key, Value> {
+    match Arc::try_unwrap(self.0) {
+      Ok(shared) => shared.storage,
+      Err(shared) => shared.copy()(&shared.storage),
     }
   }
 
-  /// Convenience function for resetting the entry to contain only one value.
-  pub fn reset(&mut self, index: Index<ValueEntry<Key, Value>>) {
-    self.head_index = index;
-    self.length = 1;
-    self.tail_index = index;
+  /// Returns whether the storage is shared with another multimap.
+  #[must_use]
+  pub fn is_shared(&self) -> bool {
+    Arc::strong_count(&self.0) > 1
   }
 }
 
-/// The value entry that is contained within the internal values list.
-#[derive(Clone)]
-pub(crate) struct ValueEntry<Key, Value> {
-  /// The index of the key in the key list for this entry.
-  key_index: Index<Key>,
+impl<Key, Value> Clone for SharedStorage<Key, Value>
+where
+  Key: Clone,
+  Value: Clone,
+{
+  fn clone(&self) -> Self {
+    let copy: fn(&Storage<Key, Value>) -> Storage<Key, Value> = Storage::clone;
+    self
+      .0
+      .copy
+      .store(copy as *mut (), atomic::Ordering::Release);
+    SharedStorage(Arc::clone(&self.0))
+  }
+}
 
-  /// The index of the next value with the same key.
-  next_index: Option<Index<ValueEntry<Key, Value>>>,
+impl<Key, Value> Deref for SharedStorage<Key, Value> {
+  type Target = Storage<Key, Value>;
 
-  /// The index of the previous value with the same key.
-  previous_index: Option<Index<ValueEntry<Key, Value>>>,
+  fn deref(&self) -> &Self::Target {
+    &self.0.storage
+  }
+}
 
-  /// The actual value stored in this entry.
-  value: Value,
+/// The keys and values of a multimap along with the index between them.
+#[derive(Clone)]
+pub(crate) struct Storage<Key, Value> {
+  /// The list of the keys in the multimap. This is ordered by time of insertion.
+  pub keys: VecList<Key>,
+
+  /// The map from indices of keys to the indices of their values in the value list. The list of the indices is ordered
+  /// by time of insertion. We never use hasher of the hashmap explicitly here, we instead use
+  /// [`ListOrderedMultimap::build_hasher`].
+  pub map: HashMap<Index<Key>, MapEntry<Key, Value>, DummyState>,
+
+  /// The list of the values in the multimap. This is ordered by time of insertion.
+  pub values: VecList<ValueEntry<Key, Value>>,
 }
 
-impl<Key, Value> ValueEntry<Key, Value> {
-  /// Convenience function for creating a new value entry.
+impl<Key, Value> Storage<Key, Value> {
+  /// Convenience function for creating storage with the given capacities.
   #[must_use]
-  pub fn new(key_index: Index<Key>, value: Value) -> Self {
-    ValueEntry {
-      key_index,
-      next_index: None,
-      previous_index: None,
-      value,
+  pub fn with_capacity(key_capacity: usize, value_capacity: usize) -> Self {
+    Storage {
+      keys: VecList::with_capacity(key_capacity),
+      map: HashMap::with_capacity_and_hasher(key_capacity, DummyState),
+      values: VecList::with_capacity(value_capacity),
     }
   }
 }
@@ -2078,6 +3093,13 @@ impl<'map, Key, Value> OccupiedEntry<'map, Key, Value> {
     map_entry.tail_index = index;
   }
 
+  /// Returns the index of the key in the key list, which stays valid until the key is removed.
+  #[cfg(feature = "serde")]
+  #[must_use]
+  pub(crate) fn key_index(&self) -> Index<Key> {
+    *self.entry.key()
+  }
+
   /// # Examples
   ///
   /// ```
@@ -2592,6 +3614,11 @@ where
 
 /// An iterator that yields immutable references to all values of a given key. The order of the values is always in the
 /// order that they were inserted.
+///
+/// With the `serde` feature, this serializes as a sequence of the values rather than in the representation of
+/// [`ListOrderedMultimap`], because the iterator does not know its key when it is created from an
+/// [`EntryValuesDrain`] or [`EntryValuesMut`]. Serialize [`ListOrderedMultimap::view_for_keys`] with the single key
+/// instead to get a multimap.
 pub struct EntryValues<'map, Key, Value> {
   /// The first index of the values not yet yielded.
   head_index: Option<Index<ValueEntry<Key, Value>>>,
@@ -2695,6 +3722,22 @@ impl<'map, Key, Value> Iterator for EntryValues<'map, Key, Value> {
   }
 }
 
+// The indices only mark the type of the list they index into and own no keys or values, so the iterator is just a
+// shared reference to the values.
+unsafe impl<Key, Value> Send for EntryValues<'_, Key, Value>
+where
+  Key: Sync,
+  Value: Sync,
+{
+}
+
+unsafe impl<Key, Value> Sync for EntryValues<'_, Key, Value>
+where
+  Key: Sync,
+  Value: Sync,
+{
+}
+
 /// An iterator that moves all values of a given key out of a multimap but preserves the underlying capacity. The order
 /// of the values is always in the order that they were inserted.
 pub struct EntryValuesDrain<'map, Key, Value> {
@@ -3113,6 +4156,9 @@ impl<'map, Key, Value> Iterator for IterMut<'map, Key, Value> {
 
 /// An iterator that yields immutable references to all keys and their value iterators. The order of the yielded items
 /// is always in the order the keys were first inserted.
+///
+/// With the `serde` feature, this serializes in the representation of [`ListOrderedMultimap`] with the values grouped
+/// by key, so the global interleaving of the values is lost.
 pub struct KeyValues<'map, Key, Value, State = RandomState> {
   /// The builder hasher for the map, kept separately for mutability concerns.
   build_hasher: &'map State,
@@ -3354,6 +4400,216 @@ impl<'map, Key> Iterator for Keys<'map, Key> {
   }
 }
 
+/// A wrapper around a multimap whose [`PartialEq`] and [`Hash`] implementations only consider the values of each key
+/// in order, but not how the values of different keys are interleaved. See [`ListOrderedMultimap::eq_per_key`].
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashSet;
+///
+/// use ordered_multimap::{list_ordered_multimap::PerKeyEq, ListOrderedMultimap};
+///
+/// let map: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("a", 3)].into_iter().collect();
+/// let other: ListOrderedMultimap<_, _> = [("b", 2), ("a", 1), ("a", 3)].into_iter().collect();
+/// assert_eq!(PerKeyEq(map.clone()), PerKeyEq(other.clone()));
+///
+/// let mut set = HashSet::new();
+/// set.insert(PerKeyEq(map));
+/// assert!(!set.insert(PerKeyEq(other)));
+/// ```
+#[derive(Clone, Debug)]
+pub struct PerKeyEq<Key, Value, State = RandomState>(pub ListOrderedMultimap<Key, Value, State>);
+
+impl<Key, Value, State> Eq for PerKeyEq<Key, Value, State>
+where
+  Key: Eq + Hash,
+  State: BuildHasher,
+  Value: Eq,
+{
+}
+
+impl<Key, Value, State> From<ListOrderedMultimap<Key, Value, State>>
+  for PerKeyEq<Key, Value, State>
+{
+  fn from(map: ListOrderedMultimap<Key, Value, State>) -> Self {
+    PerKeyEq(map)
+  }
+}
+
+impl<Key, Value, State> Hash for PerKeyEq<Key, Value, State>
+where
+  Key: Eq + Hash,
+  State: BuildHasher,
+  Value: Hash,
+{
+  fn hash<H>(&self, state: &mut H)
+  where
+    H: Hasher,
+  {
+    self.0.hash_per_key(state);
+  }
+}
+
+impl<Key, Value, State> PartialEq for PerKeyEq<Key, Value, State>
+where
+  Key: Eq + Hash,
+  State: BuildHasher,
+  Value: PartialEq,
+{
+  fn eq(&self, other: &PerKeyEq<Key, Value, State>) -> bool {
+    self.0.eq_per_key(&other.0)
+  }
+}
+
+/// A transaction on a multimap, created by [`ListOrderedMultimap::begin`].
+///
+/// The transaction dereferences to the multimap, so all of its methods can be used to make changes. Unless
+/// [`Transaction::commit`] is called, all changes are undone when the transaction is dropped.
+pub struct Transaction<'map, Key, Value, State = RandomState> {
+  /// The multimap being changed.
+  map: &'map mut ListOrderedMultimap<Key, Value, State>,
+
+  /// The state of the multimap when the transaction started. This is `None` once the transaction is committed.
+  snapshot: Option<ListOrderedMultimap<Key, Value, State>>,
+}
+
+impl<Key, Value, State> Transaction<'_, Key, Value, State> {
+  /// Keeps all changes made during the transaction.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::ListOrderedMultimap;
+  ///
+  /// let mut map = ListOrderedMultimap::new();
+  ///
+  /// let mut transaction = map.begin();
+  /// transaction.insert("key", "value");
+  /// transaction.commit();
+  ///
+  /// assert_eq!(map.get(&"key"), Some(&"value"));
+  /// ```
+  pub fn commit(mut self) {
+    self.snapshot = None;
+  }
+
+  /// Undoes all changes made during the transaction.
+  ///
+  /// This is equivalent to dropping the transaction.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::ListOrderedMultimap;
+  ///
+  /// let mut map = ListOrderedMultimap::new();
+  ///
+  /// let mut transaction = map.begin();
+  /// transaction.insert("key", "value");
+  /// transaction.rollback();
+  ///
+  /// assert!(map.is_empty());
+  /// ```
+  pub fn rollback(self) {}
+}
+
+impl<Key, Value, State> Debug for Transaction<'_, Key, Value, State>
+where
+  Key: Debug,
+  Value: Debug,
+{
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    formatter.write_str("Transaction(")?;
+    formatter.debug_map().entries(self.map.iter()).finish()?;
+    formatter.write_str(")")
+  }
+}
+
+impl<Key, Value, State> Deref for Transaction<'_, Key, Value, State> {
+  type Target = ListOrderedMultimap<Key, Value, State>;
+
+  fn deref(&self) -> &Self::Target {
+    self.map
+  }
+}
+
+impl<Key, Value, State> DerefMut for Transaction<'_, Key, Value, State> {
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    self.map
+  }
+}
+
+impl<Key, Value, State> Drop for Transaction<'_, Key, Value, State> {
+  fn drop(&mut self) {
+    if let Some(snapshot) = self.snapshot.take() {
+      *self.map = snapshot;
+    }
+  }
+}
+
+/// A wrapper around a multimap whose [`PartialEq`] and [`Hash`] implementations only consider which key-value pairs
+/// the multimap contains and how often, but not their order. See [`ListOrderedMultimap::eq_unordered`].
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashSet;
+///
+/// use ordered_multimap::{list_ordered_multimap::UnorderedEq, ListOrderedMultimap};
+///
+/// let map: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("a", 3)].into_iter().collect();
+/// let other: ListOrderedMultimap<_, _> = [("a", 3), ("b", 2), ("a", 1)].into_iter().collect();
+/// assert_eq!(UnorderedEq(map.clone()), UnorderedEq(other.clone()));
+///
+/// let mut set = HashSet::new();
+/// set.insert(UnorderedEq(map));
+/// assert!(!set.insert(UnorderedEq(other)));
+/// ```
+#[derive(Clone, Debug)]
+pub struct UnorderedEq<Key, Value, State = RandomState>(pub ListOrderedMultimap<Key, Value, State>);
+
+impl<Key, Value, State> Eq for UnorderedEq<Key, Value, State>
+where
+  Key: Eq + Hash,
+  State: BuildHasher,
+  Value: Eq,
+{
+}
+
+impl<Key, Value, State> From<ListOrderedMultimap<Key, Value, State>>
+  for UnorderedEq<Key, Value, State>
+{
+  fn from(map: ListOrderedMultimap<Key, Value, State>) -> Self {
+    UnorderedEq(map)
+  }
+}
+
+impl<Key, Value, State> Hash for UnorderedEq<Key, Value, State>
+where
+  Key: Eq + Hash,
+  State: BuildHasher,
+  Value: Hash,
+{
+  fn hash<H>(&self, state: &mut H)
+  where
+    H: Hasher,
+  {
+    self.0.hash_unordered(state);
+  }
+}
+
+impl<Key, Value, State> PartialEq for UnorderedEq<Key, Value, State>
+where
+  Key: Eq + Hash,
+  State: BuildHasher,
+  Value: PartialEq,
+{
+  fn eq(&self, other: &UnorderedEq<Key, Value, State>) -> bool {
+    self.0.eq_unordered(&other.0)
+  }
+}
+
 /// An iterator that yields immutable references to all values of a multimap. The order of the values is always in the
 /// order that they were inserted.
 pub struct Values<'map, Key, Value>(VecListIter<'map, ValueEntry<Key, Value>>);
@@ -3422,25 +4678,118 @@ where
   }
 }
 
-impl<Key, Value> DoubleEndedIterator for ValuesMut<'_, Key, Value> {
-  fn next_back(&mut self) -> Option<Self::Item> {
-    self.0.next_back().map(|entry| &mut entry.value)
+impl<Key, Value> DoubleEndedIterator for ValuesMut<'_, Key, Value> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    self.0.next_back().map(|entry| &mut entry.value)
+  }
+}
+
+impl<Key, Value> ExactSizeIterator for ValuesMut<'_, Key, Value> {}
+
+impl<Key, Value> FusedIterator for ValuesMut<'_, Key, Value> {}
+
+impl<'map, Key, Value> Iterator for ValuesMut<'map, Key, Value> {
+  type Item = &'map mut Value;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.0.next().map(|entry| &mut entry.value)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.0.size_hint()
+  }
+}
+
+/// An iterator that yields immutable references to the key-value pairs of some keys in a multimap. The order of the
+/// yielded items is always in the order that they were inserted.
+pub struct ViewForKeys<'map, Key, Value> {
+  /// The iterator over the list of all values. This is ordered by time of insertion.
+  iter: VecListIter<'map, ValueEntry<Key, Value>>,
+
+  /// The indices of the keys whose values are yielded.
+  key_indices: HashSet<Index<Key>, BuildHasherDefault<FnvHasher>>,
+
+  // The list of the keys in the map. This is ordered by time of insertion.
+  keys: &'map VecList<Key>,
+
+  /// The remaining number of values to be yielded.
+  remaining: usize,
+}
+
+impl<Key, Value> ViewForKeys<'_, Key, Value> {
+  /// Returns whether the value entry belongs to one of the keys of the view.
+  #[must_use]
+  fn contains(&self, value_entry: &ValueEntry<Key, Value>) -> bool {
+    self.key_indices.contains(&value_entry.key_index)
+  }
+}
+
+impl<'map, Key, Value> Clone for ViewForKeys<'map, Key, Value> {
+  fn clone(&self) -> ViewForKeys<'map, Key, Value> {
+    ViewForKeys {
+      iter: self.iter.clone(),
+      key_indices: self.key_indices.clone(),
+      keys: self.keys,
+      remaining: self.remaining,
+    }
+  }
+}
+
+impl<Key, Value> Debug for ViewForKeys<'_, Key, Value>
+where
+  Key: Debug,
+  Value: Debug,
+{
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    formatter.write_str("ViewForKeys(")?;
+    formatter.debug_list().entries(self.clone()).finish()?;
+    formatter.write_str(")")
+  }
+}
+
+impl<Key, Value> DoubleEndedIterator for ViewForKeys<'_, Key, Value> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    if self.remaining == 0 {
+      return None;
+    }
+
+    while let Some(value_entry) = self.iter.next_back() {
+      if self.contains(value_entry) {
+        self.remaining -= 1;
+        let key = self.keys.get(value_entry.key_index).unwrap();
+        return Some((key, &value_entry.value));
+      }
+    }
+
+    None
   }
 }
 
-impl<Key, Value> ExactSizeIterator for ValuesMut<'_, Key, Value> {}
+impl<Key, Value> ExactSizeIterator for ViewForKeys<'_, Key, Value> {}
 
-impl<Key, Value> FusedIterator for ValuesMut<'_, Key, Value> {}
+impl<Key, Value> FusedIterator for ViewForKeys<'_, Key, Value> {}
 
-impl<'map, Key, Value> Iterator for ValuesMut<'map, Key, Value> {
-  type Item = &'map mut Value;
+impl<'map, Key, Value> Iterator for ViewForKeys<'map, Key, Value> {
+  type Item = (&'map Key, &'map Value);
 
   fn next(&mut self) -> Option<Self::Item> {
-    self.0.next().map(|entry| &mut entry.value)
+    if self.remaining == 0 {
+      return None;
+    }
+
+    while let Some(value_entry) = self.iter.next() {
+      if self.contains(value_entry) {
+        self.remaining -= 1;
+        let key = self.keys.get(value_entry.key_index).unwrap();
+        return Some((key, &value_entry.value));
+      }
+    }
+
+    None
   }
 
   fn size_hint(&self) -> (usize, Option<usize>) {
-    self.0.size_hint()
+    (self.remaining, Some(self.remaining))
   }
 }
 
@@ -3470,6 +4819,49 @@ impl Hasher for DummyHasher {
   }
 }
 
+/// Returns a hasher for parts of a multimap that are combined independently of their order.
+///
+/// The hasher must not depend on the multimap, because equal multimaps with different hashers must have equal hashes.
+/// With `std`, it is keyed with a random key that is chosen once for each process.
+#[cfg(feature = "std")]
+fn part_hasher() -> impl Hasher {
+  static STATE: OnceLock<RandomState> = OnceLock::new();
+  STATE.get_or_init(RandomState::new).build_hasher()
+}
+
+/// Returns a hasher for parts of a multimap that are combined independently of their order.
+///
+/// Without `std`, there is no source of randomness, so this is a hash function with a fixed state.
+#[cfg(not(feature = "std"))]
+fn part_hasher() -> impl Hasher {
+  FnvHasher::default()
+}
+
+/// A hasher with a fixed state, used for indices into the internal lists of a multimap and for hashes of keys that were
+/// already computed with the hasher of a multimap.
+///
+/// This is the 64-bit FNV-1a hash function.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+  fn default() -> Self {
+    FnvHasher(0xcbf2_9ce4_8422_2325)
+  }
+}
+
+impl Hasher for FnvHasher {
+  fn finish(&self) -> u64 {
+    self.0
+  }
+
+  fn write(&mut self, bytes: &[u8]) {
+    for &byte in bytes {
+      self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+    }
+  }
+}
+
 #[must_use]
 fn raw_entry<'map, Key, KeyQuery, Value, State>(
   keys: &VecList<Key>,
@@ -3524,11 +4916,21 @@ where
 
 #[allow(unused_results)]
 #[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
 mod test {
-  use coverage_helper::test;
+  use std::collections::{hash_map::DefaultHasher, HashSet};
 
   use super::*;
 
+  fn hash<Type>(value: &Type) -> u64
+  where
+    Type: Hash,
+  {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+  }
+
   #[test]
   fn test_bounds() {
     fn check_bounds<Type: Send + Sync>() {}
@@ -3542,6 +4944,7 @@ mod test {
     check_bounds::<KeyValues<'static, (), ()>>();
     check_bounds::<KeyValuesMut<'static, (), ()>>();
     check_bounds::<ListOrderedMultimap<(), ()>>();
+    check_bounds::<Transaction<'static, (), ()>>();
     check_bounds::<Values<'static, (), ()>>();
     check_bounds::<ValuesMut<'static, (), ()>>();
   }
@@ -4410,6 +5813,53 @@ mod test {
     assert_eq!(map.back(), None);
   }
 
+  #[test]
+  fn test_list_ordered_multimap_begin() {
+    let mut map = ListOrderedMultimap::new();
+    map.insert("key1", "value1");
+    map.append("key2", "value2");
+    map.append("key1", "value3");
+
+    let mut transaction = map.begin();
+    transaction.append("key1", "value4");
+    transaction.remove(&"key2");
+    transaction.retain(|_, &mut value| value != "value1");
+    assert_eq!(transaction.values_len(), 2);
+    transaction.rollback();
+
+    let mut iter = map.iter();
+    assert_eq!(iter.next(), Some((&"key1", &"value1")));
+    assert_eq!(iter.next(), Some((&"key2", &"value2")));
+    assert_eq!(iter.next(), Some((&"key1", &"value3")));
+    assert_eq!(iter.next(), None);
+
+    let mut iter = map.get_all(&"key1");
+    assert_eq!(iter.next(), Some(&"value1"));
+    assert_eq!(iter.next_back(), Some(&"value3"));
+    assert_eq!(iter.next(), None);
+
+    {
+      let mut transaction = map.begin();
+      transaction.pop_front();
+    }
+
+    assert_eq!(map.front(), Some((&"key1", &"value1")));
+
+    let mut transaction = map.begin();
+    transaction.pop_front();
+    transaction.commit();
+
+    assert_eq!(map.front(), Some((&"key2", &"value2")));
+    assert_eq!(map.get_all(&"key1").collect::<Vec<_>>(), [&"value3"]);
+
+    let storage = Arc::as_ptr(&map.storage.0);
+    let transaction = map.begin();
+    assert_eq!(transaction.values_len(), 2);
+    transaction.commit();
+    assert_eq!(Arc::as_ptr(&map.storage.0), storage);
+    assert!(!map.storage.is_shared());
+  }
+
   #[test]
   fn test_list_ordered_multimap_clear() {
     let mut map = ListOrderedMultimap::new();
@@ -4423,6 +5873,51 @@ mod test {
     assert_eq!(map.get(&"key2"), None);
   }
 
+  #[test]
+  fn test_list_ordered_multimap_clone_shared() {
+    let mut map = ListOrderedMultimap::new();
+    map.insert("key1", "value1");
+    map.append("key2", "value2");
+
+    let mut clone = map.clone();
+    assert!(map.storage.is_shared());
+    assert!(Arc::ptr_eq(&map.storage.0, &clone.storage.0));
+
+    map.append("key1", "value3");
+    assert!(!map.storage.is_shared());
+    assert!(!clone.storage.is_shared());
+
+    let mut iter = map.iter();
+    assert_eq!(iter.next(), Some((&"key1", &"value1")));
+    assert_eq!(iter.next(), Some((&"key2", &"value2")));
+    assert_eq!(iter.next(), Some((&"key1", &"value3")));
+    assert_eq!(iter.next(), None);
+
+    let snapshot = clone.clone();
+    *clone.get_mut(&"key2").unwrap() = "value4";
+
+    let mut iter = clone.iter();
+    assert_eq!(iter.next(), Some((&"key1", &"value1")));
+    assert_eq!(iter.next(), Some((&"key2", &"value4")));
+    assert_eq!(iter.next(), None);
+
+    let mut iter = snapshot.iter();
+    assert_eq!(iter.next(), Some((&"key1", &"value1")));
+    assert_eq!(iter.next(), Some((&"key2", &"value2")));
+    assert_eq!(iter.next(), None);
+
+    let mut cleared = snapshot.clone();
+    cleared.clear();
+    assert!(cleared.is_empty());
+    assert_eq!(snapshot.values_len(), 2);
+
+    let mut iter = snapshot.clone().into_iter();
+    assert_eq!(iter.next(), Some(("key1", "value1")));
+    assert_eq!(iter.next(), Some(("key2", "value2")));
+    assert_eq!(iter.next(), None);
+    assert_eq!(snapshot.values_len(), 2);
+  }
+
   #[test]
   fn test_list_ordered_multimap_contains_key() {
     let mut map = ListOrderedMultimap::new();
@@ -4482,6 +5977,55 @@ mod test {
     assert_eq!(map.entry_len(&"key"), 0);
   }
 
+  #[test]
+  fn test_list_ordered_multimap_eq_per_key() {
+    let map: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("a", 3)].into_iter().collect();
+
+    let other: ListOrderedMultimap<_, _> = [("b", 2), ("a", 1), ("a", 3)].into_iter().collect();
+    assert!(map.eq_per_key(&other));
+    assert!(other.eq_per_key(&map));
+
+    let other: ListOrderedMultimap<_, _> = [("a", 3), ("b", 2), ("a", 1)].into_iter().collect();
+    assert!(!map.eq_per_key(&other));
+
+    let other: ListOrderedMultimap<_, _> = [("a", 1), ("c", 2), ("a", 3)].into_iter().collect();
+    assert!(!map.eq_per_key(&other));
+
+    let other: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("b", 3)].into_iter().collect();
+    assert!(!map.eq_per_key(&other));
+
+    let other: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2)].into_iter().collect();
+    assert!(!map.eq_per_key(&other));
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_eq_unordered() {
+    let map: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("a", 1), ("a", 3)]
+      .into_iter()
+      .collect();
+
+    let other: ListOrderedMultimap<_, _> = [("a", 3), ("a", 1), ("b", 2), ("a", 1)]
+      .into_iter()
+      .collect();
+    assert!(map.eq_unordered(&other));
+    assert!(other.eq_unordered(&map));
+
+    let other: ListOrderedMultimap<_, _> = [("a", 3), ("a", 1), ("b", 2), ("a", 3)]
+      .into_iter()
+      .collect();
+    assert!(!map.eq_unordered(&other));
+
+    let other: ListOrderedMultimap<_, _> = [("a", 3), ("a", 1), ("a", 1), ("a", 2)]
+      .into_iter()
+      .collect();
+    assert!(!map.eq_unordered(&other));
+
+    let other: ListOrderedMultimap<_, _> = [("a", 3), ("a", 1), ("b", 2), ("b", 1)]
+      .into_iter()
+      .collect();
+    assert!(!map.eq_unordered(&other));
+  }
+
   #[test]
   fn test_list_ordered_multimap_equality() {
     let mut map_1 = ListOrderedMultimap::new();
@@ -4603,6 +6147,53 @@ mod test {
     assert_eq!(map.get_mut(&"key"), Some(&mut "value"));
   }
 
+  #[allow(clippy::mutable_key_type)]
+  #[test]
+  fn test_list_ordered_multimap_hash() {
+    let map: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("a", 3)].into_iter().collect();
+    assert_eq!(hash(&map), hash(&map.clone()));
+
+    let mut set = HashSet::new();
+    assert!(set.insert(map.clone()));
+    assert!(!set.insert(map.clone()));
+
+    let other: ListOrderedMultimap<_, _> = [("b", 2), ("a", 1), ("a", 3)].into_iter().collect();
+    assert_ne!(hash(&map), hash(&other));
+    assert!(set.insert(other));
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_hash_per_key() {
+    let map: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("a", 3)].into_iter().collect();
+    let hash_per_key = |map: &ListOrderedMultimap<&str, u32>| {
+      let mut hasher = DefaultHasher::new();
+      map.hash_per_key(&mut hasher);
+      hasher.finish()
+    };
+
+    let other: ListOrderedMultimap<_, _> = [("b", 2), ("a", 1), ("a", 3)].into_iter().collect();
+    assert_eq!(hash_per_key(&map), hash_per_key(&other));
+
+    let other: ListOrderedMultimap<_, _> = [("a", 3), ("b", 2), ("a", 1)].into_iter().collect();
+    assert_ne!(hash_per_key(&map), hash_per_key(&other));
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_hash_unordered() {
+    let map: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("a", 3)].into_iter().collect();
+    let hash_unordered = |map: &ListOrderedMultimap<&str, u32>| {
+      let mut hasher = DefaultHasher::new();
+      map.hash_unordered(&mut hasher);
+      hasher.finish()
+    };
+
+    let other: ListOrderedMultimap<_, _> = [("a", 3), ("b", 2), ("a", 1)].into_iter().collect();
+    assert_eq!(hash_unordered(&map), hash_unordered(&other));
+
+    let other: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("a", 1)].into_iter().collect();
+    assert_ne!(hash_unordered(&map), hash_unordered(&other));
+  }
+
   #[test]
   fn test_list_ordered_multimap_insert() {
     let mut map = ListOrderedMultimap::new();
@@ -4753,6 +6344,27 @@ mod test {
     assert_eq!(map.values_len(), 0);
   }
 
+  #[test]
+  fn test_list_ordered_multimap_ord() {
+    let map: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2)].into_iter().collect();
+    let greater: ListOrderedMultimap<_, _> = [("a", 1), ("b", 3)].into_iter().collect();
+    let longer: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("a", 0)].into_iter().collect();
+    let empty = ListOrderedMultimap::new();
+
+    assert_eq!(map.cmp(&map.clone()), Ordering::Equal);
+    assert_eq!(map.cmp(&greater), Ordering::Less);
+    assert_eq!(map.cmp(&longer), Ordering::Less);
+    assert_eq!(map.cmp(&empty), Ordering::Greater);
+    assert_eq!(map.partial_cmp(&greater), Some(Ordering::Less));
+
+    let mut maps = vec![longer.clone(), greater.clone(), empty.clone(), map.clone()];
+    maps.sort();
+    assert_eq!(maps, [empty, map, longer, greater]);
+
+    let nan: ListOrderedMultimap<_, _> = [("a", f64::NAN)].into_iter().collect();
+    assert_eq!(nan.partial_cmp(&nan), None);
+  }
+
   #[test]
   fn test_list_ordered_multimap_pack_to() {
     let mut map: ListOrderedMultimap<&str, &str> = ListOrderedMultimap::with_capacity(5, 5);
@@ -5080,6 +6692,110 @@ mod test {
     assert_eq!(iter.next(), None);
   }
 
+  #[test]
+  fn test_list_ordered_multimap_retain_order() {
+    let mut map = ListOrderedMultimap::new();
+
+    map.append("key1", 1);
+    map.append("key2", 2);
+    map.append("key1", 3);
+    map.append("key3", 4);
+    map.append("key2", 5);
+    map.append("key1", 6);
+    map.pop_front();
+    map.append("key2", 1);
+
+    let mut visited = Vec::new();
+    map.retain(|&key, &mut value| {
+      visited.push((key, value));
+      value % 2 == 0
+    });
+
+    assert_eq!(
+      visited,
+      [
+        ("key2", 2),
+        ("key1", 3),
+        ("key3", 4),
+        ("key2", 5),
+        ("key1", 6),
+        ("key2", 1)
+      ]
+    );
+    assert_eq!(
+      map.iter().collect::<Vec<_>>(),
+      [(&"key2", &2), (&"key3", &4), (&"key1", &6)]
+    );
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_retain_adjacent() {
+    let mut map = ListOrderedMultimap::new();
+
+    map.append("key1", 1);
+    map.append("key2", 2);
+    map.append("key1", -1);
+    map.append("key1", -2);
+    map.append("key1", 3);
+    map.append("key2", -3);
+    map.append("key2", -4);
+    map.append("key3", -5);
+    map.append("key3", -6);
+
+    map.retain(|_, &mut value| value >= 0);
+
+    assert_eq!(
+      map.iter().collect::<Vec<_>>(),
+      [(&"key1", &1), (&"key2", &2), (&"key1", &3)]
+    );
+    assert_eq!(map.get_all(&"key1").collect::<Vec<_>>(), [&1, &3]);
+    assert_eq!(map.get_all(&"key1").rev().collect::<Vec<_>>(), [&3, &1]);
+    assert_eq!(map.get_all(&"key2").rev().collect::<Vec<_>>(), [&2]);
+    assert_eq!(map.keys().collect::<Vec<_>>(), [&"key1", &"key2"]);
+
+    map.append("key2", 4);
+    assert_eq!(map.get_all(&"key2").collect::<Vec<_>>(), [&2, &4]);
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_transaction() {
+    let mut map = ListOrderedMultimap::new();
+    map.insert("key1", 1);
+    map.append("key2", 2);
+
+    let result = map.transaction(|map| {
+      map.insert("key1", 3);
+      map.append("key3", 4);
+      Err::<(), _>("error")
+    });
+    assert_eq!(result, Err("error"));
+    assert_eq!(
+      map.iter().collect::<Vec<_>>(),
+      [(&"key1", &1), (&"key2", &2)]
+    );
+
+    let result = map.transaction(|map| {
+      map.append("key3", 4);
+      Ok::<_, ()>(map.values_len())
+    });
+    assert_eq!(result, Ok(3));
+    assert_eq!(
+      map.iter().collect::<Vec<_>>(),
+      [(&"key1", &1), (&"key2", &2), (&"key3", &4)]
+    );
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+      map.transaction(|map| {
+        map.clear();
+        panic!("transaction panicked");
+        #[allow(unreachable_code)]
+        Ok::<(), ()>(())
+      })
+    }));
+    assert!(result.is_err());
+    assert_eq!(map.values_len(), 3);
+  }
+
   #[test]
   fn test_list_ordered_multimap_values() {
     let mut map = ListOrderedMultimap::new();
@@ -5149,6 +6865,33 @@ mod test {
     assert_eq!(map.values_len(), 0);
   }
 
+  #[test]
+  fn test_list_ordered_multimap_view_for_keys() {
+    let mut map = ListOrderedMultimap::new();
+
+    map.insert("key1", "value1");
+    map.append("key2", "value2");
+    map.append("key3", "value3");
+    map.append("key1", "value4");
+
+    let iter = map.view_for_keys(&["key3", "key1", "key4", "key3"]);
+    assert_eq!(
+      iter.collect::<Vec<_>>(),
+      [
+        (&"key1", &"value1"),
+        (&"key3", &"value3"),
+        (&"key1", &"value4")
+      ]
+    );
+
+    let mut iter = map.view_for_keys(&["key4"]);
+    assert_eq!(iter.next(), None);
+
+    map.remove(&"key1");
+    let iter = map.view_for_keys(&["key1", "key2"]);
+    assert_eq!(iter.collect::<Vec<_>>(), [(&"key2", &"value2")]);
+  }
+
   #[test]
   fn test_list_ordered_multimap_with_capacity() {
     let map: ListOrderedMultimap<&str, &str> = ListOrderedMultimap::with_capacity(1, 2);
@@ -5192,6 +6935,44 @@ mod test {
     );
   }
 
+  #[test]
+  fn test_per_key_eq() {
+    let map: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("a", 3)].into_iter().collect();
+    let other: ListOrderedMultimap<_, _> = [("b", 2), ("a", 1), ("a", 3)].into_iter().collect();
+
+    assert_eq!(PerKeyEq(map.clone()), PerKeyEq::from(other.clone()));
+    assert_eq!(hash(&PerKeyEq(map.clone())), hash(&PerKeyEq(other)));
+
+    let other: ListOrderedMultimap<_, _> = [("a", 3), ("b", 2), ("a", 1)].into_iter().collect();
+    assert_ne!(PerKeyEq(map.clone()), PerKeyEq(other.clone()));
+    assert_ne!(hash(&PerKeyEq(map)), hash(&PerKeyEq(other)));
+  }
+
+  #[test]
+  fn test_transaction_debug() {
+    let mut map = ListOrderedMultimap::new();
+    map.insert("key", "value");
+
+    let transaction = map.begin();
+    assert_eq!(
+      format!("{transaction:?}"),
+      r#"Transaction({"key": "value"})"#
+    );
+  }
+
+  #[test]
+  fn test_unordered_eq() {
+    let map: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("a", 3)].into_iter().collect();
+    let other: ListOrderedMultimap<_, _> = [("a", 3), ("b", 2), ("a", 1)].into_iter().collect();
+
+    assert_eq!(UnorderedEq(map.clone()), UnorderedEq::from(other.clone()));
+    assert_eq!(hash(&UnorderedEq(map.clone())), hash(&UnorderedEq(other)));
+
+    let other: ListOrderedMultimap<_, _> = [("a", 1), ("b", 2), ("a", 1)].into_iter().collect();
+    assert_ne!(UnorderedEq(map.clone()), UnorderedEq(other.clone()));
+    assert_ne!(hash(&UnorderedEq(map)), hash(&UnorderedEq(other)));
+  }
+
   #[test]
   fn test_vacant_entry_debug() {
     let mut map: ListOrderedMultimap<&str, &str> = ListOrderedMultimap::new();
@@ -5357,11 +7138,69 @@ mod test {
     assert_eq!(iter.size_hint(), (0, Some(0)));
   }
 
+  #[test]
+  fn test_view_for_keys_debug() {
+    let mut map = ListOrderedMultimap::new();
+
+    map.insert("key1", "value1");
+    map.append("key2", "value2");
+    map.append("key1", "value3");
+
+    let iter = map.view_for_keys(&["key1"]);
+    assert_eq!(
+      format!("{iter:?}"),
+      r#"ViewForKeys([("key1", "value1"), ("key1", "value3")])"#
+    );
+  }
+
+  #[test]
+  fn test_view_for_keys_double_ended() {
+    let mut map = ListOrderedMultimap::new();
+
+    map.insert("key1", "value1");
+    map.append("key2", "value2");
+    map.append("key3", "value3");
+    map.append("key1", "value4");
+    map.append("key2", "value5");
+
+    let mut iter = map.view_for_keys(&["key1", "key2"]);
+    assert_eq!(iter.next(), Some((&"key1", &"value1")));
+    assert_eq!(iter.next_back(), Some((&"key2", &"value5")));
+    assert_eq!(iter.next(), Some((&"key2", &"value2")));
+    assert_eq!(iter.next_back(), Some((&"key1", &"value4")));
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.next_back(), None);
+  }
+
+  #[test]
+  fn test_view_for_keys_empty() {
+    let map: ListOrderedMultimap<&str, &str> = ListOrderedMultimap::new();
+    let mut iter = map.view_for_keys(&["key"]);
+    assert_eq!(iter.next_back(), None);
+    assert_eq!(iter.next(), None);
+  }
+
+  #[test]
+  fn test_view_for_keys_size_hint() {
+    let mut map = ListOrderedMultimap::new();
+
+    map.insert("key1", "value1");
+    map.append("key2", "value2");
+    map.append("key1", "value3");
+
+    let mut iter = map.view_for_keys(&["key1"]);
+    assert_eq!(iter.size_hint(), (2, Some(2)));
+    iter.next();
+    assert_eq!(iter.size_hint(), (1, Some(1)));
+    iter.next();
+    assert_eq!(iter.size_hint(), (0, Some(0)));
+  }
+
   #[should_panic]
   #[test]
   fn test_dummy_hasher_finish() {
     let hasher = DummyHasher;
-    hasher.finish();
+    let _ = hasher.finish();
   }
 
   #[should_panic]
@@ -0,0 +1,634 @@
+//! Provides types related to merging concurrent changes to a [`ListOrderedMultimap`].
+
+use alloc::{vec, vec::Vec};
+use core::hash::{BuildHasher, BuildHasherDefault, Hash};
+
+use hashbrown::HashMap;
+
+use crate::{
+  diff::{Alignment, Pairing},
+  list_ordered_multimap::FnvHasher,
+  ListOrderedMultimap,
+};
+
+/// A change to a key-value pair made by both sides of a merge that cannot be reconciled.
+///
+/// The merged multimap always contains our side of a conflict.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Conflict<Key, Value> {
+  /// Both sides changed the value of the key-value pair, but to different values.
+  BothChanged {
+    /// The position of our key-value pair in the merged multimap.
+    position: usize,
+
+    /// The key of the key-value pair.
+    key: Key,
+
+    /// The value in the base multimap.
+    base: Value,
+
+    /// Our value, which is the one in the merged multimap.
+    ours: Value,
+
+    /// Their value.
+    theirs: Value,
+  },
+
+  /// We changed the value of the key-value pair, but they removed it.
+  OursChangedTheirsRemoved {
+    /// The position of our key-value pair in the merged multimap.
+    position: usize,
+
+    /// The key of the key-value pair.
+    key: Key,
+
+    /// The value in the base multimap.
+    base: Value,
+
+    /// Our value, which is the one in the merged multimap.
+    ours: Value,
+  },
+
+  /// We removed the key-value pair, but they changed its value. The key-value pair is not in the merged multimap.
+  OursRemovedTheirsChanged {
+    /// The key of the key-value pair.
+    key: Key,
+
+    /// The value in the base multimap.
+    base: Value,
+
+    /// Their value.
+    theirs: Value,
+  },
+}
+
+/// The result of [`three_way_merge`].
+#[derive(Clone, Debug)]
+pub struct Merge<Key, Value, State> {
+  /// The conflicts in the order of the key-value pairs in the base multimap.
+  conflicts: Vec<Conflict<Key, Value>>,
+
+  /// The merged multimap.
+  merged: ListOrderedMultimap<Key, Value, State>,
+}
+
+impl<Key, Value, State> Merge<Key, Value, State> {
+  /// Returns the conflicts in the order of the key-value pairs in the base multimap.
+  #[must_use]
+  pub fn conflicts(&self) -> &[Conflict<Key, Value>] {
+    &self.conflicts
+  }
+
+  /// Returns the merged multimap and the conflicts.
+  #[must_use]
+  pub fn into_parts(
+    self,
+  ) -> (
+    ListOrderedMultimap<Key, Value, State>,
+    Vec<Conflict<Key, Value>>,
+  ) {
+    (self.merged, self.conflicts)
+  }
+
+  /// Returns whether the merge has no conflicts.
+  #[must_use]
+  pub fn is_clean(&self) -> bool {
+    self.conflicts.is_empty()
+  }
+
+  /// Returns the merged multimap.
+  #[must_use]
+  pub fn merged(&self) -> &ListOrderedMultimap<Key, Value, State> {
+    &self.merged
+  }
+}
+
+/// Where a key-value pair in the merge originates from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Origin {
+  /// The key-value pair at the given position in the base multimap.
+  Base(usize),
+
+  /// A key-value pair we inserted.
+  Ours,
+
+  /// A key-value pair they inserted.
+  Theirs,
+}
+
+/// A key-value pair in the merge.
+#[derive(Debug)]
+struct Item<'map, Key, Value> {
+  key: &'map Key,
+  origin: Origin,
+  value: &'map Value,
+}
+
+impl<Key, Value> Clone for Item<'_, Key, Value> {
+  fn clone(&self) -> Self {
+    *self
+  }
+}
+
+impl<Key, Value> Copy for Item<'_, Key, Value> {}
+
+/// A key-value pair of theirs that is placed after a run of ours.
+enum Placed<'map, Key, Value> {
+  /// The key-value pair at the given position among our key-value pairs, which they moved.
+  Moved(usize),
+
+  /// A key-value pair they inserted.
+  Inserted(&'map Key, &'map Value),
+}
+
+/// Merges the changes made by `ours` and `theirs` to their common ancestor `base`.
+///
+/// The changes of each side are determined as with [`ListOrderedMultimap::diff`]. Key-value pairs appended or inserted
+/// by either side are kept, key-value pairs removed by either side are removed and changed values are taken from the
+/// side that changed them. This includes reordering the values of a key, which is a move of key-value pairs in the
+/// global order.
+///
+/// Ordering conflicts are settled deterministically: key-value pairs inserted by both sides at the same place are
+/// ordered ours first, identical key-value pairs inserted by both sides at the same place are only kept once, and a
+/// key-value pair moved by both sides ends up where we moved it.
+///
+/// Changes that cannot be reconciled are reported as [`Conflict`]s. The merged multimap then contains our side.
+///
+/// The merged multimap uses a clone of the hasher of `ours`.
+///
+/// Complexity: O(C + |B| + |O| + |T| + D) where C is the complexity of [`ListOrderedMultimap::diff`] of `base` with
+/// `ours` and with `theirs`, and |B|, |O| and |T| are the number of values of `base`, `ours` and `theirs`
+/// respectively. D is the number of comparisons of key-value pairs inserted by both sides at the same place whose keys
+/// have the same hash, which is at most the product of the numbers of such key-value pairs of each side.
+///
+/// # Examples
+///
+/// ```
+/// use ordered_multimap::ListOrderedMultimap;
+/// use ordered_multimap::merge::{three_way_merge, Conflict};
+///
+/// let mut base = ListOrderedMultimap::new();
+/// base.append("a", 1);
+/// base.append("b", 2);
+/// base.append("c", 3);
+///
+/// let mut ours = base.clone();
+/// ours.append("d", 4);
+/// ours.get_mut(&"b").map(|value| *value = 20);
+///
+/// let mut theirs = base.clone();
+/// theirs.remove(&"a");
+/// theirs.get_mut(&"b").map(|value| *value = 200);
+///
+/// let merge = three_way_merge(&base, &ours, &theirs);
+///
+/// let mut iter = merge.merged().iter();
+/// assert_eq!(iter.next(), Some((&"b", &20)));
+/// assert_eq!(iter.next(), Some((&"c", &3)));
+/// assert_eq!(iter.next(), Some((&"d", &4)));
+/// assert_eq!(iter.next(), None);
+///
+/// assert_eq!(
+///   merge.conflicts(),
+///   [Conflict::BothChanged { position: 0, key: "b", base: 2, ours: 20, theirs: 200 }]
+/// );
+/// ```
+#[must_use]
+pub fn three_way_merge<Key, Value, BaseState, State, TheirState>(
+  base: &ListOrderedMultimap<Key, Value, BaseState>,
+  ours: &ListOrderedMultimap<Key, Value, State>,
+  theirs: &ListOrderedMultimap<Key, Value, TheirState>,
+) -> Merge<Key, Value, State>
+where
+  Key: Clone + Eq + Hash,
+  State: BuildHasher + Clone,
+  Value: Clone + PartialEq,
+{
+  let base_pairs: Vec<_> = base.iter().collect();
+  let our_pairs: Vec<_> = ours.iter().collect();
+  let their_pairs: Vec<_> = theirs.iter().collect();
+  let hash_key = |key: &Key| ours.hasher().hash_one(key);
+  let our_changes = Alignment::new(&base_pairs, &our_pairs, hash_key);
+  let their_changes = Alignment::new(&base_pairs, &their_pairs, hash_key);
+  let their_move = |base_index: usize| {
+    matches!(their_changes.old[base_index], Pairing::Moved(_))
+      && !matches!(our_changes.old[base_index], Pairing::Moved(_))
+  };
+
+  let mut items: Vec<_> = our_pairs
+    .iter()
+    .zip(&our_changes.new)
+    .map(|(&(key, value), &origin)| Item {
+      key,
+      origin: origin.map_or(Origin::Ours, Origin::Base),
+      value,
+    })
+    .collect();
+
+  // Apply their removals and value changes to our key-value pairs, noting the conflicts. Removals are applied after
+  // all changes, so that the position of each base key-value pair is its position on our side.
+  let mut conflicts = vec![];
+  let mut our_positions = vec![None; base_pairs.len()];
+  let mut removed = vec![false; items.len()];
+
+  for (our_index, &origin) in our_changes.new.iter().enumerate() {
+    if let Some(base_index) = origin {
+      our_positions[base_index] = Some(our_index);
+    }
+  }
+
+  for (base_index, (&our_change, &their_change)) in
+    our_changes.old.iter().zip(&their_changes.old).enumerate()
+  {
+    let (key, base_value) = base_pairs[base_index];
+    let position = our_positions[base_index];
+
+    match (our_change, their_change) {
+      (Pairing::Removed, Pairing::Replaced(their_index)) => {
+        conflicts.push((
+          base_index,
+          Conflict::OursRemovedTheirsChanged {
+            key: key.clone(),
+            base: base_value.clone(),
+            theirs: their_pairs[their_index].1.clone(),
+          },
+        ));
+      }
+      (Pairing::Replaced(our_index), Pairing::Removed) => {
+        conflicts.push((
+          base_index,
+          Conflict::OursChangedTheirsRemoved {
+            position: 0,
+            key: key.clone(),
+            base: base_value.clone(),
+            ours: our_pairs[our_index].1.clone(),
+          },
+        ));
+      }
+      (Pairing::Replaced(our_index), Pairing::Replaced(their_index)) => {
+        let (our_value, their_value) = (our_pairs[our_index].1, their_pairs[their_index].1);
+
+        if our_value != their_value {
+          conflicts.push((
+            base_index,
+            Conflict::BothChanged {
+              position: 0,
+              key: key.clone(),
+              base: base_value.clone(),
+              ours: our_value.clone(),
+              theirs: their_value.clone(),
+            },
+          ));
+        }
+      }
+      (_, Pairing::Removed) => {
+        if let Some(position) = position {
+          removed[position] = true;
+        }
+      }
+      (_, Pairing::Replaced(their_index)) => {
+        if let Some(position) = position {
+          items[position].value = their_pairs[their_index].1;
+        }
+      }
+      _ => {}
+    }
+  }
+
+  let mut removed = removed.into_iter();
+  items.retain(|_| !removed.next().unwrap());
+
+  // Their moved key-value pairs are taken out of our order. Every other key-value pair of ours stays in our order and
+  // starts a run with the key-value pairs we inserted after it. Their moved and inserted key-value pairs are grouped by
+  // the run of the key-value pair that precedes them on their side, and are placed in their order after that run.
+  let mut item_positions = vec![None; base_pairs.len()];
+  let mut base_runs = vec![None; base_pairs.len()];
+  let mut runs = 1;
+
+  for (position, item) in items.iter().enumerate() {
+    if let Origin::Base(base_index) = item.origin {
+      item_positions[base_index] = Some(position);
+
+      if !their_move(base_index) {
+        base_runs[base_index] = Some(runs);
+        runs += 1;
+      }
+    }
+  }
+
+  let mut groups: Vec<Vec<Placed<'_, Key, Value>>> = (0..runs).map(|_| Vec::new()).collect();
+  let mut run = 0;
+
+  for (&(key, value), &origin) in their_pairs.iter().zip(&their_changes.new) {
+    match origin {
+      Some(base_index) => match (base_runs[base_index], item_positions[base_index]) {
+        (Some(base_run), _) => run = base_run,
+        (None, Some(position)) => groups[run].push(Placed::Moved(position)),
+        (None, None) => {}
+      },
+      None => groups[run].push(Placed::Inserted(key, value)),
+    }
+  }
+
+  let mut merged_items = Vec::with_capacity(items.len() + their_pairs.len());
+  let mut groups = groups.into_iter();
+  let mut run_start = 0;
+
+  for item in &items {
+    match item.origin {
+      Origin::Base(base_index) if their_move(base_index) => continue,
+      Origin::Base(_) => {
+        place_group(
+          &mut merged_items,
+          run_start,
+          groups.next().unwrap(),
+          &items,
+          hash_key,
+        );
+        merged_items.push(*item);
+        run_start = merged_items.len();
+      }
+      _ => merged_items.push(*item),
+    }
+  }
+
+  place_group(
+    &mut merged_items,
+    run_start,
+    groups.next().unwrap(),
+    &items,
+    hash_key,
+  );
+  let items = merged_items;
+
+  let mut positions = vec![0; base_pairs.len()];
+
+  for (position, item) in items.iter().enumerate() {
+    if let Origin::Base(base_index) = item.origin {
+      positions[base_index] = position;
+    }
+  }
+
+  let conflicts = conflicts
+    .into_iter()
+    .map(|(base_index, mut conflict)| {
+      if let Conflict::BothChanged { position, .. }
+      | Conflict::OursChangedTheirsRemoved { position, .. } = &mut conflict
+      {
+        *position = positions[base_index];
+      }
+
+      conflict
+    })
+    .collect();
+
+  let mut merged = ListOrderedMultimap::with_capacity_and_hasher(
+    ours.keys_len(),
+    items.len(),
+    ours.hasher().clone(),
+  );
+  merged.extend(
+    items
+      .into_iter()
+      .map(|item| (item.key.clone(), item.value.clone())),
+  );
+
+  Merge { conflicts, merged }
+}
+
+/// Places a group of their moved and inserted key-value pairs after the run of our key-value pairs that starts at
+/// `run_start`. Their inserted key-value pairs that are identical to ones we inserted in the run are only kept once.
+fn place_group<'map, Key, Value, HashKey>(
+  merged_items: &mut Vec<Item<'map, Key, Value>>,
+  run_start: usize,
+  group: Vec<Placed<'map, Key, Value>>,
+  items: &[Item<'map, Key, Value>],
+  hash_key: HashKey,
+) where
+  Key: Eq,
+  Value: PartialEq,
+  HashKey: Fn(&Key) -> u64,
+{
+  let run_end = merged_items.len();
+  let mut candidates: Option<HashMap<u64, Vec<usize>, BuildHasherDefault<FnvHasher>>> = None;
+
+  for placed in group {
+    match placed {
+      Placed::Moved(position) => merged_items.push(items[position]),
+      Placed::Inserted(key, value) => {
+        let candidates = candidates.get_or_insert_with(|| {
+          let mut candidates: HashMap<_, Vec<_>, _> = HashMap::default();
+
+          for (index, item) in merged_items[run_start..run_end].iter().enumerate() {
+            if item.origin == Origin::Ours {
+              candidates
+                .entry(hash_key(item.key))
+                .or_default()
+                .push(run_start + index);
+            }
+          }
+
+          candidates
+        });
+        let duplicate = candidates.get_mut(&hash_key(key)).and_then(|indices| {
+          let offset = indices.iter().position(|&index| {
+            let item = &merged_items[index];
+            item.key == key && item.value == value
+          })?;
+          Some(indices.remove(offset))
+        });
+
+        if duplicate.is_none() {
+          merged_items.push(Item {
+            key,
+            origin: Origin::Theirs,
+            value,
+          });
+        }
+      }
+    }
+  }
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use super::*;
+
+  fn map(pairs: &[(char, u32)]) -> ListOrderedMultimap<char, u32> {
+    pairs.iter().copied().collect()
+  }
+
+  fn pairs(map: &ListOrderedMultimap<char, u32>) -> Vec<(char, u32)> {
+    map.iter().map(|(&key, &value)| (key, value)).collect()
+  }
+
+  #[test]
+  fn test_merge() {
+    let base = map(&[('a', 1), ('b', 2)]);
+    let merge = three_way_merge(&base, &base, &map(&[('a', 1), ('b', 3)]));
+
+    assert!(merge.is_clean());
+    assert!(merge.conflicts().is_empty());
+    assert_eq!(pairs(merge.merged()), [('a', 1), ('b', 3)]);
+
+    let (merged, conflicts) = merge.into_parts();
+    assert_eq!(pairs(&merged), [('a', 1), ('b', 3)]);
+    assert!(conflicts.is_empty());
+  }
+
+  #[test]
+  fn test_three_way_merge_append() {
+    let base = map(&[('a', 1), ('b', 2)]);
+    let ours = map(&[('a', 1), ('b', 2), ('c', 3), ('a', 4)]);
+    let theirs = map(&[('a', 1), ('b', 2), ('d', 5), ('c', 3)]);
+    let merge = three_way_merge(&base, &ours, &theirs);
+
+    assert!(merge.is_clean());
+    assert_eq!(
+      pairs(merge.merged()),
+      [('a', 1), ('b', 2), ('c', 3), ('a', 4), ('d', 5)]
+    );
+  }
+
+  #[test]
+  fn test_three_way_merge_conflict() {
+    let base = map(&[('a', 1), ('b', 2), ('c', 3)]);
+    let ours = map(&[('b', 20), ('c', 30)]);
+    let theirs = map(&[('a', 10), ('b', 200), ('c', 30)]);
+    let merge = three_way_merge(&base, &ours, &theirs);
+
+    assert_eq!(pairs(merge.merged()), [('b', 20), ('c', 30)]);
+    assert_eq!(
+      merge.conflicts(),
+      [
+        Conflict::OursRemovedTheirsChanged {
+          key: 'a',
+          base: 1,
+          theirs: 10
+        },
+        Conflict::BothChanged {
+          position: 0,
+          key: 'b',
+          base: 2,
+          ours: 20,
+          theirs: 200
+        },
+      ]
+    );
+
+    let merge = three_way_merge(&base, &theirs, &ours);
+
+    assert_eq!(pairs(merge.merged()), [('a', 10), ('b', 200), ('c', 30)]);
+    assert_eq!(
+      merge.conflicts(),
+      [
+        Conflict::OursChangedTheirsRemoved {
+          position: 0,
+          key: 'a',
+          base: 1,
+          ours: 10
+        },
+        Conflict::BothChanged {
+          position: 1,
+          key: 'b',
+          base: 2,
+          ours: 200,
+          theirs: 20
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn test_three_way_merge_insert() {
+    let base = map(&[('a', 1), ('b', 2)]);
+    let ours = map(&[('a', 1), ('x', 3), ('y', 4), ('b', 2)]);
+    let theirs = map(&[('z', 0), ('a', 1), ('y', 4), ('w', 5), ('b', 2)]);
+    let merge = three_way_merge(&base, &ours, &theirs);
+
+    assert!(merge.is_clean());
+    assert_eq!(
+      pairs(merge.merged()),
+      [('z', 0), ('a', 1), ('x', 3), ('y', 4), ('w', 5), ('b', 2)]
+    );
+  }
+
+  #[test]
+  fn test_three_way_merge_move() {
+    let base = map(&[('a', 1), ('b', 2), ('a', 3), ('c', 4)]);
+    let ours = map(&[('a', 1), ('b', 2), ('a', 3), ('c', 5)]);
+    let theirs = map(&[('a', 3), ('a', 1), ('b', 2), ('c', 4)]);
+    let merge = three_way_merge(&base, &ours, &theirs);
+
+    assert!(merge.is_clean());
+    assert_eq!(
+      pairs(merge.merged()),
+      [('a', 3), ('a', 1), ('b', 2), ('c', 5)]
+    );
+    assert_eq!(
+      merge.merged().get_all(&'a').copied().collect::<Vec<_>>(),
+      [3, 1]
+    );
+
+    // Both sides moving the same key-value pair keeps our order.
+    let base = map(&[('a', 1), ('b', 2), ('c', 3), ('d', 4), ('e', 5)]);
+    let ours = map(&[('e', 5), ('a', 1), ('b', 2), ('c', 3), ('d', 4)]);
+    let theirs = map(&[('a', 1), ('e', 5), ('b', 2), ('c', 3), ('d', 4)]);
+    let merge = three_way_merge(&base, &ours, &theirs);
+
+    assert!(merge.is_clean());
+    assert_eq!(pairs(merge.merged()), pairs(&ours));
+  }
+
+  #[test]
+  fn test_three_way_merge_remove() {
+    let base = map(&[('a', 1), ('b', 2), ('a', 3), ('c', 4)]);
+    let ours = map(&[('a', 1), ('b', 2), ('c', 4)]);
+    let theirs = map(&[('b', 2), ('a', 3), ('c', 5)]);
+    let merge = three_way_merge(&base, &ours, &theirs);
+
+    assert!(merge.is_clean());
+    assert_eq!(pairs(merge.merged()), [('b', 2), ('c', 5)]);
+    assert_eq!(merge.merged().keys_len(), 2);
+  }
+
+  #[test]
+  fn test_three_way_merge_unchanged_side() {
+    type Pairs = &'static [(char, u32)];
+
+    let cases: &[(Pairs, Pairs)] = &[
+      (
+        &[('a', 1), ('b', 2), ('c', 3)],
+        &[('c', 3), ('b', 2), ('a', 1)],
+      ),
+      (
+        &[('a', 1), ('b', 2), ('a', 3), ('c', 4), ('b', 5)],
+        &[('b', 5), ('a', 3), ('d', 6), ('a', 1), ('c', 7)],
+      ),
+      (
+        &[('x', 1), ('x', 1), ('y', 2), ('x', 1)],
+        &[('y', 2), ('x', 1), ('y', 3), ('x', 1)],
+      ),
+      (&[], &[('a', 1), ('a', 1)]),
+    ];
+
+    for &(base, changed) in cases {
+      let base = map(base);
+      let changed = map(changed);
+
+      let merge = three_way_merge(&base, &base, &changed);
+      assert!(merge.is_clean());
+      assert_eq!(pairs(merge.merged()), pairs(&changed));
+
+      let merge = three_way_merge(&base, &changed, &base);
+      assert!(merge.is_clean());
+      assert_eq!(pairs(merge.merged()), pairs(&changed));
+
+      let merge = three_way_merge(&base, &changed, &changed);
+      assert!(merge.is_clean());
+      assert_eq!(pairs(merge.merged()), pairs(&changed));
+    }
+  }
+}
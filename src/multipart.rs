@@ -0,0 +1,730 @@
+//! Provides parsing of `multipart/form-data` bodies, as submitted by HTML forms.
+//!
+//! A form submission is parsed into a [`Form`], a multimap from each field name to its [`Part`]s in the order in which
+//! they were submitted. Forms commonly repeat field names, for example for checkboxes or for multiple files, so a
+//! [`ListOrderedMultimap`] keeps every part together with its position among all parts.
+//!
+//! [`parse`] and [`parse_reader`] read the whole body into memory. [`MultipartReader`] instead yields each part as a
+//! [`PartReader`] that reads its body from the underlying reader, so that large files can be written elsewhere without
+//! buffering them in memory.
+//!
+//! The boundary is the `boundary` parameter of the `Content-Type` header of the request. The headers of each part are
+//! parsed with [`rfc5322::parse`] and must be valid UTF-8, and the name and filename of each part are taken from its
+//! `Content-Disposition` header. The preamble before the first boundary and the epilogue after the last boundary are
+//! ignored.
+//!
+//! # Examples
+//!
+//! ```
+//! use ordered_multimap::multipart;
+//!
+//! let body = b"--X\r\n\
+//!   Content-Disposition: form-data; name=\"tag\"\r\n\r\n\
+//!   a\r\n\
+//!   --X\r\n\
+//!   Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+//!   Content-Type: text/plain\r\n\r\n\
+//!   hello\r\n\
+//!   --X\r\n\
+//!   Content-Disposition: form-data; name=\"tag\"\r\n\r\n\
+//!   b\r\n\
+//!   --X--\r\n";
+//!
+//! let form = multipart::parse(body, "X").unwrap();
+//!
+//! let tags = form.get_all("tag").map(|part| part.body()).collect::<Vec<_>>();
+//! assert_eq!(tags, [b"a", b"b"]);
+//!
+//! let file = form.get("file").unwrap();
+//! assert_eq!(file.filename(), Some("a.txt"));
+//! assert_eq!(file.content_type(), Some("text/plain"));
+//! assert_eq!(file.body(), b"hello");
+//! ```
+//!
+//! [`rfc5322::parse`]: crate::rfc5322::parse
+
+use alloc::{string::String, vec::Vec};
+use core::{
+  fmt::{self, Display, Formatter},
+  mem, str,
+};
+use std::io::{self, Read};
+
+use crate::{
+  rfc5322::{self, HeaderName, Headers},
+  ListOrderedMultimap,
+};
+
+/// The number of bytes read from the underlying reader at once.
+const CHUNK_LENGTH: usize = 8 * 1024;
+
+/// The maximum length of the headers of a part, including the boundary line.
+const MAX_HEADERS_LENGTH: usize = 16 * 1024;
+
+/// The parts of a `multipart/form-data` body by field name.
+pub type Form = ListOrderedMultimap<String, Part>;
+
+/// A part of a `multipart/form-data` body whose body has been read into memory.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Part {
+  /// The body of the part.
+  body: Vec<u8>,
+
+  /// The filename from the `Content-Disposition` header.
+  filename: Option<String>,
+
+  /// The headers of the part.
+  headers: Headers,
+}
+
+impl Part {
+  /// Returns the body of the part.
+  #[must_use]
+  pub fn body(&self) -> &[u8] {
+    &self.body
+  }
+
+  /// Returns the value of the `Content-Type` header of the part, if any.
+  #[must_use]
+  pub fn content_type(&self) -> Option<&str> {
+    content_type(&self.headers)
+  }
+
+  /// Returns the filename from the `Content-Disposition` header of the part, if any.
+  #[must_use]
+  pub fn filename(&self) -> Option<&str> {
+    self.filename.as_deref()
+  }
+
+  /// Returns the headers of the part.
+  #[must_use]
+  pub fn headers(&self) -> &Headers {
+    &self.headers
+  }
+
+  /// Returns the body of the part, consuming the part.
+  #[must_use]
+  pub fn into_body(self) -> Vec<u8> {
+    self.body
+  }
+}
+
+/// The error returned when a body is not valid `multipart/form-data`.
+///
+/// It is returned as the inner error of an [`io::Error`] of kind [`io::ErrorKind::InvalidData`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ParseError {
+  /// The index of the invalid part.
+  part: usize,
+
+  /// The kind of error.
+  kind: ParseErrorKind,
+}
+
+impl ParseError {
+  /// Returns the kind of error.
+  #[must_use]
+  pub fn kind(&self) -> ParseErrorKind {
+    self.kind
+  }
+
+  /// Returns the index of the invalid part among all parts, starting at 0.
+  #[must_use]
+  pub fn part(&self) -> usize {
+    self.part
+  }
+}
+
+impl Display for ParseError {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    write!(formatter, "invalid multipart part {}: ", self.part)?;
+
+    match self.kind {
+      ParseErrorKind::HeadersTooLong => write!(formatter, "headers are too long"),
+      ParseErrorKind::InvalidDelimiter => write!(formatter, "unexpected characters after boundary"),
+      ParseErrorKind::InvalidHeaders => write!(formatter, "invalid headers"),
+      ParseErrorKind::MissingName => write!(formatter, "missing name in `Content-Disposition`"),
+      ParseErrorKind::UnexpectedEnd => write!(formatter, "body ends before the closing boundary"),
+    }
+  }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The kind of a [`ParseError`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ParseErrorKind {
+  /// The headers of a part are longer than 16 KiB.
+  HeadersTooLong,
+
+  /// A boundary is followed by characters other than whitespace or `--` on its line.
+  InvalidDelimiter,
+
+  /// The headers of a part are not valid UTF-8 or not valid RFC 5322 headers.
+  InvalidHeaders,
+
+  /// A part has no `Content-Disposition` header with a `name` parameter.
+  MissingName,
+
+  /// The body ends before the closing boundary.
+  UnexpectedEnd,
+}
+
+/// The position of a [`MultipartReader`] in the body.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum State {
+  /// Before the first boundary.
+  Preamble,
+
+  /// In the body of a part.
+  Body,
+
+  /// After the closing boundary.
+  End,
+}
+
+/// Reads the parts of a `multipart/form-data` body one at a time without buffering their bodies.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Read};
+///
+/// use ordered_multimap::{multipart::MultipartReader, ListOrderedMultimap};
+///
+/// let body: &[u8] = b"--X\r\n\
+///   Content-Disposition: form-data; name=\"upload\"; filename=\"big.bin\"\r\n\r\n\
+///   0123456789\r\n\
+///   --X\r\n\
+///   Content-Disposition: form-data; name=\"comment\"\r\n\r\n\
+///   large file\r\n\
+///   --X--";
+///
+/// let mut reader = MultipartReader::new(body, "X");
+/// let mut fields = ListOrderedMultimap::new();
+///
+/// while let Some(mut part) = reader.next_part().unwrap() {
+///   let name = part.name().to_string();
+///
+///   if part.filename().is_some() {
+///     // Stream the file somewhere instead of into memory.
+///     let length = io::copy(&mut part, &mut io::sink()).unwrap();
+///     fields.append(name, format!("{length} bytes"));
+///   } else {
+///     let mut value = String::new();
+///     part.read_to_string(&mut value).unwrap();
+///     fields.append(name, value);
+///   }
+/// }
+///
+/// assert_eq!(fields.get("upload").unwrap(), "10 bytes");
+/// assert_eq!(fields.get("comment").unwrap(), "large file");
+/// ```
+#[derive(Debug)]
+pub struct MultipartReader<R> {
+  /// The bytes read from the reader but not yet consumed.
+  buffer: Vec<u8>,
+
+  /// The delimiter that precedes each part, which is a line break followed by `--` and the boundary.
+  delimiter: Vec<u8>,
+
+  /// The number of parts that have been started.
+  parts: usize,
+
+  /// The underlying reader.
+  reader: R,
+
+  /// The position in the body.
+  state: State,
+}
+
+impl<R> MultipartReader<R>
+where
+  R: Read,
+{
+  /// Creates a reader of the parts of the body with the given boundary.
+  #[must_use]
+  pub fn new(reader: R, boundary: &str) -> Self {
+    let mut delimiter = b"\r\n--".to_vec();
+    delimiter.extend_from_slice(boundary.as_bytes());
+
+    MultipartReader {
+      // The line break lets a boundary at the very start match the delimiter.
+      buffer: b"\r\n".to_vec(),
+      delimiter,
+      parts: 0,
+      reader,
+      state: State::Preamble,
+    }
+  }
+
+  /// Returns the next part, or `None` after the closing boundary. The body of the previous part is skipped if it has
+  /// not been read to the end.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if reading fails or if the body is not valid `multipart/form-data`, see [`ParseError`].
+  pub fn next_part(&mut self) -> io::Result<Option<PartReader<'_, R>>> {
+    match self.state {
+      State::Preamble => {
+        while find(&self.buffer, &self.delimiter).is_none() {
+          // Keep the bytes that might start a delimiter.
+          let keep = self.buffer.len().min(self.delimiter.len() - 1);
+          mem::drop(self.buffer.drain(..self.buffer.len() - keep));
+
+          if !self.fill()? {
+            return Err(self.error(ParseErrorKind::UnexpectedEnd));
+          }
+        }
+
+        let position = find(&self.buffer, &self.delimiter).unwrap();
+        mem::drop(self.buffer.drain(..position));
+      }
+      State::Body => {
+        let _ = io::copy(&mut BodyReader(self), &mut io::sink())?;
+      }
+      State::End => return Ok(None),
+    }
+
+    // The buffer now starts with the delimiter.
+    self.fill_to(self.delimiter.len() + 2)?;
+    mem::drop(self.buffer.drain(..self.delimiter.len()));
+
+    if self.buffer.starts_with(b"--") {
+      self.state = State::End;
+      return Ok(None);
+    }
+
+    // Skip the transport padding up to the line break of the boundary line, but keep the line break so that a part
+    // without headers is followed by an empty header block.
+    let line_end = self.find_within(b"\r\n")?;
+
+    if !self.buffer[..line_end]
+      .iter()
+      .all(|&byte| matches!(byte, b' ' | b'\t'))
+    {
+      return Err(self.error(ParseErrorKind::InvalidDelimiter));
+    }
+
+    mem::drop(self.buffer.drain(..line_end));
+    let headers_end = self.find_within(b"\r\n\r\n")?;
+    let headers = str::from_utf8(&self.buffer[2..headers_end + 2])
+      .ok()
+      .and_then(|headers| rfc5322::parse(headers).ok())
+      .map(|(headers, _)| headers)
+      .ok_or_else(|| self.error(ParseErrorKind::InvalidHeaders))?;
+    let (name, filename) =
+      content_disposition(&headers).ok_or_else(|| self.error(ParseErrorKind::MissingName))?;
+    mem::drop(self.buffer.drain(..headers_end + 4));
+    self.parts += 1;
+    self.state = State::Body;
+
+    Ok(Some(PartReader {
+      filename,
+      headers,
+      name,
+      reader: self,
+    }))
+  }
+
+  /// Returns the error of the given kind for the current part.
+  fn error(&self, kind: ParseErrorKind) -> io::Error {
+    let error = ParseError {
+      part: self.parts,
+      kind,
+    };
+    io::Error::new(io::ErrorKind::InvalidData, error)
+  }
+
+  /// Reads more bytes into the buffer and returns whether the reader has ended.
+  fn fill(&mut self) -> io::Result<bool> {
+    let length = self.buffer.len();
+    self.buffer.resize(length + CHUNK_LENGTH, 0);
+
+    let result = loop {
+      match self.reader.read(&mut self.buffer[length..]) {
+        Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+        result => break result,
+      }
+    };
+
+    let read = *result.as_ref().unwrap_or(&0);
+    self.buffer.truncate(length + read);
+    result.map(|read| read > 0)
+  }
+
+  /// Reads into the buffer until it contains at least `length` bytes.
+  fn fill_to(&mut self, length: usize) -> io::Result<()> {
+    while self.buffer.len() < length {
+      if !self.fill()? {
+        return Err(self.error(ParseErrorKind::UnexpectedEnd));
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Reads into the buffer until it contains `needle` within the maximum length of the headers and returns its
+  /// position.
+  fn find_within(&mut self, needle: &[u8]) -> io::Result<usize> {
+    loop {
+      if let Some(position) = find(&self.buffer, needle) {
+        return Ok(position);
+      }
+
+      if self.buffer.len() > MAX_HEADERS_LENGTH {
+        return Err(self.error(ParseErrorKind::HeadersTooLong));
+      }
+
+      if !self.fill()? {
+        return Err(self.error(ParseErrorKind::UnexpectedEnd));
+      }
+    }
+  }
+
+  /// Reads from the body of the current part, returning 0 at the delimiter that ends it.
+  fn read_body(&mut self, output: &mut [u8]) -> io::Result<usize> {
+    if output.is_empty() {
+      return Ok(0);
+    }
+
+    loop {
+      // Bytes before a possible start of the delimiter belong to the body.
+      let available = match find(&self.buffer, &self.delimiter) {
+        Some(position) => position,
+        None => self.buffer.len().saturating_sub(self.delimiter.len() - 1),
+      };
+
+      if available > 0 {
+        let length = available.min(output.len());
+        output[..length].copy_from_slice(&self.buffer[..length]);
+        mem::drop(self.buffer.drain(..length));
+        return Ok(length);
+      }
+
+      if self.buffer.starts_with(&self.delimiter) {
+        return Ok(0);
+      }
+
+      if !self.fill()? {
+        return Err(self.error(ParseErrorKind::UnexpectedEnd));
+      }
+    }
+  }
+}
+
+/// Reads the body of the current part of a [`MultipartReader`].
+struct BodyReader<'reader, R>(&'reader mut MultipartReader<R>);
+
+impl<R> Read for BodyReader<'_, R>
+where
+  R: Read,
+{
+  fn read(&mut self, output: &mut [u8]) -> io::Result<usize> {
+    self.0.read_body(output)
+  }
+}
+
+/// A part of a `multipart/form-data` body whose body is read from the underlying reader of a [`MultipartReader`].
+#[derive(Debug)]
+pub struct PartReader<'reader, R> {
+  /// The filename from the `Content-Disposition` header.
+  filename: Option<String>,
+
+  /// The headers of the part.
+  headers: Headers,
+
+  /// The name from the `Content-Disposition` header.
+  name: String,
+
+  /// The reader of the whole body.
+  reader: &'reader mut MultipartReader<R>,
+}
+
+impl<R> PartReader<'_, R> {
+  /// Returns the value of the `Content-Type` header of the part, if any.
+  #[must_use]
+  pub fn content_type(&self) -> Option<&str> {
+    content_type(&self.headers)
+  }
+
+  /// Returns the filename from the `Content-Disposition` header of the part, if any.
+  #[must_use]
+  pub fn filename(&self) -> Option<&str> {
+    self.filename.as_deref()
+  }
+
+  /// Returns the headers of the part.
+  #[must_use]
+  pub fn headers(&self) -> &Headers {
+    &self.headers
+  }
+
+  /// Returns the field name from the `Content-Disposition` header of the part.
+  #[must_use]
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+}
+
+impl<R> Read for PartReader<'_, R>
+where
+  R: Read,
+{
+  fn read(&mut self, output: &mut [u8]) -> io::Result<usize> {
+    self.reader.read_body(output)
+  }
+}
+
+/// Parses a `multipart/form-data` body with the given boundary into its parts.
+///
+/// # Errors
+///
+/// Returns an error if the body is not valid `multipart/form-data`, see [`ParseError`].
+pub fn parse(body: &[u8], boundary: &str) -> io::Result<Form> {
+  parse_reader(body, boundary)
+}
+
+/// Parses a `multipart/form-data` body with the given boundary from a reader into its parts, reading the body of every
+/// part into memory.
+///
+/// # Errors
+///
+/// Returns an error if reading fails or if the body is not valid `multipart/form-data`, see [`ParseError`].
+pub fn parse_reader<R>(reader: R, boundary: &str) -> io::Result<Form>
+where
+  R: Read,
+{
+  let mut reader = MultipartReader::new(reader, boundary);
+  let mut form = Form::new();
+
+  while let Some(mut part) = reader.next_part()? {
+    let mut body = Vec::new();
+    let _ = part.read_to_end(&mut body)?;
+    let name = part.name;
+    let part = Part {
+      body,
+      filename: part.filename,
+      headers: part.headers,
+    };
+    let _ = form.append(name, part);
+  }
+
+  Ok(form)
+}
+
+/// Returns the value of the `Content-Type` header, if any.
+fn content_type(headers: &Headers) -> Option<&str> {
+  headers
+    .get(&HeaderName::from_static("Content-Type"))
+    .map(String::as_str)
+}
+
+/// Returns the `name` and `filename` parameters of the `Content-Disposition` header, or `None` if there is no `name`.
+fn content_disposition(headers: &Headers) -> Option<(String, Option<String>)> {
+  let value = headers.get(&HeaderName::from_static("Content-Disposition"))?;
+  let mut name = None;
+  let mut filename = None;
+  let mut rest = value.split_once(';')?.1;
+
+  while let Some((parameter, value, next)) = parameter(rest) {
+    if parameter.eq_ignore_ascii_case("name") {
+      name = Some(value);
+    } else if parameter.eq_ignore_ascii_case("filename") {
+      filename = Some(value);
+    }
+
+    rest = next;
+  }
+
+  Some((name?, filename))
+}
+
+/// Parses the next `parameter=value` of a header value whose preceding `;` has been consumed, and returns the
+/// parameter, the value without quotes and the rest after the next `;`.
+fn parameter(input: &str) -> Option<(&str, String, &str)> {
+  let (parameter, rest) = input.split_once('=')?;
+  let rest = rest.trim_start();
+  let mut value = String::new();
+
+  let rest = if let Some(quoted) = rest.strip_prefix('"') {
+    let mut characters = quoted.char_indices();
+    let end = loop {
+      match characters.next() {
+        Some((_, '\\')) => value.push(characters.next().map_or('\\', |(_, character)| character)),
+        Some((index, '"')) => break index + 1,
+        Some((_, character)) => value.push(character),
+        None => break quoted.len(),
+      }
+    };
+    &quoted[end..]
+  } else {
+    let end = rest.find(';').unwrap_or(rest.len());
+    value.push_str(rest[..end].trim_end());
+    &rest[end..]
+  };
+
+  let next = rest.split_once(';').map_or("", |(_, next)| next);
+  Some((parameter.trim(), value, next))
+}
+
+/// Returns the position of the first occurrence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+  haystack
+    .windows(needle.len())
+    .position(|window| window == needle)
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use super::*;
+
+  /// A reader that returns at most one byte at a time to exercise the buffering.
+  struct ByteReader<'a>(&'a [u8]);
+
+  impl Read for ByteReader<'_> {
+    fn read(&mut self, output: &mut [u8]) -> io::Result<usize> {
+      let length = output.len().min(self.0.len()).min(1);
+      output[..length].copy_from_slice(&self.0[..length]);
+      self.0 = &self.0[length..];
+      Ok(length)
+    }
+  }
+
+  const BODY: &[u8] = b"preamble\r\n\
+    --boundary \t\r\n\
+    Content-Disposition: form-data; name=\"a\"\r\n\
+    \r\n\
+    1\r\n\
+    --boundary\r\n\
+    content-disposition: form-data; filename=\"x \\\"y\\\".txt\"; name=b\r\n\
+    Content-Type: application/octet-stream\r\n\
+    \r\n\
+    \r\n--boundar\r\n\r\n\
+    --boundary\r\n\
+    Content-Disposition: form-data; name=\"a\"\r\n\
+    \r\n\
+    \r\n\
+    --boundary--\r\n\
+    epilogue";
+
+  fn error_kind(error: &io::Error) -> ParseErrorKind {
+    error
+      .get_ref()
+      .unwrap()
+      .downcast_ref::<ParseError>()
+      .unwrap()
+      .kind()
+  }
+
+  #[test]
+  fn test_parse() {
+    let form = parse(BODY, "boundary").unwrap();
+
+    assert_eq!(
+      form
+        .iter()
+        .map(|(name, part)| (name.as_str(), part.filename(), part.body()))
+        .collect::<Vec<_>>(),
+      [
+        ("a", None, &b"1"[..]),
+        ("b", Some("x \"y\".txt"), b"\r\n--boundar\r\n"),
+        ("a", None, b""),
+      ]
+    );
+
+    let part = form.get("b").unwrap();
+    assert_eq!(part.content_type(), Some("application/octet-stream"));
+    assert_eq!(part.headers().keys_len(), 2);
+    assert_eq!(part.clone().into_body(), b"\r\n--boundar\r\n");
+  }
+
+  #[test]
+  fn test_parse_empty() {
+    assert!(parse(b"--boundary--", "boundary").unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_parse_error() {
+    let cases: [(&[u8], ParseErrorKind); 6] = [
+      (b"", ParseErrorKind::UnexpectedEnd),
+      (
+        b"--boundary\r\nContent-Disposition: form-data; name=a\r\n\r\nbody",
+        ParseErrorKind::UnexpectedEnd,
+      ),
+      (b"--boundaryx\r\n", ParseErrorKind::InvalidDelimiter),
+      (
+        b"--boundary\r\nContent-Disposition: form-data\r\n\r\n",
+        ParseErrorKind::MissingName,
+      ),
+      (
+        b"--boundary\r\nContent-Type: text/plain\r\n\r\n",
+        ParseErrorKind::MissingName,
+      ),
+      (
+        b"--boundary\r\nno colon\r\n\r\n",
+        ParseErrorKind::InvalidHeaders,
+      ),
+    ];
+
+    for (body, kind) in cases {
+      let error = parse(body, "boundary").unwrap_err();
+      assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+      assert_eq!(error_kind(&error), kind);
+    }
+
+    let mut body = b"--boundary\r\nX: ".to_vec();
+    body.resize(body.len() + MAX_HEADERS_LENGTH, b'x');
+    let error = parse(&body, "boundary").unwrap_err();
+    assert_eq!(error_kind(&error), ParseErrorKind::HeadersTooLong);
+    assert_eq!(
+      error.to_string(),
+      "invalid multipart part 0: headers are too long"
+    );
+  }
+
+  #[test]
+  fn test_parse_reader() {
+    assert_eq!(
+      parse_reader(ByteReader(BODY), "boundary").unwrap(),
+      parse(BODY, "boundary").unwrap()
+    );
+  }
+
+  #[test]
+  fn test_multipart_reader_skip() {
+    let mut reader = MultipartReader::new(ByteReader(BODY), "boundary");
+    let mut names = vec![];
+
+    while let Some(part) = reader.next_part().unwrap() {
+      names.push(part.name().to_string());
+    }
+
+    assert_eq!(names, ["a", "b", "a"]);
+    assert!(reader.next_part().unwrap().is_none());
+  }
+
+  #[test]
+  fn test_part_reader() {
+    let mut reader = MultipartReader::new(BODY, "boundary");
+    reader.next_part().unwrap();
+    let mut part = reader.next_part().unwrap().unwrap();
+
+    assert_eq!(part.name(), "b");
+    assert_eq!(part.filename(), Some("x \"y\".txt"));
+    assert_eq!(part.content_type(), Some("application/octet-stream"));
+    assert_eq!(part.headers().values_len(), 2);
+
+    let mut output = [0; 4];
+    assert_eq!(part.read(&mut output).unwrap(), 4);
+    assert_eq!(&output, b"\r\n--");
+    assert_eq!(part.read(&mut []).unwrap(), 0);
+
+    let mut rest = vec![];
+    part.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, b"boundar\r\n");
+    assert_eq!(part.read(&mut output).unwrap(), 0);
+  }
+}
@@ -0,0 +1,589 @@
+//! Provides a wrapper around [`ListOrderedMultimap`] that reports every mutation to an [`Observer`].
+
+use alloc::vec::Vec;
+use core::{
+  borrow::Borrow,
+  fmt::{self, Debug, Formatter},
+  hash::{BuildHasher, Hash},
+  ops::Deref,
+};
+
+use crate::list_ordered_multimap::{KeyWrapper, ListOrderedMultimap, RandomState};
+
+/// Receives a callback for every mutation made through an [`ObservedMultimap`].
+///
+/// Every method has an empty default implementation so that implementors only need to handle the events they care
+/// about. Callbacks are invoked after the multimap has been modified, so the multimap already reflects the change.
+pub trait Observer<Key, Value> {
+  /// Called after `value` was appended to the values of `key`.
+  fn appended(&mut self, key: &Key, value: &Value) {
+    let _ = (key, value);
+  }
+
+  /// Called after `value` replaced all values of `key`. The replaced values are given in insertion order and are empty
+  /// if the key was not in the multimap.
+  fn inserted(&mut self, key: &Key, value: &Value, replaced: &[Value]) {
+    let _ = (key, value, replaced);
+  }
+
+  /// Called after all values of `key` were removed. The removed values are given in insertion order.
+  fn removed(&mut self, key: &Key, values: &[Value]) {
+    let _ = (key, values);
+  }
+
+  /// Called after the last key-value pair was popped.
+  fn popped_back(&mut self, key: &Key, value: &Value) {
+    let _ = (key, value);
+  }
+
+  /// Called after the first key-value pair was popped.
+  fn popped_front(&mut self, key: &Key, value: &Value) {
+    let _ = (key, value);
+  }
+
+  /// Called once for every key-value pair removed by [`ObservedMultimap::retain`], in global order. The callbacks are
+  /// made after the predicate has been applied to all pairs and all rejected pairs have been removed.
+  fn retain_removed(&mut self, key: &Key, value: &Value) {
+    let _ = (key, value);
+  }
+
+  /// Called after all keys and values were removed.
+  fn cleared(&mut self) {}
+
+  /// Called after the multimap was packed.
+  ///
+  /// Packing compacts the internal storage of the keys and values, which moves entries between storage slots that are
+  /// never exposed. The multimap does not hand out indices to its entries, so derived indexes can only refer to a
+  /// key-value pair by its position in the global order or among the values of its key, and packing changes neither.
+  /// The index remapping of a pack is therefore the identity, which is why no remapping is passed to this callback.
+  fn packed(&mut self) {}
+}
+
+/// A multimap that notifies an [`Observer`] of every mutation.
+///
+/// Read access is provided by dereferencing to the wrapped [`ListOrderedMultimap`]. Mutable access is only possible
+/// through the methods of this type so that no mutation goes unobserved. In particular, values cannot be modified in
+/// place.
+pub struct ObservedMultimap<Key, Value, Handler, State = RandomState> {
+  /// The multimap being observed.
+  map: ListOrderedMultimap<Key, Value, State>,
+
+  /// The observer notified of every mutation.
+  observer: Handler,
+}
+
+impl<Key, Value, Handler, State> ObservedMultimap<Key, Value, Handler, State> {
+  /// Wraps the given multimap such that all further mutations are reported to the given observer.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::ListOrderedMultimap;
+  /// use ordered_multimap::observer::{ObservedMultimap, Observer};
+  ///
+  /// struct Counter(usize);
+  ///
+  /// impl Observer<&'static str, i32> for Counter {
+  ///   fn appended(&mut self, _: &&'static str, _: &i32) {
+  ///     self.0 += 1;
+  ///   }
+  /// }
+  ///
+  /// let mut map = ObservedMultimap::new(ListOrderedMultimap::new(), Counter(0));
+  /// map.append("key", 1);
+  /// map.append("key", 2);
+  /// assert_eq!(map.observer().0, 2);
+  /// ```
+  #[must_use]
+  pub fn new(
+    map: ListOrderedMultimap<Key, Value, State>,
+    observer: Handler,
+  ) -> ObservedMultimap<Key, Value, Handler, State> {
+    ObservedMultimap { map, observer }
+  }
+
+  /// Removes all keys and values from the multimap.
+  ///
+  /// The observer is notified through [`Observer::cleared`].
+  pub fn clear(&mut self)
+  where
+    Handler: Observer<Key, Value>,
+  {
+    self.map.clear();
+    self.observer.cleared();
+  }
+
+  /// Returns the wrapped multimap and observer.
+  #[must_use]
+  pub fn into_inner(self) -> (ListOrderedMultimap<Key, Value, State>, Handler) {
+    (self.map, self.observer)
+  }
+
+  /// Returns an immutable reference to the observer.
+  #[must_use]
+  pub fn observer(&self) -> &Handler {
+    &self.observer
+  }
+
+  /// Returns a mutable reference to the observer.
+  #[must_use]
+  pub fn observer_mut(&mut self) -> &mut Handler {
+    &mut self.observer
+  }
+}
+
+impl<Key, Value, Handler, State> ObservedMultimap<Key, Value, Handler, State>
+where
+  Handler: Observer<Key, Value>,
+  Key: Eq + Hash,
+  State: BuildHasher,
+{
+  /// Appends a value to the list of values associated with the given key.
+  ///
+  /// The observer is notified through [`Observer::appended`]. See [`ListOrderedMultimap::append`].
+  pub fn append(&mut self, key: Key, value: Value) -> bool {
+    let existed = self.map.append(key, value);
+    let (key, value) = self.map.back().unwrap();
+    self.observer.appended(key, value);
+    existed
+  }
+
+  /// Inserts the key-value pair into the multimap and returns the first value previously associated with the key.
+  ///
+  /// The observer is notified through [`Observer::inserted`]. See [`ListOrderedMultimap::insert`].
+  pub fn insert(&mut self, key: Key, value: Value) -> Option<Value> {
+    self.insert_all(key, value).into_iter().next()
+  }
+
+  /// Inserts the key-value pair into the multimap and returns all values previously associated with the key by
+  /// insertion order.
+  ///
+  /// The observer is notified through [`Observer::inserted`]. See [`ListOrderedMultimap::insert_all`].
+  pub fn insert_all(&mut self, key: Key, value: Value) -> Vec<Value> {
+    let replaced: Vec<_> = self.map.insert_all(key, value).collect();
+    let (key, value) = self.map.back().unwrap();
+    self.observer.inserted(key, value, &replaced);
+    replaced
+  }
+
+  /// Reorganizes the multimap to ensure maximum spatial locality and changes the key and value capacities to the
+  /// provided values.
+  ///
+  /// The observer is notified through [`Observer::packed`]. See [`ListOrderedMultimap::pack_to`].
+  #[cfg(feature = "std")]
+  pub fn pack_to(&mut self, keys_minimum_capacity: usize, values_minimum_capacity: usize)
+  where
+    State: Default,
+  {
+    self
+      .map
+      .pack_to(keys_minimum_capacity, values_minimum_capacity);
+    self.observer.packed();
+  }
+
+  /// Reorganizes the multimap to ensure maximum spatial locality and removes any excess key and value capacity.
+  ///
+  /// The observer is notified through [`Observer::packed`]. See [`ListOrderedMultimap::pack_to_fit`].
+  #[cfg(feature = "std")]
+  pub fn pack_to_fit(&mut self)
+  where
+    State: Default,
+  {
+    self.map.pack_to_fit();
+    self.observer.packed();
+  }
+
+  /// Removes the last key-value pair to have been inserted.
+  ///
+  /// The observer is notified through [`Observer::popped_back`]. See [`ListOrderedMultimap::pop_back`].
+  pub fn pop_back(&mut self) -> Option<(KeyWrapper<'_, Key>, Value)> {
+    let (key, value) = self.map.pop_back()?;
+
+    match &key {
+      KeyWrapper::Borrowed(key) => self.observer.popped_back(key, &value),
+      KeyWrapper::Owned(key) => self.observer.popped_back(key, &value),
+    }
+
+    Some((key, value))
+  }
+
+  /// Removes the first key-value pair to have been inserted.
+  ///
+  /// The observer is notified through [`Observer::popped_front`]. See [`ListOrderedMultimap::pop_front`].
+  pub fn pop_front(&mut self) -> Option<(KeyWrapper<'_, Key>, Value)> {
+    let (key, value) = self.map.pop_front()?;
+
+    match &key {
+      KeyWrapper::Borrowed(key) => self.observer.popped_front(key, &value),
+      KeyWrapper::Owned(key) => self.observer.popped_front(key, &value),
+    }
+
+    Some((key, value))
+  }
+
+  /// Removes all values associated with the given key from the map and returns the first value by insertion order.
+  ///
+  /// The observer is notified through [`Observer::removed`]. See [`ListOrderedMultimap::remove`].
+  pub fn remove<KeyQuery>(&mut self, key: &KeyQuery) -> Option<Value>
+  where
+    Key: Borrow<KeyQuery>,
+    KeyQuery: ?Sized + Eq + Hash,
+  {
+    self.remove_entry(key).map(|(_, value)| value)
+  }
+
+  /// Removes all values associated with the given key from the map and returns them by insertion order.
+  ///
+  /// The observer is notified through [`Observer::removed`]. See [`ListOrderedMultimap::remove_all`].
+  pub fn remove_all<KeyQuery>(&mut self, key: &KeyQuery) -> Vec<Value>
+  where
+    Key: Borrow<KeyQuery>,
+    KeyQuery: ?Sized + Eq + Hash,
+  {
+    self
+      .remove_entry_all(key)
+      .map(|(_, values)| values)
+      .unwrap_or_default()
+  }
+
+  /// Removes all values associated with the given key from the map and returns the key and first value.
+  ///
+  /// The observer is notified through [`Observer::removed`]. See [`ListOrderedMultimap::remove_entry`].
+  pub fn remove_entry<KeyQuery>(&mut self, key: &KeyQuery) -> Option<(Key, Value)>
+  where
+    Key: Borrow<KeyQuery>,
+    KeyQuery: ?Sized + Eq + Hash,
+  {
+    let (key, values) = self.remove_entry_all(key)?;
+    Some((key, values.into_iter().next().unwrap()))
+  }
+
+  /// Removes all values associated with the given key from the map and returns the key and those values by insertion
+  /// order.
+  ///
+  /// The observer is notified through [`Observer::removed`]. See [`ListOrderedMultimap::remove_entry_all`].
+  pub fn remove_entry_all<KeyQuery>(&mut self, key: &KeyQuery) -> Option<(Key, Vec<Value>)>
+  where
+    Key: Borrow<KeyQuery>,
+    KeyQuery: ?Sized + Eq + Hash,
+  {
+    let (key, values) = self.map.remove_entry_all(key)?;
+    let values: Vec<_> = values.collect();
+    self.observer.removed(&key, &values);
+    Some((key, values))
+  }
+
+  /// Keeps all key-value pairs that satisfy the given predicate function.
+  ///
+  /// The observer is notified through [`Observer::retain_removed`] for every removed key-value pair. See
+  /// [`ListOrderedMultimap::retain`].
+  pub fn retain<Function>(&mut self, function: Function)
+  where
+    Function: FnMut(&Key, &mut Value) -> bool,
+  {
+    let observer = &mut self.observer;
+    self
+      .map
+      .retain_then(function, |key, value| observer.retain_removed(key, value));
+  }
+}
+
+impl<Key, Value, Handler, State> Debug for ObservedMultimap<Key, Value, Handler, State>
+where
+  Handler: Debug,
+  Key: Debug,
+  Value: Debug,
+{
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    formatter
+      .debug_struct("ObservedMultimap")
+      .field("map", &self.map)
+      .field("observer", &self.observer)
+      .finish()
+  }
+}
+
+impl<Key, Value, Handler, State> Deref for ObservedMultimap<Key, Value, Handler, State> {
+  type Target = ListOrderedMultimap<Key, Value, State>;
+
+  fn deref(&self) -> &Self::Target {
+    &self.map
+  }
+}
+
+impl<Key, Value, Handler, State> Extend<(Key, Value)>
+  for ObservedMultimap<Key, Value, Handler, State>
+where
+  Handler: Observer<Key, Value>,
+  Key: Eq + Hash,
+  State: BuildHasher,
+{
+  fn extend<Iter>(&mut self, iter: Iter)
+  where
+    Iter: IntoIterator<Item = (Key, Value)>,
+  {
+    for (key, value) in iter {
+      let _ = self.append(key, value);
+    }
+  }
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use alloc::{format, string::String};
+
+  use super::*;
+
+  #[derive(Debug, Default)]
+  struct Recorder(Vec<String>);
+
+  impl Observer<&'static str, i32> for Recorder {
+    fn appended(&mut self, key: &&'static str, value: &i32) {
+      self.0.push(format!("appended {key} {value}"));
+    }
+
+    fn inserted(&mut self, key: &&'static str, value: &i32, replaced: &[i32]) {
+      self.0.push(format!("inserted {key} {value} {replaced:?}"));
+    }
+
+    fn removed(&mut self, key: &&'static str, values: &[i32]) {
+      self.0.push(format!("removed {key} {values:?}"));
+    }
+
+    fn popped_back(&mut self, key: &&'static str, value: &i32) {
+      self.0.push(format!("popped_back {key} {value}"));
+    }
+
+    fn popped_front(&mut self, key: &&'static str, value: &i32) {
+      self.0.push(format!("popped_front {key} {value}"));
+    }
+
+    fn retain_removed(&mut self, key: &&'static str, value: &i32) {
+      self.0.push(format!("retain_removed {key} {value}"));
+    }
+
+    fn cleared(&mut self) {
+      self.0.push(String::from("cleared"));
+    }
+
+    fn packed(&mut self) {
+      self.0.push(String::from("packed"));
+    }
+  }
+
+  fn observed() -> ObservedMultimap<&'static str, i32, Recorder> {
+    ObservedMultimap::new(ListOrderedMultimap::new(), Recorder::default())
+  }
+
+  #[test]
+  fn test_observed_multimap_append() {
+    let mut map = observed();
+    assert!(!map.append("key1", 1));
+    assert!(map.append("key1", 2));
+    map.extend([("key2", 3)]);
+
+    assert_eq!(map.values_len(), 3);
+    assert_eq!(
+      map.observer().0,
+      ["appended key1 1", "appended key1 2", "appended key2 3"]
+    );
+  }
+
+  #[test]
+  fn test_observed_multimap_clear() {
+    let mut map = observed();
+    map.append("key", 1);
+    map.clear();
+
+    assert!(map.is_empty());
+    assert_eq!(map.observer().0, ["appended key 1", "cleared"]);
+  }
+
+  #[test]
+  fn test_observed_multimap_debug() {
+    let mut map = observed();
+    map.observer_mut().0.push(String::from("event"));
+
+    assert_eq!(
+      format!("{map:?}"),
+      r#"ObservedMultimap { map: {}, observer: Recorder(["event"]) }"#
+    );
+  }
+
+  #[test]
+  fn test_observed_multimap_insert() {
+    let mut map = observed();
+    assert_eq!(map.insert("key", 1), None);
+    map.append("key", 2);
+    assert_eq!(map.insert_all("key", 3), [1, 2]);
+
+    assert_eq!(map.get(&"key"), Some(&3));
+    assert_eq!(
+      map.observer().0,
+      [
+        "inserted key 1 []",
+        "appended key 2",
+        "inserted key 3 [1, 2]"
+      ]
+    );
+  }
+
+  #[test]
+  fn test_observed_multimap_into_inner() {
+    let mut map = observed();
+    map.append("key", 1);
+
+    let (map, observer) = map.into_inner();
+    assert_eq!(map.get(&"key"), Some(&1));
+    assert_eq!(observer.0, ["appended key 1"]);
+  }
+
+  #[test]
+  fn test_observed_multimap_pack_to() {
+    let mut map = observed();
+    map.append("key1", 1);
+    map.append("key2", 2);
+    map.pack_to(5, 5);
+    map.pack_to_fit();
+
+    assert_eq!(map.keys_capacity(), 2);
+    assert_eq!(
+      map.observer().0,
+      ["appended key1 1", "appended key2 2", "packed", "packed"]
+    );
+  }
+
+  #[test]
+  fn test_observed_multimap_pop() {
+    let mut map = observed();
+    assert_eq!(map.pop_back(), None);
+    assert_eq!(map.pop_front(), None);
+
+    map.append("key", 1);
+    map.append("key", 2);
+    map.append("key", 3);
+
+    assert_eq!(map.pop_back(), Some((KeyWrapper::Borrowed(&"key"), 3)));
+    assert_eq!(map.pop_front(), Some((KeyWrapper::Borrowed(&"key"), 1)));
+    assert_eq!(map.pop_back(), Some((KeyWrapper::Owned("key"), 2)));
+    assert_eq!(
+      &map.observer().0[3..],
+      [
+        "popped_back key 3",
+        "popped_front key 1",
+        "popped_back key 2"
+      ]
+    );
+
+    map.append("key", 4);
+    assert_eq!(map.pop_front(), Some((KeyWrapper::Owned("key"), 4)));
+    assert_eq!(map.observer().0.last().unwrap(), "popped_front key 4");
+  }
+
+  #[test]
+  fn test_observed_multimap_remove() {
+    let mut map = observed();
+    assert_eq!(map.remove(&"key1"), None);
+    assert!(map.remove_all(&"key1").is_empty());
+
+    map.append("key1", 1);
+    map.append("key1", 2);
+    map.append("key2", 3);
+    map.append("key3", 4);
+
+    assert_eq!(map.remove_all(&"key1"), [1, 2]);
+    assert_eq!(map.remove(&"key2"), Some(3));
+    assert_eq!(map.remove_entry(&"key3"), Some(("key3", 4)));
+    assert!(map.is_empty());
+    assert_eq!(
+      &map.observer().0[4..],
+      [
+        "removed key1 [1, 2]",
+        "removed key2 [3]",
+        "removed key3 [4]"
+      ]
+    );
+  }
+
+  #[test]
+  fn test_observed_multimap_retain() {
+    let mut map = observed();
+    map.append("key1", 1);
+    map.append("key2", -2);
+    map.append("key1", -3);
+
+    map.retain(|_, &mut value| value > 0);
+
+    let mut iter = map.iter();
+    assert_eq!(iter.next(), Some((&"key1", &1)));
+    assert_eq!(iter.next(), None);
+    assert_eq!(
+      &map.observer().0[3..],
+      ["retain_removed key2 -2", "retain_removed key1 -3"]
+    );
+  }
+
+  #[test]
+  fn test_observed_multimap_retain_after_removal() {
+    use core::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Shared(Rc<RefCell<Vec<String>>>);
+
+    impl Observer<&'static str, i32> for Shared {
+      fn retain_removed(&mut self, key: &&'static str, value: &i32) {
+        self
+          .0
+          .borrow_mut()
+          .push(format!("retain_removed {key} {value}"));
+      }
+    }
+
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let mut map = ObservedMultimap::new(ListOrderedMultimap::new(), Shared(Rc::clone(&events)));
+    map.append("key1", -1);
+    map.append("key2", 2);
+    map.append("key1", -3);
+
+    map.retain(|key, value| {
+      events.borrow_mut().push(format!("predicate {key} {value}"));
+      *value > 0
+    });
+
+    assert_eq!(map.keys_len(), 1);
+    assert_eq!(
+      events.take(),
+      [
+        "predicate key1 -1",
+        "predicate key2 2",
+        "predicate key1 -3",
+        "retain_removed key1 -1",
+        "retain_removed key1 -3"
+      ]
+    );
+  }
+
+  #[test]
+  fn test_observer_defaults() {
+    struct Silent;
+
+    impl Observer<&'static str, i32> for Silent {}
+
+    let mut map = ObservedMultimap::new(ListOrderedMultimap::new(), Silent);
+    map.append("key", 1);
+    map.insert("key", 2);
+    map.append("key", 3);
+    map.pop_back();
+    map.pop_front();
+    map.append("key", 4);
+    map.retain(|_, _| false);
+    map.append("key", 5);
+    map.remove(&"key");
+    map.pack_to_fit();
+    map.clear();
+
+    assert!(map.is_empty());
+  }
+}
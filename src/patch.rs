@@ -0,0 +1,950 @@
+//! Provides types related to applying patches to a [`ListOrderedMultimap`].
+
+use alloc::{vec, vec::Vec};
+use core::{
+  fmt::{self, Display, Formatter},
+  hash::{BuildHasher, BuildHasherDefault, Hash},
+  mem,
+};
+
+use dlv_list::Index;
+use hashbrown::HashMap;
+
+use crate::{
+  diff::Edit,
+  list_ordered_multimap::{FnvHasher, RemovedValue},
+  ListOrderedMultimap,
+};
+
+/// A single operation of a [`Patch`].
+///
+/// Positions are indices into the global order of the multimap at the time the operation is applied, that is, after
+/// all previous operations of the patch have been applied.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Operation<Key, Value> {
+  /// Appends the key-value pair, see [`ListOrderedMultimap::append`].
+  Append {
+    /// The key to append the value to.
+    key: Key,
+
+    /// The value to append.
+    value: Value,
+  },
+
+  /// Inserts the key-value pair such that it ends up at `position`.
+  ///
+  /// The value is placed among the other values of the key according to the global order. If the key is not already in
+  /// the multimap, it is ordered after all other keys.
+  Insert {
+    /// The position of the inserted key-value pair. This must not be greater than the number of values.
+    position: usize,
+
+    /// The key of the inserted key-value pair.
+    key: Key,
+
+    /// The value of the inserted key-value pair.
+    value: Value,
+  },
+
+  /// Moves the key-value pair at `from` such that it ends up at `to`.
+  Move {
+    /// The position of the key-value pair before it is moved. This must be less than the number of values.
+    from: usize,
+
+    /// The position of the key-value pair after it is moved. This must be less than the number of values.
+    to: usize,
+  },
+
+  /// Removes the `nth` value of `key` by insertion order.
+  ///
+  /// If this is the only value of the key, the key is removed as well.
+  Remove {
+    /// The key to remove the value from. This must be in the multimap.
+    key: Key,
+
+    /// The index of the value among the values of the key. This must be less than the number of values of the key.
+    nth: usize,
+  },
+
+  /// Changes the value of the key-value pair at `position` in place.
+  Replace {
+    /// The position of the key-value pair to change. This must be less than the number of values.
+    position: usize,
+
+    /// The key of the key-value pair at `position`. This is checked to guard against conflicting changes.
+    key: Key,
+
+    /// The new value.
+    value: Value,
+  },
+}
+
+/// An ordered list of operations that can be applied to a multimap with [`ListOrderedMultimap::apply`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Patch<Key, Value> {
+  /// The operations in the order they are applied.
+  operations: Vec<Operation<Key, Value>>,
+}
+
+impl<Key, Value> Patch<Key, Value> {
+  /// Creates a new empty patch.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::patch::Patch;
+  ///
+  /// let patch: Patch<&str, &str> = Patch::new();
+  /// assert!(patch.is_empty());
+  /// ```
+  #[must_use]
+  pub fn new() -> Patch<Key, Value> {
+    Patch { operations: vec![] }
+  }
+
+  /// Returns a patch that transforms `old` into `new` when applied to it.
+  ///
+  /// The patch is built from the edit script returned by [`ListOrderedMultimap::diff`].
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::ListOrderedMultimap;
+  /// use ordered_multimap::patch::Patch;
+  ///
+  /// let mut old = ListOrderedMultimap::new();
+  /// old.append("a", 1);
+  /// old.append("b", 2);
+  ///
+  /// let mut new = ListOrderedMultimap::new();
+  /// new.append("b", 2);
+  /// new.append("a", 3);
+  ///
+  /// let patch = Patch::diff(&old, &new);
+  /// old.apply(patch).unwrap();
+  /// assert_eq!(old, new);
+  /// ```
+  #[must_use]
+  pub fn diff<OldState, NewState>(
+    old: &ListOrderedMultimap<Key, Value, OldState>,
+    new: &ListOrderedMultimap<Key, Value, NewState>,
+  ) -> Patch<Key, Value>
+  where
+    Key: Clone + Eq + Hash,
+    OldState: BuildHasher,
+    Value: Clone + PartialEq,
+  {
+    // The keys in the current global order, needed to determine the index of a removed value among the values of its
+    // key.
+    let mut keys: Vec<_> = old.iter().map(|(key, _)| key).collect();
+    let mut patch = Patch::new();
+
+    for edit in old.diff(new) {
+      let operation = match edit {
+        Edit::Insert {
+          position,
+          key,
+          value,
+        } => {
+          keys.insert(position, key);
+          Operation::Insert {
+            position,
+            key: key.clone(),
+            value: value.clone(),
+          }
+        }
+        Edit::Move { from, to, .. } => {
+          let key = keys.remove(from);
+          keys.insert(to, key);
+          Operation::Move { from, to }
+        }
+        Edit::Remove { position, key, .. } => {
+          let nth = keys[..position]
+            .iter()
+            .filter(|&&other| other == key)
+            .count();
+          let _ = keys.remove(position);
+          Operation::Remove {
+            key: key.clone(),
+            nth,
+          }
+        }
+        Edit::Replace {
+          position,
+          key,
+          new_value,
+          ..
+        } => Operation::Replace {
+          position,
+          key: key.clone(),
+          value: new_value.clone(),
+        },
+      };
+      patch.push(operation);
+    }
+
+    patch
+  }
+
+  /// Returns whether the patch has no operations.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::patch::{Operation, Patch};
+  ///
+  /// let mut patch = Patch::new();
+  /// assert!(patch.is_empty());
+  ///
+  /// patch.push(Operation::Append { key: "key", value: "value" });
+  /// assert!(!patch.is_empty());
+  /// ```
+  #[must_use]
+  pub fn is_empty(&self) -> bool {
+    self.operations.is_empty()
+  }
+
+  /// Returns the number of operations in the patch.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::patch::{Operation, Patch};
+  ///
+  /// let mut patch = Patch::new();
+  /// patch.push(Operation::Append { key: "key", value: "value" });
+  /// assert_eq!(patch.len(), 1);
+  /// ```
+  #[must_use]
+  pub fn len(&self) -> usize {
+    self.operations.len()
+  }
+
+  /// Returns the operations of the patch in the order they are applied.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::patch::{Operation, Patch};
+  ///
+  /// let mut patch = Patch::new();
+  /// patch.push(Operation::Append { key: "key", value: "value" });
+  /// assert_eq!(patch.operations(), [Operation::Append { key: "key", value: "value" }]);
+  /// ```
+  #[must_use]
+  pub fn operations(&self) -> &[Operation<Key, Value>] {
+    &self.operations
+  }
+
+  /// Adds an operation to the end of the patch.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::patch::{Operation, Patch};
+  ///
+  /// let mut patch: Patch<&str, &str> = Patch::new();
+  /// patch.push(Operation::Remove { key: "key", nth: 0 });
+  /// assert_eq!(patch.len(), 1);
+  /// ```
+  pub fn push(&mut self, operation: Operation<Key, Value>) {
+    self.operations.push(operation);
+  }
+}
+
+impl<Key, Value> Default for Patch<Key, Value> {
+  fn default() -> Self {
+    Patch::new()
+  }
+}
+
+impl<Key, Value> Extend<Operation<Key, Value>> for Patch<Key, Value> {
+  fn extend<Iter>(&mut self, iter: Iter)
+  where
+    Iter: IntoIterator<Item = Operation<Key, Value>>,
+  {
+    self.operations.extend(iter);
+  }
+}
+
+impl<Key, Value> From<Vec<Operation<Key, Value>>> for Patch<Key, Value> {
+  fn from(operations: Vec<Operation<Key, Value>>) -> Self {
+    Patch { operations }
+  }
+}
+
+impl<Key, Value> FromIterator<Operation<Key, Value>> for Patch<Key, Value> {
+  fn from_iter<Iter>(iter: Iter) -> Self
+  where
+    Iter: IntoIterator<Item = Operation<Key, Value>>,
+  {
+    Patch {
+      operations: iter.into_iter().collect(),
+    }
+  }
+}
+
+impl<Key, Value> IntoIterator for Patch<Key, Value> {
+  type IntoIter = vec::IntoIter<Operation<Key, Value>>;
+  type Item = Operation<Key, Value>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.operations.into_iter()
+  }
+}
+
+/// The error returned when an operation of a [`Patch`] conflicts with the state of the multimap.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PatchError {
+  /// The index of the conflicting operation in the patch.
+  operation: usize,
+
+  /// The kind of conflict.
+  kind: PatchErrorKind,
+}
+
+impl PatchError {
+  /// Returns the kind of conflict.
+  #[must_use]
+  pub fn kind(&self) -> PatchErrorKind {
+    self.kind
+  }
+
+  /// Returns the index of the conflicting operation in the patch.
+  #[must_use]
+  pub fn operation(&self) -> usize {
+    self.operation
+  }
+}
+
+impl Display for PatchError {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    write!(formatter, "patch operation {} conflicts: ", self.operation)?;
+
+    match self.kind {
+      PatchErrorKind::KeyMismatch { position } => {
+        write!(formatter, "key at position {position} does not match")
+      }
+      PatchErrorKind::KeyNotFound => write!(formatter, "key not found"),
+      PatchErrorKind::NthOutOfBounds { nth, length } => {
+        write!(
+          formatter,
+          "value {nth} is out of bounds for key with {length} values"
+        )
+      }
+      PatchErrorKind::PositionOutOfBounds { position, length } => write!(
+        formatter,
+        "position {position} is out of bounds for multimap with {length} values"
+      ),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PatchError {}
+
+/// The kind of conflict of a [`PatchError`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PatchErrorKind {
+  /// The key of a [`Operation::Replace`] does not match the key at its position.
+  KeyMismatch {
+    /// The position of the key-value pair.
+    position: usize,
+  },
+
+  /// The key of a [`Operation::Remove`] is not in the multimap.
+  KeyNotFound,
+
+  /// The value index of a [`Operation::Remove`] is not less than the number of values of the key.
+  NthOutOfBounds {
+    /// The index of the value among the values of the key.
+    nth: usize,
+
+    /// The number of values of the key.
+    length: usize,
+  },
+
+  /// A position is out of bounds of the global order.
+  PositionOutOfBounds {
+    /// The position given by the operation.
+    position: usize,
+
+    /// The number of values in the multimap.
+    length: usize,
+  },
+}
+
+/// Reverts an operation that was applied by [`ListOrderedMultimap::apply`] when a later operation conflicts.
+enum Undo<Key, Value> {
+  /// Reverts an [`Operation::Append`] by removing the last key-value pair.
+  Append,
+
+  /// Reverts an [`Operation::Insert`] by removing the key-value pair at the position.
+  Insert {
+    /// The position of the inserted key-value pair.
+    position: usize,
+  },
+
+  /// Reverts an [`Operation::Move`] by moving the key-value pair back.
+  Move {
+    /// The position of the key-value pair before it was moved.
+    from: usize,
+
+    /// The position of the key-value pair after it was moved.
+    to: usize,
+  },
+
+  /// Reverts an [`Operation::Remove`] by putting back the removed key-value pair.
+  Remove(RemovedValue<Key, Value>),
+
+  /// Reverts an [`Operation::Replace`] by putting back the replaced value.
+  Replace {
+    /// The position of the changed key-value pair.
+    position: usize,
+
+    /// The replaced value.
+    value: Value,
+  },
+}
+
+impl<Key, Value, State> ListOrderedMultimap<Key, Value, State>
+where
+  Key: Eq + Hash,
+  State: BuildHasher,
+{
+  /// Applies the operations of the patch in order.
+  ///
+  /// Each operation is validated against the state of the multimap left by the previous operations. The patch is
+  /// applied atomically: if any operation conflicts, the operations applied before it are reverted in reverse order,
+  /// the multimap is left unchanged and the conflict is returned. If hashing or comparing a key panics, the patch may be
+  /// left partially applied.
+  ///
+  /// Complexity: O(|P| * min(p, |V| - p)) where |P| is the number of operations, p is a position given by an operation
+  /// and |V| is the number of values. Positions are found by walking from the nearer end of the global order, and
+  /// placing a value among the values of its key walks back to the previous value of the key. The multimap is not
+  /// copied; reverting a conflicting patch costs about as much as applying it.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::ListOrderedMultimap;
+  /// use ordered_multimap::patch::{Operation, Patch, PatchErrorKind};
+  ///
+  /// let mut map = ListOrderedMultimap::new();
+  /// map.append("a", 1);
+  /// map.append("b", 2);
+  ///
+  /// let patch = Patch::from(vec![
+  ///   Operation::Insert { position: 1, key: "a", value: 3 },
+  ///   Operation::Replace { position: 2, key: "b", value: 4 },
+  /// ]);
+  /// map.apply(patch).unwrap();
+  ///
+  /// let mut iter = map.iter();
+  /// assert_eq!(iter.next(), Some((&"a", &1)));
+  /// assert_eq!(iter.next(), Some((&"a", &3)));
+  /// assert_eq!(iter.next(), Some((&"b", &4)));
+  /// assert_eq!(iter.next(), None);
+  ///
+  /// let patch = Patch::from(vec![
+  ///   Operation::Append { key: "c", value: 5 },
+  ///   Operation::Remove { key: "b", nth: 1 },
+  /// ]);
+  /// let error = map.apply(patch).unwrap_err();
+  /// assert_eq!(error.operation(), 1);
+  /// assert_eq!(error.kind(), PatchErrorKind::NthOutOfBounds { nth: 1, length: 1 });
+  /// assert_eq!(map.values_len(), 3);
+  /// ```
+  pub fn apply(&mut self, patch: Patch<Key, Value>) -> Result<(), PatchError> {
+    let mut undos = Vec::with_capacity(patch.len());
+
+    for (index, operation) in patch.operations.into_iter().enumerate() {
+      match self.apply_operation(operation) {
+        Ok(undo) => undos.push(undo),
+        Err(kind) => {
+          self.revert(undos);
+          return Err(PatchError {
+            operation: index,
+            kind,
+          });
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Validates and applies a single operation, and returns how to revert it. If the operation conflicts, the multimap
+  /// is left unchanged.
+  fn apply_operation(
+    &mut self,
+    operation: Operation<Key, Value>,
+  ) -> Result<Undo<Key, Value>, PatchErrorKind> {
+    let length = self.values_len();
+    let check_position = |position: usize, bound: usize| {
+      if position < bound {
+        Ok(())
+      } else {
+        Err(PatchErrorKind::PositionOutOfBounds { position, length })
+      }
+    };
+
+    let undo = match operation {
+      Operation::Append { key, value } => {
+        let _ = self.append(key, value);
+        Undo::Append
+      }
+      Operation::Insert {
+        position,
+        key,
+        value,
+      } => {
+        check_position(position, length + 1)?;
+        self.insert_at(position, key, value);
+        Undo::Insert { position }
+      }
+      Operation::Move { from, to } => {
+        check_position(from, length)?;
+        check_position(to, length)?;
+        self.move_value(from, to);
+        Undo::Move { from, to }
+      }
+      Operation::Remove { key, nth } => {
+        let length = self.entry_len(&key);
+
+        if length == 0 {
+          return Err(PatchErrorKind::KeyNotFound);
+        }
+
+        if nth >= length {
+          return Err(PatchErrorKind::NthOutOfBounds { nth, length });
+        }
+
+        Undo::Remove(self.remove_nth(&key, nth).unwrap())
+      }
+      Operation::Replace {
+        position,
+        key,
+        value,
+      } => {
+        check_position(position, length)?;
+        let (existing_key, existing_value) = self.get_at_mut(position).unwrap();
+
+        if *existing_key != key {
+          return Err(PatchErrorKind::KeyMismatch { position });
+        }
+
+        Undo::Replace {
+          position,
+          value: mem::replace(existing_value, value),
+        }
+      }
+    };
+
+    Ok(undo)
+  }
+
+  /// Reverts the applied operations in reverse order.
+  fn revert(&mut self, undos: Vec<Undo<Key, Value>>) {
+    // Keys that were removed entirely get a new index when they are put back. Earlier removals refer to them by the
+    // index they had before.
+    let mut key_indices: HashMap<Index<Key>, Index<Key>, BuildHasherDefault<FnvHasher>> =
+      HashMap::default();
+    let key_index = |key_indices: &HashMap<_, _, _>, key_index| {
+      key_indices.get(&key_index).copied().unwrap_or(key_index)
+    };
+
+    for undo in undos.into_iter().rev() {
+      match undo {
+        Undo::Append => {
+          mem::drop(self.remove_at(self.values_len() - 1));
+        }
+        Undo::Insert { position } => {
+          mem::drop(self.remove_at(position));
+        }
+        Undo::Move { from, to } => self.move_value(to, from),
+        Undo::Remove(mut removed) => {
+          let old_key_index = removed.key_index;
+          removed.key_index = key_index(&key_indices, old_key_index);
+
+          if let Some((_, next_key_index)) = &mut removed.key {
+            *next_key_index = next_key_index.map(|index| key_index(&key_indices, index));
+          }
+
+          let new_key_index = self.restore(removed);
+
+          if new_key_index != old_key_index {
+            let _ = key_indices.insert(old_key_index, new_key_index);
+          }
+        }
+        Undo::Replace { position, value } => {
+          *self.get_at_mut(position).unwrap().1 = value;
+        }
+      }
+    }
+  }
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use alloc::string::ToString;
+
+  use super::*;
+
+  fn map(pairs: &[(char, u32)]) -> ListOrderedMultimap<char, u32> {
+    pairs.iter().copied().collect()
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_apply() {
+    let mut map = map(&[('a', 1), ('b', 2), ('a', 3), ('c', 4)]);
+    let patch = Patch::from(vec![
+      Operation::Remove { key: 'a', nth: 0 },
+      Operation::Insert {
+        position: 0,
+        key: 'b',
+        value: 5,
+      },
+      Operation::Move { from: 3, to: 1 },
+      Operation::Insert {
+        position: 4,
+        key: 'd',
+        value: 6,
+      },
+      Operation::Replace {
+        position: 3,
+        key: 'a',
+        value: 7,
+      },
+      Operation::Append { key: 'c', value: 8 },
+      Operation::Remove { key: 'd', nth: 0 },
+    ]);
+
+    map.apply(patch).unwrap();
+    assert_eq!(
+      map.iter().collect::<Vec<_>>(),
+      [(&'b', &5), (&'c', &4), (&'b', &2), (&'a', &7), (&'c', &8)]
+    );
+    assert_eq!(map.get_all(&'b').collect::<Vec<_>>(), [&5, &2]);
+    assert_eq!(map.get_all(&'b').rev().collect::<Vec<_>>(), [&2, &5]);
+    assert_eq!(map.get_all(&'c').collect::<Vec<_>>(), [&4, &8]);
+    assert_eq!(map.get_all(&'c').rev().collect::<Vec<_>>(), [&8, &4]);
+    assert_eq!(map.keys().collect::<Vec<_>>(), [&'a', &'b', &'c']);
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_apply_conflict() {
+    let original = map(&[('a', 1), ('b', 2)]);
+    let cases = [
+      (
+        Operation::Insert {
+          position: 3,
+          key: 'a',
+          value: 0,
+        },
+        PatchErrorKind::PositionOutOfBounds {
+          position: 3,
+          length: 2,
+        },
+      ),
+      (
+        Operation::Move { from: 2, to: 0 },
+        PatchErrorKind::PositionOutOfBounds {
+          position: 2,
+          length: 2,
+        },
+      ),
+      (
+        Operation::Move { from: 0, to: 2 },
+        PatchErrorKind::PositionOutOfBounds {
+          position: 2,
+          length: 2,
+        },
+      ),
+      (
+        Operation::Remove { key: 'c', nth: 0 },
+        PatchErrorKind::KeyNotFound,
+      ),
+      (
+        Operation::Remove { key: 'a', nth: 1 },
+        PatchErrorKind::NthOutOfBounds { nth: 1, length: 1 },
+      ),
+      (
+        Operation::Replace {
+          position: 2,
+          key: 'a',
+          value: 0,
+        },
+        PatchErrorKind::PositionOutOfBounds {
+          position: 2,
+          length: 2,
+        },
+      ),
+      (
+        Operation::Replace {
+          position: 1,
+          key: 'a',
+          value: 0,
+        },
+        PatchErrorKind::KeyMismatch { position: 1 },
+      ),
+    ];
+
+    for (operation, kind) in cases {
+      let mut map = original.clone();
+      let patch = Patch::from(vec![
+        Operation::Replace {
+          position: 0,
+          key: 'a',
+          value: 3,
+        },
+        operation,
+      ]);
+      let error = map.apply(patch).unwrap_err();
+
+      assert_eq!(error.operation(), 1);
+      assert_eq!(error.kind(), kind);
+      assert_eq!(map, original);
+    }
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_apply_rollback() {
+    let original = map(&[('a', 1), ('b', 2), ('a', 3), ('c', 4), ('d', 5)]);
+    let mut map = original.clone();
+    let patch = Patch::from(vec![
+      Operation::Remove { key: 'a', nth: 0 },
+      Operation::Remove { key: 'c', nth: 0 },
+      Operation::Remove { key: 'a', nth: 0 },
+      Operation::Remove { key: 'b', nth: 0 },
+      Operation::Append { key: 'a', value: 6 },
+      Operation::Insert {
+        position: 0,
+        key: 'e',
+        value: 7,
+      },
+      Operation::Move { from: 2, to: 0 },
+      Operation::Replace {
+        position: 1,
+        key: 'e',
+        value: 8,
+      },
+      Operation::Remove { key: 'a', nth: 0 },
+      Operation::Remove { key: 'b', nth: 0 },
+    ]);
+    let error = map.apply(patch).unwrap_err();
+
+    assert_eq!(error.operation(), 9);
+    assert_eq!(error.kind(), PatchErrorKind::KeyNotFound);
+    assert_eq!(
+      map
+        .iter()
+        .map(|(&key, &value)| (key, value))
+        .collect::<Vec<_>>(),
+      [('a', 1), ('b', 2), ('a', 3), ('c', 4), ('d', 5)]
+    );
+    assert_eq!(map.keys().collect::<Vec<_>>(), [&'a', &'b', &'c', &'d']);
+    assert_eq!(map.get_all(&'a').collect::<Vec<_>>(), [&1, &3]);
+    assert_eq!(map.get_all(&'a').rev().collect::<Vec<_>>(), [&3, &1]);
+    assert_eq!(map.get_all(&'c').collect::<Vec<_>>(), [&4]);
+    assert_eq!(map, original);
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_apply_move() {
+    let original = map(&[('a', 1), ('b', 2), ('a', 3), ('c', 4)]);
+
+    for from in 0..4 {
+      for to in 0..4 {
+        let mut map = original.clone();
+        map
+          .apply(Patch::from(vec![Operation::Move { from, to }]))
+          .unwrap();
+
+        let mut expected: Vec<_> = original.iter().map(|(&key, &value)| (key, value)).collect();
+        let pair = expected.remove(from);
+        expected.insert(to, pair);
+
+        assert_eq!(
+          map
+            .iter()
+            .map(|(&key, &value)| (key, value))
+            .collect::<Vec<_>>(),
+          expected
+        );
+
+        for key in ['a', 'b', 'c'] {
+          let values: Vec<_> = expected
+            .iter()
+            .filter(|(other, _)| *other == key)
+            .map(|(_, value)| value)
+            .collect();
+          assert_eq!(map.get_all(&key).collect::<Vec<_>>(), values);
+          assert_eq!(
+            map.get_all(&key).rev().collect::<Vec<_>>(),
+            values.into_iter().rev().collect::<Vec<_>>()
+          );
+        }
+
+        assert_eq!(map.keys().collect::<Vec<_>>(), [&'a', &'b', &'c']);
+      }
+    }
+  }
+
+  #[test]
+  fn test_patch() {
+    let mut patch = Patch::default();
+    assert!(patch.is_empty());
+    assert_eq!(patch.len(), 0);
+
+    patch.push(Operation::Append { key: 'a', value: 1 });
+    patch.extend([Operation::Move { from: 0, to: 0 }]);
+    assert_eq!(patch.len(), 2);
+
+    let other: Patch<_, _> = patch.clone().into_iter().collect();
+    assert_eq!(other, patch);
+    assert_eq!(
+      other.operations(),
+      [
+        Operation::Append { key: 'a', value: 1 },
+        Operation::Move { from: 0, to: 0 }
+      ]
+    );
+  }
+
+  #[test]
+  fn test_patch_diff() {
+    let old = map(&[('a', 1), ('b', 2), ('a', 3), ('c', 4), ('b', 5)]);
+    let new = map(&[('b', 5), ('a', 3), ('d', 6), ('a', 10), ('c', 7), ('b', 2)]);
+
+    let mut map = old.clone();
+    map.apply(Patch::diff(&old, &new)).unwrap();
+    assert_eq!(map, new);
+
+    let mut map = new.clone();
+    map.apply(Patch::diff(&new, &old)).unwrap();
+    assert_eq!(map, old);
+  }
+
+  #[test]
+  fn test_patch_error_display() {
+    let error = |kind| PatchError { operation: 2, kind }.to_string();
+
+    assert_eq!(
+      error(PatchErrorKind::KeyMismatch { position: 1 }),
+      "patch operation 2 conflicts: key at position 1 does not match"
+    );
+    assert_eq!(
+      error(PatchErrorKind::KeyNotFound),
+      "patch operation 2 conflicts: key not found"
+    );
+    assert_eq!(
+      error(PatchErrorKind::NthOutOfBounds { nth: 3, length: 2 }),
+      "patch operation 2 conflicts: value 3 is out of bounds for key with 2 values"
+    );
+    assert_eq!(
+      error(PatchErrorKind::PositionOutOfBounds {
+        position: 5,
+        length: 4
+      }),
+      "patch operation 2 conflicts: position 5 is out of bounds for multimap with 4 values"
+    );
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_patch_serde() {
+    use serde_test::{assert_tokens, Token};
+
+    let patch: Patch<char, u32> = Patch::from(vec![
+      Operation::Remove { key: 'a', nth: 1 },
+      Operation::Move { from: 0, to: 2 },
+    ]);
+
+    assert_tokens(
+      &patch,
+      &[
+        Token::Seq { len: Some(2) },
+        Token::StructVariant {
+          name: "Operation",
+          variant: "Remove",
+          len: 2,
+        },
+        Token::Str("key"),
+        Token::Char('a'),
+        Token::Str("nth"),
+        Token::U64(1),
+        Token::StructVariantEnd,
+        Token::StructVariant {
+          name: "Operation",
+          variant: "Move",
+          len: 2,
+        },
+        Token::Str("from"),
+        Token::U64(0),
+        Token::Str("to"),
+        Token::U64(2),
+        Token::StructVariantEnd,
+        Token::SeqEnd,
+      ],
+    );
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_patch_serde_roundtrip() {
+    let patch: Patch<String, u32> = Patch::from(vec![
+      Operation::Append {
+        key: "a".to_string(),
+        value: 1,
+      },
+      Operation::Insert {
+        position: 0,
+        key: "b".to_string(),
+        value: 2,
+      },
+      Operation::Move { from: 0, to: 1 },
+      Operation::Remove {
+        key: "a".to_string(),
+        nth: 0,
+      },
+      Operation::Replace {
+        position: 0,
+        key: "b".to_string(),
+        value: 3,
+      },
+    ]);
+
+    let json = serde_json::to_string(&patch).unwrap();
+    assert_eq!(
+      serde_json::from_str::<Patch<String, u32>>(&json).unwrap(),
+      patch
+    );
+
+    let bytes = postcard::to_allocvec(&patch).unwrap();
+    assert_eq!(
+      postcard::from_bytes::<Patch<String, u32>>(&bytes).unwrap(),
+      patch
+    );
+
+    let json = r#"[{"Move":{"to":1,"other":null,"from":0}}]"#;
+    let patch: Patch<String, u32> = serde_json::from_str(json).unwrap();
+    assert_eq!(patch.operations(), [Operation::Move { from: 0, to: 1 }]);
+
+    let json = r#"[{"Remove":{"key":"a"}}]"#;
+    let error = serde_json::from_str::<Patch<String, u32>>(json).unwrap_err();
+    assert_eq!(error.to_string(), "missing field `nth` at line 1 column 22");
+
+    let json = r#"[{"Remove":{"key":"a","nth":0,"key":"b"}}]"#;
+    let error = serde_json::from_str::<Patch<String, u32>>(json).unwrap_err();
+    assert_eq!(
+      error.to_string(),
+      "duplicate field `key` at line 1 column 40"
+    );
+
+    let json = r#"[{"Swap":{}}]"#;
+    assert!(serde_json::from_str::<Patch<String, u32>>(json).is_err());
+  }
+}
@@ -0,0 +1,251 @@
+//! Provides `proptest` strategies that generate [`ListOrderedMultimap`]s, for property testing code that uses
+//! multimaps.
+//!
+//! Bugs in code built on multimaps tend to hide in a few shapes of maps: keys with many values, values of different keys
+//! interleaved in global order, and maps that had entries removed. The strategies control each of them:
+//!
+//!  - The keys of a map are drawn from a pool of generated keys whose size is chosen separately from the number of
+//!    values. The smaller the pool is relative to the number of values, the more values share a key.
+//!  - [`Interleaving`] controls whether the values of different keys are interleaved in global order, or whether the
+//!    values of each key are adjacent.
+//!  - [`fragmented_multimap`] additionally appends entries that it removes again, which leaves vacant slots in the
+//!    internal storage of the map. A key whose first value was removed keeps its position in the order of the keys, so
+//!    the order of the keys may differ from the order of their first remaining values.
+//!
+//! # Examples
+//!
+//! ```
+//! use ordered_multimap::proptest::{multimap, Interleaving};
+//! use proptest::{prelude::*, test_runner::TestRunner};
+//!
+//! let strategy = multimap(any::<u32>(), any::<u8>(), 1..4, 0..32, Interleaving::Random);
+//!
+//! TestRunner::default()
+//!   .run(&strategy, |map| {
+//!     prop_assert!(map.keys_len() <= 3);
+//!     prop_assert!(map.values_len() < 32);
+//!     Ok(())
+//!   })
+//!   .unwrap();
+//! ```
+
+use alloc::vec::Vec;
+use core::{fmt::Debug, hash::Hash};
+
+use proptest::{
+  collection::{self, SizeRange},
+  prelude::*,
+  sample::Index,
+};
+
+use crate::ListOrderedMultimap;
+
+/// How the values of different keys are ordered in the global order of a generated multimap.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Interleaving {
+  /// The values of each key are adjacent, and the keys are in the order of their first value.
+  Grouped,
+
+  /// Each value belongs to a key chosen at random, so the values of different keys are interleaved.
+  Random,
+}
+
+/// Returns a strategy that generates multimaps with keys drawn from a pool of `keys` keys generated by `key`, and
+/// `values` values generated by `value`.
+///
+/// Keys generated for the pool may be equal, so a map has at most as many keys as the pool. A map has no values if the
+/// pool is empty.
+///
+/// # Examples
+///
+/// ```
+/// use ordered_multimap::proptest::{multimap, Interleaving};
+/// use proptest::{prelude::*, strategy::ValueTree, test_runner::TestRunner};
+///
+/// let strategy = multimap(any::<u64>(), any::<u8>(), 3, 100, Interleaving::Grouped);
+/// let map = strategy.new_tree(&mut TestRunner::default()).unwrap().current();
+///
+/// assert_eq!(map.values_len(), 100);
+/// assert!(map.keys_len() <= 3);
+/// ```
+pub fn multimap<KeyStrategy, ValueStrategy>(
+  key: KeyStrategy,
+  value: ValueStrategy,
+  keys: impl Into<SizeRange>,
+  values: impl Into<SizeRange>,
+  interleaving: Interleaving,
+) -> impl Strategy<Value = ListOrderedMultimap<KeyStrategy::Value, ValueStrategy::Value>>
+where
+  KeyStrategy: Clone + Strategy,
+  KeyStrategy::Value: Clone + Eq + Hash,
+  ValueStrategy: Clone + Strategy,
+  ValueStrategy::Value: Debug,
+{
+  fragmented_multimap(key, value, keys, values, interleaving, 0)
+}
+
+/// Returns a strategy that generates multimaps like [`multimap`], whose internal storage is fragmented by `removals`
+/// additional entries that are appended among the others and removed again.
+///
+/// The key of each removed entry is either taken from the pool, which leaves a vacant slot among the values of that key,
+/// or generated by `key`, which usually also leaves a vacant slot among the keys.
+///
+/// # Examples
+///
+/// ```
+/// use ordered_multimap::proptest::{fragmented_multimap, Interleaving};
+/// use proptest::{prelude::*, strategy::ValueTree, test_runner::TestRunner};
+///
+/// let strategy = fragmented_multimap(any::<u64>(), any::<u8>(), 3, 10, Interleaving::Random, 20);
+/// let map = strategy.new_tree(&mut TestRunner::default()).unwrap().current();
+///
+/// assert_eq!(map.values_len(), 10);
+/// assert!(map.values_capacity() >= 30);
+/// ```
+pub fn fragmented_multimap<KeyStrategy, ValueStrategy>(
+  key: KeyStrategy,
+  value: ValueStrategy,
+  keys: impl Into<SizeRange>,
+  values: impl Into<SizeRange>,
+  interleaving: Interleaving,
+  removals: impl Into<SizeRange>,
+) -> impl Strategy<Value = ListOrderedMultimap<KeyStrategy::Value, ValueStrategy::Value>>
+where
+  KeyStrategy: Clone + Strategy,
+  KeyStrategy::Value: Clone + Eq + Hash,
+  ValueStrategy: Clone + Strategy,
+  ValueStrategy::Value: Debug,
+{
+  let pool = collection::vec(key.clone(), keys);
+  let entries = collection::vec((any::<Index>(), value.clone()), values);
+  let removed = collection::vec(
+    (any::<Index>(), any::<Option<Index>>(), key, value),
+    removals,
+  );
+
+  (pool, entries, removed)
+    .prop_map(move |(pool, entries, removed)| build(pool, entries, interleaving, removed))
+}
+
+/// Builds a multimap from the generated pool of keys, the entries with indices into the pool, and the removed entries
+/// with their positions and either an index into the pool or their own key.
+fn build<Key, Value>(
+  pool: Vec<Key>,
+  entries: Vec<(Index, Value)>,
+  interleaving: Interleaving,
+  removed: Vec<(Index, Option<Index>, Key, Value)>,
+) -> ListOrderedMultimap<Key, Value>
+where
+  Key: Clone + Eq + Hash,
+{
+  if pool.is_empty() {
+    return ListOrderedMultimap::new();
+  }
+
+  // Equal keys in the pool are identified by their first position, so that grouping keeps their values adjacent.
+  let mut entries = entries
+    .into_iter()
+    .map(|(index, value)| {
+      let key = index.get(&pool);
+      let position = pool.iter().position(|other| other == key).unwrap();
+      (position, value)
+    })
+    .collect::<Vec<_>>();
+
+  if interleaving == Interleaving::Grouped {
+    entries.sort_by_key(|&(position, _)| position);
+  }
+
+  let mut sequence = entries
+    .into_iter()
+    .map(|(position, value)| (pool[position].clone(), value, false))
+    .collect::<Vec<_>>();
+
+  for (position, index, key, value) in removed {
+    let key = index.map_or(key, |index| index.get(&pool).clone());
+    sequence.insert(position.index(sequence.len() + 1), (key, value, true));
+  }
+
+  let mut map = ListOrderedMultimap::with_capacity(pool.len(), sequence.len());
+  let mut removals = Vec::with_capacity(sequence.len());
+
+  for (key, value, remove) in sequence {
+    let _ = map.append(key, value);
+    removals.push(remove);
+  }
+
+  // `retain` visits the values in global order, which is the order they were appended in.
+  let mut removals = removals.into_iter();
+  map.retain(|_, _| !removals.next().unwrap());
+  map
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use proptest::{strategy::ValueTree, test_runner::TestRunner};
+
+  use super::*;
+
+  fn is_grouped<Key, Value>(map: &ListOrderedMultimap<Key, Value>) -> bool
+  where
+    Key: Eq,
+  {
+    let mut seen = Vec::new();
+
+    for (key, _) in map.iter() {
+      if seen.last() != Some(&key) {
+        if seen.contains(&key) {
+          return false;
+        }
+
+        seen.push(key);
+      }
+    }
+
+    true
+  }
+
+  proptest! {
+    #[test]
+    fn test_fragmented_multimap(
+      map in fragmented_multimap(any::<u8>(), any::<u16>(), 1..8, 0..64, Interleaving::Grouped, 1..64)
+    ) {
+      prop_assert!(map.keys_len() < 8);
+      prop_assert!(map.values_len() < 64);
+      prop_assert!(map.values_capacity() > map.values_len());
+      prop_assert!(is_grouped(&map));
+      prop_assert_eq!(map.clone(), map.iter().map(|(&key, &value)| (key, value)).collect());
+    }
+
+    #[test]
+    fn test_multimap(
+      map in multimap(any::<u8>(), any::<u16>(), 1..8, 0..64, Interleaving::Grouped)
+    ) {
+      prop_assert!(map.keys_len() < 8);
+      prop_assert!(map.values_len() < 64);
+      prop_assert!(is_grouped(&map));
+    }
+  }
+
+  #[test]
+  fn test_multimap_collisions() {
+    let mut runner = TestRunner::deterministic();
+    let strategy = multimap(any::<u64>(), any::<u16>(), 2, 200, Interleaving::Random);
+    let map = strategy.new_tree(&mut runner).unwrap().current();
+
+    assert_eq!(map.values_len(), 200);
+    assert_eq!(map.keys_len(), 2);
+    assert!(!is_grouped(&map));
+  }
+
+  #[test]
+  fn test_multimap_empty_pool() {
+    let mut runner = TestRunner::deterministic();
+    let strategy = fragmented_multimap(any::<u8>(), any::<u16>(), 0, 10, Interleaving::Random, 10);
+    let map = strategy.new_tree(&mut runner).unwrap().current();
+
+    assert!(map.is_empty());
+  }
+}
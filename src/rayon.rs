@@ -0,0 +1,625 @@
+//! Provides parallel iterators over [`ListOrderedMultimap`] and parallel collection into it using `rayon`.
+//!
+//! [`par_iter`](rayon::iter::IntoParallelRefIterator::par_iter) and
+//! [`par_iter_mut`](rayon::iter::IntoParallelRefMutIterator::par_iter_mut) are available through the
+//! `rayon::prelude`, and [`ListOrderedMultimap::par_values_mut`] and [`ListOrderedMultimap::par_pairs`] are the
+//! parallel counterparts of [`ListOrderedMultimap::values_mut`] and [`ListOrderedMultimap::pairs`].
+//!
+//! The keys and values of a multimap are stored in linked lists, which cannot be split for parallel processing without
+//! walking them. The parallel iterators therefore first collect references to the items into a vector in a sequential
+//! pass, and then process the items in parallel. The pass takes about as long as a sequential iteration that does no
+//! work per item, so the parallel iterators only pay off with several threads and when the work done per item outweighs
+//! the cost of the pass. All parallel iterators are indexed, so collecting their results, for example into a [`Vec`],
+//! keeps the global order of the multimap.
+//!
+//! Collecting a parallel iterator into a multimap with
+//! [`collect`](rayon::iter::ParallelIterator::collect) or [`par_extend`](ParallelExtend::par_extend) appends the
+//! key-value pairs in the order of the parallel iterator, as if they had been appended sequentially.
+//!
+//! # Examples
+//!
+//! ```
+//! use ordered_multimap::ListOrderedMultimap;
+//! use rayon::prelude::*;
+//!
+//! let map: ListOrderedMultimap<_, _> = (0..1000).into_par_iter().map(|value| (value % 3, value)).collect();
+//! assert_eq!(map.keys().collect::<Vec<_>>(), [&0, &1, &2]);
+//!
+//! let squares = map.par_iter().map(|(_, value)| value * value).collect::<Vec<_>>();
+//! assert_eq!(squares[..4], [0, 1, 4, 9]);
+//!
+//! let sums = map
+//!   .par_pairs()
+//!   .map(|(key, values)| (*key, values.sum::<i32>()))
+//!   .collect::<Vec<_>>();
+//! assert_eq!(sums, [(0, 166_833), (1, 166_167), (2, 166_500)]);
+//! ```
+
+use alloc::vec::Vec;
+use core::{
+  fmt::{self, Debug, Formatter},
+  hash::{BuildHasher, Hash},
+};
+
+use rayon::iter::{
+  plumbing::{Consumer, ProducerCallback, UnindexedConsumer},
+  FromParallelIterator, IndexedParallelIterator, IntoParallelIterator, ParallelExtend,
+  ParallelIterator,
+};
+
+use crate::{list_ordered_multimap::EntryValues, ListOrderedMultimap};
+
+impl<Key, Value, State> ListOrderedMultimap<Key, Value, State> {
+  /// Returns a parallel iterator that yields mutable references to all values in the multimap in global order.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::ListOrderedMultimap;
+  /// use rayon::prelude::*;
+  ///
+  /// let mut map = ListOrderedMultimap::new();
+  /// map.insert("key1", 1);
+  /// map.insert("key2", 2);
+  /// map.append("key1", 3);
+  ///
+  /// map.par_values_mut().for_each(|value| *value *= 10);
+  /// assert_eq!(map.values().collect::<Vec<_>>(), [&10, &20, &30]);
+  /// ```
+  #[must_use]
+  pub fn par_values_mut(&mut self) -> ParValuesMut<'_, Value>
+  where
+    Value: Send,
+  {
+    let mut values = Vec::with_capacity(self.values_len());
+    values.extend(self.values_mut());
+    ParValuesMut(values)
+  }
+
+  /// Returns a parallel iterator that yields immutable references to keys and all associated values with those keys as
+  /// separate iterators. The order of the yielded pairs is the order in which the keys were first inserted into the
+  /// multimap.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::ListOrderedMultimap;
+  /// use rayon::prelude::*;
+  ///
+  /// let mut map = ListOrderedMultimap::new();
+  /// map.insert("key1", 1);
+  /// map.insert("key2", 2);
+  /// map.append("key1", 3);
+  ///
+  /// let counts = map
+  ///   .par_pairs()
+  ///   .map(|(key, values)| (*key, values.count()))
+  ///   .collect::<Vec<_>>();
+  /// assert_eq!(counts, [("key1", 2), ("key2", 1)]);
+  /// ```
+  #[must_use]
+  pub fn par_pairs(&self) -> ParKeyValues<'_, Key, Value>
+  where
+    Key: Eq + Hash + Sync,
+    State: BuildHasher,
+    Value: Sync,
+  {
+    let mut pairs = Vec::with_capacity(self.keys_len());
+    pairs.extend(self.pairs());
+    ParKeyValues(pairs)
+  }
+}
+
+impl<Key, Value, State> FromParallelIterator<(Key, Value)>
+  for ListOrderedMultimap<Key, Value, State>
+where
+  Key: Eq + Hash + Send,
+  State: BuildHasher + Default,
+  Value: Send,
+{
+  fn from_par_iter<Iter>(par_iter: Iter) -> Self
+  where
+    Iter: IntoParallelIterator<Item = (Key, Value)>,
+  {
+    let mut map = ListOrderedMultimap::with_hasher(State::default());
+    map.par_extend(par_iter);
+    map
+  }
+}
+
+impl<Key, Value, State> ParallelExtend<(Key, Value)> for ListOrderedMultimap<Key, Value, State>
+where
+  Key: Eq + Hash + Send,
+  State: BuildHasher,
+  Value: Send,
+{
+  fn par_extend<Iter>(&mut self, par_iter: Iter)
+  where
+    Iter: IntoParallelIterator<Item = (Key, Value)>,
+  {
+    // Appending has to be sequential, so only the production of the key-value pairs is parallel.
+    let pairs: Vec<_> = par_iter.into_par_iter().collect();
+    self.extend(pairs);
+  }
+}
+
+impl<'a, Key, Value, State> ParallelExtend<(&'a Key, &'a Value)>
+  for ListOrderedMultimap<Key, Value, State>
+where
+  Key: Copy + Eq + Hash + Send + Sync,
+  State: BuildHasher,
+  Value: Copy + Send + Sync,
+{
+  fn par_extend<Iter>(&mut self, par_iter: Iter)
+  where
+    Iter: IntoParallelIterator<Item = (&'a Key, &'a Value)>,
+  {
+    self.par_extend(par_iter.into_par_iter().map(|(&key, &value)| (key, value)));
+  }
+}
+
+impl<'map, Key, Value, State> IntoParallelIterator for &'map ListOrderedMultimap<Key, Value, State>
+where
+  Key: Sync,
+  Value: Sync,
+{
+  type Item = (&'map Key, &'map Value);
+  type Iter = ParIter<'map, Key, Value>;
+
+  fn into_par_iter(self) -> Self::Iter {
+    let mut pairs = Vec::with_capacity(self.values_len());
+    pairs.extend(self.iter());
+    ParIter(pairs)
+  }
+}
+
+impl<'map, Key, Value, State> IntoParallelIterator
+  for &'map mut ListOrderedMultimap<Key, Value, State>
+where
+  Key: Sync,
+  Value: Send,
+{
+  type Item = (&'map Key, &'map mut Value);
+  type Iter = ParIterMut<'map, Key, Value>;
+
+  fn into_par_iter(self) -> Self::Iter {
+    let mut pairs = Vec::with_capacity(self.values_len());
+    pairs.extend(self.iter_mut());
+    ParIterMut(pairs)
+  }
+}
+
+/// A parallel iterator that yields immutable references to all key-value pairs in a multimap in global order.
+pub struct ParIter<'map, Key, Value>(Vec<(&'map Key, &'map Value)>);
+
+impl<Key, Value> Clone for ParIter<'_, Key, Value> {
+  fn clone(&self) -> Self {
+    ParIter(self.0.clone())
+  }
+}
+
+impl<Key, Value> Debug for ParIter<'_, Key, Value>
+where
+  Key: Debug,
+  Value: Debug,
+{
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    formatter.write_str("ParIter(")?;
+    formatter.debug_list().entries(self.0.iter()).finish()?;
+    formatter.write_str(")")
+  }
+}
+
+impl<Key, Value> IndexedParallelIterator for ParIter<'_, Key, Value>
+where
+  Key: Sync,
+  Value: Sync,
+{
+  fn drive<C>(self, consumer: C) -> C::Result
+  where
+    C: Consumer<Self::Item>,
+  {
+    self.0.into_par_iter().drive(consumer)
+  }
+
+  fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  fn with_producer<CB>(self, callback: CB) -> CB::Output
+  where
+    CB: ProducerCallback<Self::Item>,
+  {
+    self.0.into_par_iter().with_producer(callback)
+  }
+}
+
+impl<'map, Key, Value> ParallelIterator for ParIter<'map, Key, Value>
+where
+  Key: Sync,
+  Value: Sync,
+{
+  type Item = (&'map Key, &'map Value);
+
+  fn drive_unindexed<C>(self, consumer: C) -> C::Result
+  where
+    C: UnindexedConsumer<Self::Item>,
+  {
+    self.0.into_par_iter().drive_unindexed(consumer)
+  }
+
+  fn opt_len(&self) -> Option<usize> {
+    Some(self.0.len())
+  }
+}
+
+/// A parallel iterator that yields immutable references to keys and mutable references to values for all key-value
+/// pairs in a multimap in global order.
+pub struct ParIterMut<'map, Key, Value>(Vec<(&'map Key, &'map mut Value)>);
+
+impl<Key, Value> Debug for ParIterMut<'_, Key, Value>
+where
+  Key: Debug,
+  Value: Debug,
+{
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    formatter.write_str("ParIterMut(")?;
+    formatter.debug_list().entries(self.0.iter()).finish()?;
+    formatter.write_str(")")
+  }
+}
+
+impl<Key, Value> IndexedParallelIterator for ParIterMut<'_, Key, Value>
+where
+  Key: Sync,
+  Value: Send,
+{
+  fn drive<C>(self, consumer: C) -> C::Result
+  where
+    C: Consumer<Self::Item>,
+  {
+    self.0.into_par_iter().drive(consumer)
+  }
+
+  fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  fn with_producer<CB>(self, callback: CB) -> CB::Output
+  where
+    CB: ProducerCallback<Self::Item>,
+  {
+    self.0.into_par_iter().with_producer(callback)
+  }
+}
+
+impl<'map, Key, Value> ParallelIterator for ParIterMut<'map, Key, Value>
+where
+  Key: Sync,
+  Value: Send,
+{
+  type Item = (&'map Key, &'map mut Value);
+
+  fn drive_unindexed<C>(self, consumer: C) -> C::Result
+  where
+    C: UnindexedConsumer<Self::Item>,
+  {
+    self.0.into_par_iter().drive_unindexed(consumer)
+  }
+
+  fn opt_len(&self) -> Option<usize> {
+    Some(self.0.len())
+  }
+}
+
+/// A parallel iterator that yields immutable references to keys and all associated values with those keys as separate
+/// iterators. The order of the keys is the order in which they were first inserted into the multimap.
+pub struct ParKeyValues<'map, Key, Value>(Vec<(&'map Key, EntryValues<'map, Key, Value>)>);
+
+impl<Key, Value> Clone for ParKeyValues<'_, Key, Value> {
+  fn clone(&self) -> Self {
+    ParKeyValues(self.0.clone())
+  }
+}
+
+impl<Key, Value> Debug for ParKeyValues<'_, Key, Value>
+where
+  Key: Debug,
+  Value: Debug,
+{
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    formatter.write_str("ParKeyValues(")?;
+    formatter.debug_list().entries(self.0.iter()).finish()?;
+    formatter.write_str(")")
+  }
+}
+
+impl<Key, Value> IndexedParallelIterator for ParKeyValues<'_, Key, Value>
+where
+  Key: Sync,
+  Value: Sync,
+{
+  fn drive<C>(self, consumer: C) -> C::Result
+  where
+    C: Consumer<Self::Item>,
+  {
+    self.0.into_par_iter().drive(consumer)
+  }
+
+  fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  fn with_producer<CB>(self, callback: CB) -> CB::Output
+  where
+    CB: ProducerCallback<Self::Item>,
+  {
+    self.0.into_par_iter().with_producer(callback)
+  }
+}
+
+impl<'map, Key, Value> ParallelIterator for ParKeyValues<'map, Key, Value>
+where
+  Key: Sync,
+  Value: Sync,
+{
+  type Item = (&'map Key, EntryValues<'map, Key, Value>);
+
+  fn drive_unindexed<C>(self, consumer: C) -> C::Result
+  where
+    C: UnindexedConsumer<Self::Item>,
+  {
+    self.0.into_par_iter().drive_unindexed(consumer)
+  }
+
+  fn opt_len(&self) -> Option<usize> {
+    Some(self.0.len())
+  }
+}
+
+/// A parallel iterator that yields mutable references to all values in a multimap in global order.
+pub struct ParValuesMut<'map, Value>(Vec<&'map mut Value>);
+
+impl<Value> Debug for ParValuesMut<'_, Value>
+where
+  Value: Debug,
+{
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    formatter.write_str("ParValuesMut(")?;
+    formatter.debug_list().entries(self.0.iter()).finish()?;
+    formatter.write_str(")")
+  }
+}
+
+impl<Value> IndexedParallelIterator for ParValuesMut<'_, Value>
+where
+  Value: Send,
+{
+  fn drive<C>(self, consumer: C) -> C::Result
+  where
+    C: Consumer<Self::Item>,
+  {
+    self.0.into_par_iter().drive(consumer)
+  }
+
+  fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  fn with_producer<CB>(self, callback: CB) -> CB::Output
+  where
+    CB: ProducerCallback<Self::Item>,
+  {
+    self.0.into_par_iter().with_producer(callback)
+  }
+}
+
+impl<'map, Value> ParallelIterator for ParValuesMut<'map, Value>
+where
+  Value: Send,
+{
+  type Item = &'map mut Value;
+
+  fn drive_unindexed<C>(self, consumer: C) -> C::Result
+  where
+    C: UnindexedConsumer<Self::Item>,
+  {
+    self.0.into_par_iter().drive_unindexed(consumer)
+  }
+
+  fn opt_len(&self) -> Option<usize> {
+    Some(self.0.len())
+  }
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use core::marker::PhantomData;
+  use std::sync::MutexGuard;
+
+  use rayon::iter::{IntoParallelRefIterator, IntoParallelRefMutIterator};
+
+  use super::*;
+
+  /// Creates a multimap whose value list has vacant slots and whose global order differs from the storage order.
+  fn fragmented_map() -> ListOrderedMultimap<usize, usize> {
+    let mut map: ListOrderedMultimap<_, _> = (0..100).map(|value| (value % 7, value)).collect();
+    map.remove(&3);
+    map.retain(|_, value| *value % 5 != 0);
+    map.extend((100..110).map(|value| (value % 7, value)));
+    map
+  }
+
+  /// Creates a multimap with interleaved values of two keys.
+  fn small_map() -> ListOrderedMultimap<&'static str, i32> {
+    let mut map = ListOrderedMultimap::new();
+    map.append("key1", 1);
+    map.append("key2", 2);
+    map.append("key1", 3);
+    map
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_from_par_iter() {
+    let map: ListOrderedMultimap<_, _> = (0..10_000)
+      .into_par_iter()
+      .map(|value| (value % 10, value))
+      .collect();
+    let expected: ListOrderedMultimap<_, _> =
+      (0..10_000).map(|value| (value % 10, value)).collect();
+
+    assert_eq!(map, expected);
+    assert!(map.iter().eq(expected.iter()));
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_from_par_iter_unindexed() {
+    let map: ListOrderedMultimap<_, _> = (0..10_000)
+      .into_par_iter()
+      .filter(|value| value % 3 == 0)
+      .map(|value| (value % 10, value))
+      .collect();
+
+    assert!(map
+      .values()
+      .copied()
+      .eq((0..10_000).filter(|value| value % 3 == 0)));
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_par_extend() {
+    let mut map = fragmented_map();
+    let mut expected = map.clone();
+
+    map.par_extend((0..1000).into_par_iter().map(|value| (value % 13, value)));
+    expected.extend((0..1000).map(|value| (value % 13, value)));
+    assert!(map.iter().eq(expected.iter()));
+
+    let other = fragmented_map();
+    map.par_extend(other.par_iter());
+    expected.extend(other.iter());
+    assert!(map.iter().eq(expected.iter()));
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_par_iter() {
+    let map = fragmented_map();
+
+    let pairs = map.par_iter().collect::<Vec<_>>();
+    assert_eq!(pairs, map.iter().collect::<Vec<_>>());
+    assert_eq!(map.par_iter().len(), map.values_len());
+    assert_eq!(
+      map.par_iter().clone().rev().collect::<Vec<_>>(),
+      map.iter().rev().collect::<Vec<_>>()
+    );
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_par_iter_mut() {
+    let mut map = fragmented_map();
+    let expected = map
+      .iter()
+      .map(|(key, value)| (*key, key * value))
+      .collect::<Vec<_>>();
+
+    assert_eq!(map.par_iter_mut().len(), map.values_len());
+    map.par_iter_mut().for_each(|(key, value)| *value *= key);
+    assert_eq!(
+      map
+        .iter()
+        .map(|(key, value)| (*key, *value))
+        .collect::<Vec<_>>(),
+      expected
+    );
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_par_pairs() {
+    let map = fragmented_map();
+
+    let pairs = map
+      .par_pairs()
+      .map(|(key, values)| (*key, values.copied().collect::<Vec<_>>()))
+      .collect::<Vec<_>>();
+    let expected = map
+      .pairs()
+      .map(|(key, values)| (*key, values.copied().collect::<Vec<_>>()))
+      .collect::<Vec<_>>();
+    assert_eq!(pairs, expected);
+    assert_eq!(map.par_pairs().len(), map.keys_len());
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_par_pairs_sync() {
+    /// A type that can be shared but not sent between threads.
+    #[derive(Debug, Eq, Hash, PartialEq)]
+    struct SyncOnly(usize, PhantomData<MutexGuard<'static, ()>>);
+
+    let map: ListOrderedMultimap<_, _> = (0..10)
+      .map(|value| {
+        (
+          SyncOnly(value % 3, PhantomData),
+          SyncOnly(value, PhantomData),
+        )
+      })
+      .collect();
+
+    let counts = map
+      .par_pairs()
+      .map(|(key, values)| (key.0, values.map(|value| value.0).sum::<usize>()))
+      .collect::<Vec<_>>();
+    assert_eq!(counts, [(0, 18), (1, 12), (2, 15)]);
+  }
+
+  #[test]
+  fn test_list_ordered_multimap_par_values_mut() {
+    let mut map = fragmented_map();
+    let expected = map.values().map(|value| value + 1).collect::<Vec<_>>();
+
+    assert_eq!(map.par_values_mut().len(), map.values_len());
+    map.par_values_mut().for_each(|value| *value += 1);
+    assert_eq!(map.values().copied().collect::<Vec<_>>(), expected);
+  }
+
+  #[test]
+  fn test_par_iter_debug() {
+    let map = small_map();
+
+    assert_eq!(
+      format!("{:?}", map.par_iter()),
+      r#"ParIter([("key1", 1), ("key2", 2), ("key1", 3)])"#
+    );
+  }
+
+  #[test]
+  fn test_par_iter_mut_debug() {
+    let mut map = small_map();
+
+    assert_eq!(
+      format!("{:?}", map.par_iter_mut()),
+      r#"ParIterMut([("key1", 1), ("key2", 2), ("key1", 3)])"#
+    );
+  }
+
+  #[test]
+  fn test_par_key_values_debug() {
+    let map = small_map();
+
+    assert_eq!(
+      format!("{:?}", map.par_pairs()),
+      r#"ParKeyValues([("key1", EntryValues([1, 3])), ("key2", EntryValues([2]))])"#
+    );
+  }
+
+  #[test]
+  fn test_par_values_mut_debug() {
+    let mut map = small_map();
+
+    assert_eq!(
+      format!("{:?}", map.par_values_mut()),
+      "ParValuesMut([1, 2, 3])"
+    );
+  }
+}
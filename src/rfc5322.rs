@@ -0,0 +1,614 @@
+//! Provides parsing and writing of RFC 5322 header blocks, such as the headers of an email or of a MIME part.
+//!
+//! The headers are parsed into a [`Headers`] multimap from each [`HeaderName`] to the values of its fields in the
+//! order in which they occur. The relative order of fields matters for example for `Received` trace fields, which are
+//! prepended by each relay, and for signatures such as DKIM, which sign specific occurrences of fields. Header names are
+//! compared case-insensitively. A multimap stores each key once, so all fields with the same name are written with the
+//! case of the first one.
+//!
+//! Folded fields, whose value continues on lines starting with a space or tab, are unfolded by removing the line breaks
+//! before the continuation lines. Whitespace around values is removed. [`write()`] folds fields longer than 78
+//! characters at whitespace, so that parsing the written fields results in the same values.
+//!
+//! # Examples
+//!
+//! ```
+//! use ordered_multimap::rfc5322::{self, HeaderName};
+//!
+//! let input = "Received: from b.example\r\n  by c.example\r\nreceived: from a.example\r\nSubject: Hi\r\n\r\nBody";
+//! let (headers, body) = rfc5322::parse(input).unwrap();
+//!
+//! let received = headers
+//!   .get_all(&HeaderName::from_static("RECEIVED"))
+//!   .collect::<Vec<_>>();
+//! assert_eq!(received, ["from b.example  by c.example", "from a.example"]);
+//! assert_eq!(body, "Body");
+//!
+//! assert_eq!(
+//!   rfc5322::to_string(&headers).unwrap(),
+//!   "Received: from b.example  by c.example\r\nReceived: from a.example\r\nSubject: Hi\r\n"
+//! );
+//! ```
+
+use alloc::string::{String, ToString};
+use core::{
+  fmt::{self, Debug, Display, Formatter, Write},
+  hash::{Hash, Hasher},
+};
+
+use crate::ListOrderedMultimap;
+
+/// The maximum length of a written line, excluding the line break, unless it cannot be folded.
+const LINE_LENGTH: usize = 78;
+
+/// The fields of a header block.
+pub type Headers = ListOrderedMultimap<HeaderName, String>;
+
+/// The name of a header field, which is compared case-insensitively but keeps its original case.
+///
+/// A header name consists of one or more printable ASCII characters other than `:`.
+#[derive(Clone)]
+pub struct HeaderName(String);
+
+impl HeaderName {
+  /// Returns the header name in its original case.
+  #[must_use]
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+
+  /// Creates a header name from a static string.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the name is not a valid header name.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::rfc5322::HeaderName;
+  ///
+  /// assert_eq!(HeaderName::from_static("Subject"), HeaderName::from_static("subject"));
+  /// ```
+  #[must_use]
+  pub fn from_static(name: &'static str) -> HeaderName {
+    HeaderName::new(name).expect("invalid header name")
+  }
+
+  /// Creates a header name, or returns `None` if the name is empty or contains characters other than printable ASCII
+  /// characters except `:`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use ordered_multimap::rfc5322::HeaderName;
+  ///
+  /// assert_eq!(HeaderName::new("DKIM-Signature").unwrap().as_str(), "DKIM-Signature");
+  /// assert!(HeaderName::new("Bad Name").is_none());
+  /// ```
+  #[must_use]
+  pub fn new(name: &str) -> Option<HeaderName> {
+    let valid = !name.is_empty()
+      && name
+        .bytes()
+        .all(|byte| matches!(byte, b'!'..=b'9' | b';'..=b'~'));
+    valid.then(|| HeaderName(name.to_string()))
+  }
+}
+
+impl Debug for HeaderName {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    Debug::fmt(&self.0, formatter)
+  }
+}
+
+impl Display for HeaderName {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    formatter.write_str(&self.0)
+  }
+}
+
+impl Eq for HeaderName {}
+
+impl Hash for HeaderName {
+  fn hash<H>(&self, state: &mut H)
+  where
+    H: Hasher,
+  {
+    for byte in self.0.bytes() {
+      state.write_u8(byte.to_ascii_lowercase());
+    }
+
+    state.write_u8(0xff);
+  }
+}
+
+impl PartialEq for HeaderName {
+  fn eq(&self, other: &HeaderName) -> bool {
+    self.0.eq_ignore_ascii_case(&other.0)
+  }
+}
+
+impl PartialEq<str> for HeaderName {
+  fn eq(&self, other: &str) -> bool {
+    self.0.eq_ignore_ascii_case(other)
+  }
+}
+
+impl PartialEq<&str> for HeaderName {
+  fn eq(&self, other: &&str) -> bool {
+    self.0.eq_ignore_ascii_case(other)
+  }
+}
+
+/// The error returned when parsing a header block fails.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ParseError {
+  /// The line number of the invalid line, starting at 1.
+  line: usize,
+
+  /// The kind of error.
+  kind: ParseErrorKind,
+}
+
+impl ParseError {
+  /// Returns the kind of error.
+  #[must_use]
+  pub fn kind(&self) -> ParseErrorKind {
+    self.kind
+  }
+
+  /// Returns the line number of the invalid line, starting at 1.
+  #[must_use]
+  pub fn line(&self) -> usize {
+    self.line
+  }
+}
+
+impl Display for ParseError {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    write!(formatter, "invalid header line {}: ", self.line)?;
+
+    match self.kind {
+      ParseErrorKind::InvalidName => write!(formatter, "invalid header name"),
+      ParseErrorKind::MissingColon => write!(formatter, "expected `:` after header name"),
+      ParseErrorKind::UnexpectedContinuation => {
+        write!(formatter, "continuation line without a header field")
+      }
+    }
+  }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The kind of a [`ParseError`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ParseErrorKind {
+  /// The header name before the `:` is empty or contains invalid characters.
+  InvalidName,
+
+  /// A line that is not a continuation line does not contain `:`.
+  MissingColon,
+
+  /// The first line starts with whitespace, so it would continue a field that does not exist.
+  UnexpectedContinuation,
+}
+
+/// The error returned when writing a header block fails.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct WriteError {
+  /// The index of the field in the global order, starting at 0.
+  field: usize,
+
+  /// The kind of error.
+  kind: WriteErrorKind,
+}
+
+impl WriteError {
+  /// Returns the index of the field in the global order, starting at 0.
+  #[must_use]
+  pub fn field(&self) -> usize {
+    self.field
+  }
+
+  /// Returns the kind of error.
+  #[must_use]
+  pub fn kind(&self) -> WriteErrorKind {
+    self.kind
+  }
+}
+
+impl Display for WriteError {
+  fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    write!(formatter, "cannot write header field {}: ", self.field)?;
+
+    match self.kind {
+      WriteErrorKind::InvalidValue => write!(formatter, "value contains a line break"),
+      WriteErrorKind::Writer => write!(formatter, "writer failed"),
+    }
+  }
+}
+
+impl std::error::Error for WriteError {}
+
+/// The kind of a [`WriteError`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum WriteErrorKind {
+  /// The value contains `\r` or `\n`, which would end the field and could inject other fields.
+  InvalidValue,
+
+  /// The writer returned an error.
+  Writer,
+}
+
+/// Parses a header block into its fields and returns them together with the rest of the input after the blank line
+/// that ends the header block.
+///
+/// Lines may end with either `\r\n` or `\n`. If there is no blank line, the whole input is parsed as the header block
+/// and the rest is empty.
+///
+/// # Errors
+///
+/// Returns an error for a line without a `:`, for an invalid header name and for a continuation line before the first
+/// field.
+///
+/// # Examples
+///
+/// ```
+/// use ordered_multimap::rfc5322::{self, HeaderName};
+///
+/// let (headers, body) = rfc5322::parse("To: a@example.com,\n b@example.com\nSubject: Hi\n\nHello").unwrap();
+///
+/// let to = headers.get(&HeaderName::from_static("to")).unwrap();
+/// assert_eq!(to, "a@example.com, b@example.com");
+/// assert_eq!(body, "Hello");
+///
+/// let error = rfc5322::parse("Subject Hi\n").unwrap_err();
+/// assert_eq!(error.line(), 1);
+/// ```
+pub fn parse(input: &str) -> Result<(Headers, &str), ParseError> {
+  let mut headers = Headers::new();
+  let mut offset = 0;
+
+  for (index, raw_line) in input.split_inclusive('\n').enumerate() {
+    offset += raw_line.len();
+    let line = raw_line.strip_suffix('\n').unwrap_or(raw_line);
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    let error = |kind| ParseError {
+      line: index + 1,
+      kind,
+    };
+
+    if line.is_empty() {
+      trim_values(&mut headers);
+      return Ok((headers, &input[offset..]));
+    }
+
+    if line.starts_with([' ', '\t']) {
+      let (_, value) = headers
+        .back_mut()
+        .ok_or_else(|| error(ParseErrorKind::UnexpectedContinuation))?;
+
+      if value.is_empty() {
+        value.push_str(line.trim_start_matches([' ', '\t']));
+      } else {
+        value.push_str(line);
+      }
+    } else {
+      let (name, value) = line
+        .split_once(':')
+        .ok_or_else(|| error(ParseErrorKind::MissingColon))?;
+      let name = HeaderName::new(name).ok_or_else(|| error(ParseErrorKind::InvalidName))?;
+      let value = value.trim_start_matches([' ', '\t']).to_string();
+      let _ = headers.append(name, value);
+    }
+  }
+
+  trim_values(&mut headers);
+  Ok((headers, ""))
+}
+
+/// Removes the whitespace at the end of the values, which is only known after all continuation lines are unfolded.
+fn trim_values(headers: &mut Headers) {
+  for value in headers.values_mut() {
+    let length = value.trim_end_matches([' ', '\t']).len();
+    value.truncate(length);
+  }
+}
+
+/// Writes the fields in global order, each followed by `\r\n` and folded at whitespace if it is longer than 78
+/// characters. The blank line that ends a header block is not written.
+///
+/// All values are checked before anything is written. Header names are checked when a [`HeaderName`] is created.
+///
+/// # Errors
+///
+/// Returns an error if a value contains `\r` or `\n`, or if the writer fails.
+///
+/// # Examples
+///
+/// ```
+/// use ordered_multimap::rfc5322::{self, HeaderName, Headers};
+///
+/// let mut headers = Headers::new();
+/// headers.append(HeaderName::from_static("Subject"), "word ".repeat(20).trim_end().to_string());
+///
+/// let mut output = String::new();
+/// rfc5322::write(&mut output, &headers).unwrap();
+/// assert!(output.lines().all(|line| line.len() <= 78));
+/// assert_eq!(rfc5322::parse(&output).unwrap().0, headers);
+///
+/// headers.append(HeaderName::from_static("To"), "a@example.com\r\nBcc: b@example.com".to_string());
+/// assert!(rfc5322::write(&mut String::new(), &headers).is_err());
+/// ```
+pub fn write<W, State>(
+  writer: &mut W,
+  headers: &ListOrderedMultimap<HeaderName, String, State>,
+) -> Result<(), WriteError>
+where
+  W: Write,
+{
+  if let Some(field) = headers
+    .values()
+    .position(|value| value.contains(['\r', '\n']))
+  {
+    return Err(WriteError {
+      field,
+      kind: WriteErrorKind::InvalidValue,
+    });
+  }
+
+  for (field, (name, value)) in headers.iter().enumerate() {
+    write_field(writer, name, value).map_err(|_| WriteError {
+      field,
+      kind: WriteErrorKind::Writer,
+    })?;
+  }
+
+  Ok(())
+}
+
+/// Writes a single field with a valid value, folded if it is too long.
+fn write_field<W>(writer: &mut W, name: &HeaderName, value: &str) -> fmt::Result
+where
+  W: Write,
+{
+  let mut rest = value;
+  let mut length = name.0.len() + 1;
+  write!(writer, "{name}:")?;
+
+  if !rest.is_empty() {
+    writer.write_char(' ')?;
+    length += 1;
+  }
+
+  while length + rest.len() > LINE_LENGTH {
+    let Some(fold) = fold_position(rest, LINE_LENGTH.saturating_sub(length)) else {
+      break;
+    };
+
+    writer.write_str(&rest[..fold])?;
+    writer.write_str("\r\n")?;
+    rest = &rest[fold..];
+    length = 0;
+  }
+
+  writer.write_str(rest)?;
+  writer.write_str("\r\n")?;
+  Ok(())
+}
+
+/// Writes the fields into a string, see [`write()`].
+///
+/// # Errors
+///
+/// Returns an error if a value contains `\r` or `\n`.
+pub fn to_string<State>(
+  headers: &ListOrderedMultimap<HeaderName, String, State>,
+) -> Result<String, WriteError> {
+  let mut output = String::new();
+  write(&mut output, headers)?;
+  Ok(output)
+}
+
+/// Returns the byte position of the whitespace before which to fold the value, preferring the last one within
+/// `maximum` bytes. Folding there must leave some non-whitespace before the line break.
+fn fold_position(value: &str, maximum: usize) -> Option<usize> {
+  let mut position = None;
+  let mut text = false;
+
+  for (index, character) in value.char_indices() {
+    if !matches!(character, ' ' | '\t') {
+      text = true;
+    } else if text {
+      if index > maximum {
+        return position.or(Some(index));
+      }
+
+      position = Some(index);
+    }
+  }
+
+  position
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use std::collections::hash_map::DefaultHasher;
+
+  use super::*;
+
+  fn hash(name: &HeaderName) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    hasher.finish()
+  }
+
+  fn fields(headers: &Headers) -> Vec<(&str, &str)> {
+    headers
+      .iter()
+      .map(|(name, value)| (name.as_str(), value.as_str()))
+      .collect()
+  }
+
+  #[test]
+  fn test_header_name() {
+    let name = HeaderName::from_static("Content-Type");
+
+    assert_eq!(name, HeaderName::from_static("content-TYPE"));
+    assert_eq!(hash(&name), hash(&HeaderName::from_static("CONTENT-TYPE")));
+    assert_ne!(name, HeaderName::from_static("Content-Length"));
+    assert_eq!(name, "content-type");
+    assert_eq!(name.to_string(), "Content-Type");
+    assert_eq!(format!("{name:?}"), r#""Content-Type""#);
+
+    assert!(HeaderName::new("").is_none());
+    assert!(HeaderName::new("a:b").is_none());
+    assert!(HeaderName::new("ä").is_none());
+  }
+
+  #[test]
+  fn test_parse() {
+    let input =
+      "A: 1\r\nb:2 \r\nEmpty:\r\n\tcontinued  \r\n  twice\r\nA:\t3\r\nC:\r\n\r\nbody\r\n\r\nmore";
+    let (headers, body) = parse(input).unwrap();
+
+    assert_eq!(
+      fields(&headers),
+      [
+        ("A", "1"),
+        ("b", "2"),
+        ("Empty", "continued    twice"),
+        ("A", "3"),
+        ("C", ""),
+      ]
+    );
+    assert_eq!(
+      headers
+        .get_all(&HeaderName::from_static("a"))
+        .collect::<Vec<_>>(),
+      ["1", "3"]
+    );
+    assert_eq!(body, "body\r\n\r\nmore");
+  }
+
+  #[test]
+  fn test_parse_empty() {
+    let (headers, body) = parse("").unwrap();
+    assert!(headers.is_empty());
+    assert_eq!(body, "");
+
+    let (headers, body) = parse("\nbody").unwrap();
+    assert!(headers.is_empty());
+    assert_eq!(body, "body");
+  }
+
+  #[test]
+  fn test_parse_error() {
+    let error = parse("A: 1\nB\n").unwrap_err();
+    assert_eq!(error.line(), 2);
+    assert_eq!(error.kind(), ParseErrorKind::MissingColon);
+    assert_eq!(
+      error.to_string(),
+      "invalid header line 2: expected `:` after header name"
+    );
+
+    let error = parse("A: 1\nB C: 2\n").unwrap_err();
+    assert_eq!(error.kind(), ParseErrorKind::InvalidName);
+    assert_eq!(
+      error.to_string(),
+      "invalid header line 2: invalid header name"
+    );
+
+    let error = parse(" A: 1\n").unwrap_err();
+    assert_eq!(error.line(), 1);
+    assert_eq!(error.kind(), ParseErrorKind::UnexpectedContinuation);
+    assert_eq!(
+      error.to_string(),
+      "invalid header line 1: continuation line without a header field"
+    );
+  }
+
+  #[test]
+  fn test_parse_without_body() {
+    let (headers, body) = parse("A: 1\nB: 2").unwrap();
+    assert_eq!(fields(&headers), [("A", "1"), ("B", "2")]);
+    assert_eq!(body, "");
+  }
+
+  #[test]
+  fn test_write() {
+    let mut headers = Headers::new();
+    headers.append(HeaderName::from_static("Received"), "from a".to_string());
+    headers.append(HeaderName::from_static("Subject"), "".to_string());
+    headers.append(HeaderName::from_static("received"), "from b".to_string());
+
+    assert_eq!(
+      to_string(&headers).unwrap(),
+      "Received: from a\r\nSubject:\r\nReceived: from b\r\n"
+    );
+  }
+
+  #[test]
+  fn test_write_fold() {
+    let long_word = "x".repeat(100);
+    let values = [
+      "word ".repeat(40).trim_end().to_string(),
+      format!("{long_word} a b"),
+      format!("a {long_word} b"),
+      format!("a{}b", " ".repeat(100)),
+      "é ".repeat(60).trim_end().to_string(),
+    ];
+
+    for value in values {
+      let mut headers = Headers::new();
+      headers.append(HeaderName::from_static("X-Long"), value);
+      let output = to_string(&headers).unwrap();
+
+      for line in output.split_terminator("\r\n") {
+        assert!(
+          line.len() <= LINE_LENGTH || line.contains(&long_word),
+          "{line:?}"
+        );
+        assert!(!line.trim().is_empty());
+      }
+
+      assert_eq!(parse(&output).unwrap().0, headers);
+    }
+  }
+
+  #[test]
+  fn test_write_error() {
+    for value in ["x\r\nBcc: evil", "x\nBcc: evil", "x\r"] {
+      let mut headers = Headers::new();
+      headers.append(HeaderName::from_static("Subject"), "Hi".to_string());
+      headers.append(HeaderName::from_static("To"), value.to_string());
+
+      let mut output = String::new();
+      let error = write(&mut output, &headers).unwrap_err();
+      assert_eq!(error.field(), 1);
+      assert_eq!(error.kind(), WriteErrorKind::InvalidValue);
+      assert_eq!(
+        error.to_string(),
+        "cannot write header field 1: value contains a line break"
+      );
+      assert_eq!(output, "");
+      assert_eq!(to_string(&headers), Err(error));
+    }
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+      fn write_str(&mut self, _: &str) -> fmt::Result {
+        Err(fmt::Error)
+      }
+    }
+
+    let mut headers = Headers::new();
+    headers.append(HeaderName::from_static("Subject"), "Hi".to_string());
+    let error = write(&mut FailingWriter, &headers).unwrap_err();
+    assert_eq!(error.kind(), WriteErrorKind::Writer);
+  }
+}
@@ -1,15 +1,80 @@
+//! Provides serde support for [`ListOrderedMultimap`].
+//!
+//! The [`Serialize`] and [`Deserialize`] implementations of [`ListOrderedMultimap`] use the [`as_pairs`]
+//! representation. The submodules provide `serialize` and `deserialize` functions for use with
+//! `#[serde(with = "...")]`, so that each field can choose its representation:
+//!
+//!  - [`as_pairs`] uses a sequence of key-value tuples in global order.
+//!  - [`as_map`] uses a map with one entry per value in global order, repeating keys with multiple values.
+//!  - [`as_grouped`] uses a map from each key to a sequence of its values. This loses the global interleaving.
+//!  - [`as_grouped_lossless`] uses the same map as [`as_grouped`] together with a sequence of key ordinals that
+//!    restores the global interleaving.
+//!
+//! Multimaps with `&'de str` or `&'de [u8]` keys and values borrow from the input with every representation. The
+//! functions in [`borrow_cow`] do the same for `Cow<'de, str>` keys and values.
+//!
+//! [`Iter`], [`KeyValues`] and [`ViewForKeys`] implement [`Serialize`] with the [`as_pairs`] representation, so a view
+//! of a multimap can be serialized without building a new multimap and deserialized as a [`ListOrderedMultimap`].
+//! [`KeyValues`] yields the values grouped by key, so the global interleaving is lost. [`EntryValues`] yields the
+//! values of a single key and, unlike the others, implements [`Serialize`] as a sequence of the values, since it does
+//! not know its key when created from [`EntryValuesDrain`] or [`EntryValuesMut`]. Serialize
+//! [`ListOrderedMultimap::view_for_keys`] with the single key instead to get a multimap.
+//!
+//! [`EntryValuesDrain`]: crate::list_ordered_multimap::EntryValuesDrain
+//! [`EntryValuesMut`]: crate::list_ordered_multimap::EntryValuesMut
+//!
+//! [`DeserializeOptions`] deserializes the [`as_pairs`] and [`as_map`] representations with a policy for duplicate
+//! keys and limits on the number of values, for example for untrusted input.
+//!
+//! # Examples
+//!
+//! ```
+//! use ordered_multimap::ListOrderedMultimap;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, Deserialize, PartialEq, Serialize)]
+//! struct Request {
+//!   #[serde(with = "ordered_multimap::serde::as_map")]
+//!   query: ListOrderedMultimap<String, String>,
+//!
+//!   #[serde(with = "ordered_multimap::serde::as_grouped")]
+//!   headers: ListOrderedMultimap<String, String>,
+//! }
+//!
+//! let json = r#"{"query":{"tag":"a","tag":"b"},"headers":{"accept":["text/html","text/plain"]}}"#;
+//! let request: Request = serde_json::from_str(json).unwrap();
+//! assert_eq!(request.query.entry_len("tag"), 2);
+//! assert_eq!(request.headers.entry_len("accept"), 2);
+//! assert_eq!(serde_json::to_string(&request).unwrap(), json);
+//! ```
+
+pub mod as_grouped;
+pub mod as_grouped_lossless;
+pub mod as_map;
+pub mod as_pairs;
+pub mod borrow_cow;
+
+use alloc::vec::Vec;
 use core::{
-  fmt::{self, Formatter},
+  fmt::{self, Debug, Formatter},
   hash::{BuildHasher, Hash},
   marker::PhantomData,
+  mem,
 };
 
 use serde::{
-  de::{Deserialize, Deserializer, SeqAccess, Visitor},
-  ser::{Serialize, SerializeSeq, Serializer},
+  de::{
+    Deserialize, Deserializer, EnumAccess, Error, Expected, IgnoredAny, MapAccess, SeqAccess,
+    Unexpected, VariantAccess, Visitor,
+  },
+  ser::{Serialize, SerializeStructVariant, Serializer},
 };
 
-use crate::ListOrderedMultimap;
+use crate::{
+  list_ordered_multimap::{EntryValues, Iter, KeyValues, ViewForKeys},
+  patch::{Operation, Patch},
+  ListOrderedMultimap,
+};
 
 impl<K, V, S> Serialize for ListOrderedMultimap<K, V, S>
 where
@@ -21,70 +86,932 @@ where
   where
     T: Serializer,
   {
-    let mut seq = serializer.serialize_seq(Some(self.values_len()))?;
+    as_pairs::serialize(self, serializer)
+  }
+}
+
+impl<'de, K, V, S> Deserialize<'de> for ListOrderedMultimap<K, V, S>
+where
+  K: Deserialize<'de> + Eq + Hash,
+  V: Deserialize<'de>,
+  S: BuildHasher + Default,
+{
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    as_pairs::deserialize(deserializer)
+  }
+
+  fn deserialize_in_place<D>(deserializer: D, place: &mut Self) -> Result<(), D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    as_pairs::deserialize_into(deserializer, place)
+  }
+}
+
+impl<K, V> Serialize for EntryValues<'_, K, V>
+where
+  V: Serialize,
+{
+  fn serialize<T>(&self, serializer: T) -> Result<T::Ok, T::Error>
+  where
+    T: Serializer,
+  {
+    serializer.collect_seq(self.clone())
+  }
+}
+
+impl<K, V> Serialize for Iter<'_, K, V>
+where
+  K: Serialize,
+  V: Serialize,
+{
+  fn serialize<T>(&self, serializer: T) -> Result<T::Ok, T::Error>
+  where
+    T: Serializer,
+  {
+    as_pairs::serialize_iter(self.len(), self.clone(), serializer)
+  }
+}
 
-    for (key, value) in self.into_iter() {
-      seq.serialize_element(&(key, value))?;
+impl<K, V, S> Serialize for KeyValues<'_, K, V, S>
+where
+  K: Eq + Hash + Serialize,
+  S: BuildHasher,
+  V: Serialize,
+{
+  fn serialize<T>(&self, serializer: T) -> Result<T::Ok, T::Error>
+  where
+    T: Serializer,
+  {
+    let len = self.clone().map(|(_, values)| values.len()).sum();
+    let pairs = self
+      .clone()
+      .flat_map(|(key, values)| values.map(move |value| (key, value)));
+    as_pairs::serialize_iter(len, pairs, serializer)
+  }
+}
+
+impl<K, V> Serialize for ViewForKeys<'_, K, V>
+where
+  K: Serialize,
+  V: Serialize,
+{
+  fn serialize<T>(&self, serializer: T) -> Result<T::Ok, T::Error>
+  where
+    T: Serializer,
+  {
+    as_pairs::serialize_iter(self.len(), self.clone(), serializer)
+  }
+}
+
+impl<K, V> Serialize for Operation<K, V>
+where
+  K: Serialize,
+  V: Serialize,
+{
+  fn serialize<T>(&self, serializer: T) -> Result<T::Ok, T::Error>
+  where
+    T: Serializer,
+  {
+    let variant = OperationVariant::of(self);
+    let fields = variant.fields();
+    let mut state = serializer.serialize_struct_variant(
+      "Operation",
+      variant as u32,
+      OPERATION_VARIANTS[variant as usize],
+      fields.len(),
+    )?;
+
+    match self {
+      Operation::Append { key, value } => {
+        state.serialize_field("key", key)?;
+        state.serialize_field("value", value)?;
+      }
+      Operation::Insert {
+        position,
+        key,
+        value,
+      }
+      | Operation::Replace {
+        position,
+        key,
+        value,
+      } => {
+        state.serialize_field("position", position)?;
+        state.serialize_field("key", key)?;
+        state.serialize_field("value", value)?;
+      }
+      Operation::Move { from, to } => {
+        state.serialize_field("from", from)?;
+        state.serialize_field("to", to)?;
+      }
+      Operation::Remove { key, nth } => {
+        state.serialize_field("key", key)?;
+        state.serialize_field("nth", nth)?;
+      }
     }
 
-    seq.end()
+    state.end()
   }
 }
 
-struct ListOrderedMultimapVisitor<K, V, S>(PhantomData<(K, V, S)>);
+impl<'de, K, V> Deserialize<'de> for Operation<K, V>
+where
+  K: Deserialize<'de>,
+  V: Deserialize<'de>,
+{
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    deserializer.deserialize_enum(
+      "Operation",
+      OPERATION_VARIANTS,
+      OperationVisitor(PhantomData),
+    )
+  }
+}
 
-impl<'de, K, V, S> Visitor<'de> for ListOrderedMultimapVisitor<K, V, S>
+impl<K, V> Serialize for Patch<K, V>
 where
-  K: Deserialize<'de> + Eq + Hash,
+  K: Serialize,
+  V: Serialize,
+{
+  fn serialize<T>(&self, serializer: T) -> Result<T::Ok, T::Error>
+  where
+    T: Serializer,
+  {
+    self.operations().serialize(serializer)
+  }
+}
+
+impl<'de, K, V> Deserialize<'de> for Patch<K, V>
+where
+  K: Deserialize<'de>,
+  V: Deserialize<'de>,
+{
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    Vec::deserialize(deserializer).map(Patch::from)
+  }
+}
+
+/// How to handle a key that occurs more than once during deserialization with [`DeserializeOptions`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum DuplicatePolicy {
+  /// Keeps all values of the key, as with [`ListOrderedMultimap::append`].
+  #[default]
+  KeepAll,
+
+  /// Keeps only the last value of the key, as with [`ListOrderedMultimap::insert`].
+  LastWins,
+
+  /// Keeps only the first value of the key and ignores the others.
+  FirstWins,
+
+  /// Fails with an error naming the key.
+  Reject,
+}
+
+/// Options for deserializing a multimap from untrusted input.
+///
+/// By default, all values are kept and there are no limits, which is the same as the [`Deserialize`] implementation of
+/// [`ListOrderedMultimap`]. The options apply to the [`as_pairs`] and [`as_map`] representations. The size hint of the
+/// input is never trusted beyond [`DeserializeOptions::max_pairs`] when allocating.
+///
+/// # Examples
+///
+/// ```
+/// use ordered_multimap::serde::{DeserializeOptions, DuplicatePolicy};
+/// use ordered_multimap::ListOrderedMultimap;
+///
+/// let options = DeserializeOptions::new()
+///   .duplicate_policy(DuplicatePolicy::Reject)
+///   .max_pairs(100);
+///
+/// let mut deserializer = serde_json::Deserializer::from_str(r#"{"a":1,"b":2}"#);
+/// let map: ListOrderedMultimap<String, u32> = options.deserialize_map(&mut deserializer).unwrap();
+/// assert_eq!(map.values_len(), 2);
+///
+/// let mut deserializer = serde_json::Deserializer::from_str(r#"{"a":1,"a":2}"#);
+/// let result: Result<ListOrderedMultimap<String, u32>, _> = options.deserialize_map(&mut deserializer);
+/// assert_eq!(result.unwrap_err().to_string(), r#"duplicate key "a" at line 1 column 13"#);
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct DeserializeOptions {
+  /// How to handle a key that occurs more than once.
+  duplicate_policy: DuplicatePolicy,
+
+  /// The maximum number of key-value pairs in the input.
+  max_pairs: Option<usize>,
+
+  /// The maximum number of values of a key in the multimap.
+  max_values_per_key: Option<usize>,
+}
+
+impl DeserializeOptions {
+  /// Creates options that keep all values and have no limits.
+  #[must_use]
+  pub const fn new() -> DeserializeOptions {
+    DeserializeOptions {
+      duplicate_policy: DuplicatePolicy::KeepAll,
+      max_pairs: None,
+      max_values_per_key: None,
+    }
+  }
+
+  /// Deserializes a multimap in the [`as_map`] representation with these options.
+  ///
+  /// # Errors
+  ///
+  /// Fails if the input is not a map, if [`DuplicatePolicy::Reject`] is used and a key occurs more than once or if a
+  /// limit is exceeded.
+  pub fn deserialize_map<'de, K, V, S, D>(
+    self,
+    deserializer: D,
+  ) -> Result<ListOrderedMultimap<K, V, S>, D::Error>
+  where
+    D: Deserializer<'de>,
+    K: Debug + Deserialize<'de> + Eq + Hash,
+    S: BuildHasher + Default,
+    V: Deserialize<'de>,
+  {
+    deserializer.deserialize_map(as_map::MapVisitor::new(self))
+  }
+
+  /// Deserializes a multimap in the [`as_pairs`] representation with these options.
+  ///
+  /// # Errors
+  ///
+  /// Fails if the input is not a sequence, if [`DuplicatePolicy::Reject`] is used and a key occurs more than once or if
+  /// a limit is exceeded.
+  pub fn deserialize_pairs<'de, K, V, S, D>(
+    self,
+    deserializer: D,
+  ) -> Result<ListOrderedMultimap<K, V, S>, D::Error>
+  where
+    D: Deserializer<'de>,
+    K: Debug + Deserialize<'de> + Eq + Hash,
+    S: BuildHasher + Default,
+    V: Deserialize<'de>,
+  {
+    deserializer.deserialize_seq(as_pairs::PairsVisitor::new(self))
+  }
+
+  /// Sets how to handle a key that occurs more than once.
+  #[must_use]
+  pub const fn duplicate_policy(mut self, duplicate_policy: DuplicatePolicy) -> DeserializeOptions {
+    self.duplicate_policy = duplicate_policy;
+    self
+  }
+
+  /// Sets the maximum number of key-value pairs in the input, including those that are ignored or replaced because of
+  /// the [`DuplicatePolicy`].
+  #[must_use]
+  pub const fn max_pairs(mut self, max_pairs: usize) -> DeserializeOptions {
+    self.max_pairs = Some(max_pairs);
+    self
+  }
+
+  /// Sets the maximum number of values of a key in the multimap.
+  #[must_use]
+  pub const fn max_values_per_key(mut self, max_values_per_key: usize) -> DeserializeOptions {
+    self.max_values_per_key = Some(max_values_per_key);
+    self
+  }
+}
+
+/// Inserts deserialized key-value pairs into a multimap.
+pub(crate) trait Insert<K, V, S> {
+  /// Returns the capacity to allocate for the given size hint of the input.
+  fn capacity(&self, size_hint: Option<usize>) -> usize;
+
+  /// Inserts the key-value pair, which is the `index`th one in the input, into the multimap.
+  fn insert<E>(
+    &self,
+    map: &mut ListOrderedMultimap<K, V, S>,
+    index: usize,
+    key: K,
+    value: V,
+  ) -> Result<(), E>
+  where
+    E: Error;
+}
+
+/// Appends all key-value pairs, which is what the [`Deserialize`] implementation of [`ListOrderedMultimap`] does.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Append;
+
+impl<K, V, S> Insert<K, V, S> for Append
+where
+  K: Eq + Hash,
+  S: BuildHasher,
+{
+  fn capacity(&self, size_hint: Option<usize>) -> usize {
+    size_hint.unwrap_or_default()
+  }
+
+  fn insert<E>(
+    &self,
+    map: &mut ListOrderedMultimap<K, V, S>,
+    _: usize,
+    key: K,
+    value: V,
+  ) -> Result<(), E>
+  where
+    E: Error,
+  {
+    let _ = map.append(key, value);
+    Ok(())
+  }
+}
+
+impl<K, V, S> Insert<K, V, S> for DeserializeOptions
+where
+  K: Debug + Eq + Hash,
+  S: BuildHasher,
+{
+  fn capacity(&self, size_hint: Option<usize>) -> usize {
+    let size_hint = size_hint.unwrap_or_default();
+    self
+      .max_pairs
+      .map_or(size_hint, |max_pairs| size_hint.min(max_pairs))
+  }
+
+  fn insert<E>(
+    &self,
+    map: &mut ListOrderedMultimap<K, V, S>,
+    index: usize,
+    key: K,
+    value: V,
+  ) -> Result<(), E>
+  where
+    E: Error,
+  {
+    if let Some(max_pairs) = self.max_pairs {
+      if index >= max_pairs {
+        return Err(E::custom(format_args!(
+          "more than {max_pairs} key-value pairs"
+        )));
+      }
+    }
+
+    let length = map.entry_len(&key);
+
+    if length > 0 {
+      match self.duplicate_policy {
+        DuplicatePolicy::KeepAll => {}
+        DuplicatePolicy::LastWins => {
+          mem::drop(map.insert(key, value));
+          return Ok(());
+        }
+        DuplicatePolicy::FirstWins => return Ok(()),
+        DuplicatePolicy::Reject => return Err(E::custom(format_args!("duplicate key {key:?}"))),
+      }
+    }
+
+    if let Some(max_values_per_key) = self.max_values_per_key {
+      if length >= max_values_per_key {
+        return Err(E::custom(format_args!(
+          "more than {max_values_per_key} values for key {key:?}"
+        )));
+      }
+    }
+
+    let _ = map.append(key, value);
+    Ok(())
+  }
+}
+
+/// The names of the variants of [`Operation`], in the order of their indices.
+const OPERATION_VARIANTS: &[&str] = &["Append", "Insert", "Move", "Remove", "Replace"];
+
+/// The variants of [`Operation`], used to serialize and deserialize them.
+#[derive(Clone, Copy)]
+enum OperationVariant {
+  Append,
+  Insert,
+  Move,
+  Remove,
+  Replace,
+}
+
+impl OperationVariant {
+  fn of<K, V>(operation: &Operation<K, V>) -> OperationVariant {
+    match operation {
+      Operation::Append { .. } => OperationVariant::Append,
+      Operation::Insert { .. } => OperationVariant::Insert,
+      Operation::Move { .. } => OperationVariant::Move,
+      Operation::Remove { .. } => OperationVariant::Remove,
+      Operation::Replace { .. } => OperationVariant::Replace,
+    }
+  }
+
+  /// Returns the names of the fields of the variant, in the order in which they are serialized.
+  fn fields(self) -> &'static [&'static str] {
+    match self {
+      OperationVariant::Append => &["key", "value"],
+      OperationVariant::Insert | OperationVariant::Replace => &["position", "key", "value"],
+      OperationVariant::Move => &["from", "to"],
+      OperationVariant::Remove => &["key", "nth"],
+    }
+  }
+}
+
+impl<'de> Deserialize<'de> for OperationVariant {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    deserializer.deserialize_identifier(OperationVariantVisitor)
+  }
+}
+
+struct OperationVariantVisitor;
+
+impl Visitor<'_> for OperationVariantVisitor {
+  type Value = OperationVariant;
+
+  fn expecting(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    write!(formatter, "a variant identifier")
+  }
+
+  fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+  where
+    E: Error,
+  {
+    match value {
+      0 => Ok(OperationVariant::Append),
+      1 => Ok(OperationVariant::Insert),
+      2 => Ok(OperationVariant::Move),
+      3 => Ok(OperationVariant::Remove),
+      4 => Ok(OperationVariant::Replace),
+      _ => Err(E::invalid_value(
+        Unexpected::Unsigned(value),
+        &"a variant index less than 5",
+      )),
+    }
+  }
+
+  fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+  where
+    E: Error,
+  {
+    match value {
+      "Append" => Ok(OperationVariant::Append),
+      "Insert" => Ok(OperationVariant::Insert),
+      "Move" => Ok(OperationVariant::Move),
+      "Remove" => Ok(OperationVariant::Remove),
+      "Replace" => Ok(OperationVariant::Replace),
+      _ => Err(E::unknown_variant(value, OPERATION_VARIANTS)),
+    }
+  }
+}
+
+/// A field of a variant of [`Operation`]. Fields that no variant has are ignored.
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum OperationField {
+  From,
+  Key,
+  Nth,
+  Position,
+  To,
+  Value,
+  Other,
+}
+
+impl OperationField {
+  fn name(self) -> &'static str {
+    match self {
+      OperationField::From => "from",
+      OperationField::Key => "key",
+      OperationField::Nth => "nth",
+      OperationField::Position => "position",
+      OperationField::To => "to",
+      OperationField::Value => "value",
+      OperationField::Other => "",
+    }
+  }
+}
+
+impl<'de> Deserialize<'de> for OperationField {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    deserializer.deserialize_identifier(OperationFieldVisitor)
+  }
+}
+
+struct OperationFieldVisitor;
+
+impl Visitor<'_> for OperationFieldVisitor {
+  type Value = OperationField;
+
+  fn expecting(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    write!(formatter, "a field identifier")
+  }
+
+  fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+  where
+    E: Error,
+  {
+    Ok(match value {
+      "from" => OperationField::From,
+      "key" => OperationField::Key,
+      "nth" => OperationField::Nth,
+      "position" => OperationField::Position,
+      "to" => OperationField::To,
+      "value" => OperationField::Value,
+      _ => OperationField::Other,
+    })
+  }
+}
+
+struct OperationVisitor<K, V>(PhantomData<(K, V)>);
+
+impl<'de, K, V> Visitor<'de> for OperationVisitor<K, V>
+where
+  K: Deserialize<'de>,
+  V: Deserialize<'de>,
+{
+  type Value = Operation<K, V>;
+
+  fn expecting(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    write!(formatter, "a patch operation")
+  }
+
+  fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+  where
+    A: EnumAccess<'de>,
+  {
+    let (variant, access) = data.variant::<OperationVariant>()?;
+    access.struct_variant(
+      variant.fields(),
+      OperationFieldsVisitor {
+        variant,
+        marker: PhantomData,
+      },
+    )
+  }
+}
+
+/// Deserializes the fields of the variant of [`Operation`].
+struct OperationFieldsVisitor<K, V> {
+  variant: OperationVariant,
+  marker: PhantomData<(K, V)>,
+}
+
+impl<'de, K, V> Visitor<'de> for OperationFieldsVisitor<K, V>
+where
+  K: Deserialize<'de>,
   V: Deserialize<'de>,
-  S: BuildHasher + Default,
 {
-  type Value = ListOrderedMultimap<K, V, S>;
+  type Value = Operation<K, V>;
 
   fn expecting(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
-    write!(formatter, "a sequence")
+    write!(
+      formatter,
+      "struct variant Operation::{}",
+      OPERATION_VARIANTS[self.variant as usize]
+    )
   }
 
   fn visit_seq<A>(self, mut access: A) -> Result<Self::Value, A::Error>
   where
     A: SeqAccess<'de>,
   {
-    let mut map = ListOrderedMultimap::with_capacity_and_hasher(
-      access.size_hint().unwrap_or_default(),
-      access.size_hint().unwrap_or_default(),
-      S::default(),
-    );
+    let mut next = OperationElements {
+      access: &mut access,
+      index: 0,
+      expected: &self,
+    };
+
+    Ok(match self.variant {
+      OperationVariant::Append => Operation::Append {
+        key: next.element()?,
+        value: next.element()?,
+      },
+      OperationVariant::Insert => Operation::Insert {
+        position: next.element()?,
+        key: next.element()?,
+        value: next.element()?,
+      },
+      OperationVariant::Move => Operation::Move {
+        from: next.element()?,
+        to: next.element()?,
+      },
+      OperationVariant::Remove => Operation::Remove {
+        key: next.element()?,
+        nth: next.element()?,
+      },
+      OperationVariant::Replace => Operation::Replace {
+        position: next.element()?,
+        key: next.element()?,
+        value: next.element()?,
+      },
+    })
+  }
 
-    while let Some((key, value)) = access.next_element()? {
-      let _ = map.append(key, value);
+  fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+  where
+    A: MapAccess<'de>,
+  {
+    let fields = self.variant.fields();
+    let mut from = None;
+    let mut key = None;
+    let mut nth = None;
+    let mut position = None;
+    let mut to = None;
+    let mut value = None;
+
+    while let Some(field) = access.next_key::<OperationField>()? {
+      if !fields.contains(&field.name()) {
+        let _ = access.next_value::<IgnoredAny>()?;
+        continue;
+      }
+
+      let duplicate = match field {
+        OperationField::From => from.replace(access.next_value()?).is_some(),
+        OperationField::Key => key.replace(access.next_value()?).is_some(),
+        OperationField::Nth => nth.replace(access.next_value()?).is_some(),
+        OperationField::Position => position.replace(access.next_value()?).is_some(),
+        OperationField::To => to.replace(access.next_value()?).is_some(),
+        OperationField::Value => value.replace(access.next_value()?).is_some(),
+        OperationField::Other => unreachable!("expected a field of the variant"),
+      };
+
+      if duplicate {
+        return Err(Error::duplicate_field(field.name()));
+      }
     }
 
-    Ok(map)
+    Ok(match self.variant {
+      OperationVariant::Append => Operation::Append {
+        key: required(key, "key")?,
+        value: required(value, "value")?,
+      },
+      OperationVariant::Insert => Operation::Insert {
+        position: required(position, "position")?,
+        key: required(key, "key")?,
+        value: required(value, "value")?,
+      },
+      OperationVariant::Move => Operation::Move {
+        from: required(from, "from")?,
+        to: required(to, "to")?,
+      },
+      OperationVariant::Remove => Operation::Remove {
+        key: required(key, "key")?,
+        nth: required(nth, "nth")?,
+      },
+      OperationVariant::Replace => Operation::Replace {
+        position: required(position, "position")?,
+        key: required(key, "key")?,
+        value: required(value, "value")?,
+      },
+    })
   }
 }
 
-impl<'de, K, V, S> Deserialize<'de> for ListOrderedMultimap<K, V, S>
+/// Deserializes the fields of a variant of [`Operation`] from a sequence, in order.
+struct OperationElements<'access, 'expected, A> {
+  access: &'access mut A,
+  index: usize,
+  expected: &'expected dyn Expected,
+}
+
+impl<'de, A> OperationElements<'_, '_, A>
 where
-  K: Deserialize<'de> + Eq + Hash,
-  V: Deserialize<'de>,
-  S: BuildHasher + Default,
+  A: SeqAccess<'de>,
 {
-  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  fn element<T>(&mut self) -> Result<T, A::Error>
   where
-    D: Deserializer<'de>,
+    T: Deserialize<'de>,
   {
-    deserializer.deserialize_seq(ListOrderedMultimapVisitor(PhantomData))
+    let element = self
+      .access
+      .next_element()?
+      .ok_or_else(|| Error::invalid_length(self.index, self.expected))?;
+    self.index += 1;
+    Ok(element)
   }
 }
 
+/// Returns the value of a field, or an error if the field was missing.
+fn required<T, E>(field: Option<T>, name: &'static str) -> Result<T, E>
+where
+  E: Error,
+{
+  field.ok_or_else(|| Error::missing_field(name))
+}
+
 #[allow(unused_results)]
 #[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
 mod test {
-  use coverage_helper::test;
-  use serde_test::{assert_de_tokens_error, assert_tokens, Token};
+  use std::{
+    collections::hash_map::{DefaultHasher, RandomState},
+    hash::Hasher,
+  };
+
+  use serde::{Deserialize, Serialize};
+  use serde_test::{assert_de_tokens_error, assert_ser_tokens, assert_tokens, Token};
 
   use super::*;
 
+  fn deserialize_json(
+    options: DeserializeOptions,
+    json: &str,
+  ) -> Result<ListOrderedMultimap<char, u32>, serde_json::Error> {
+    options.deserialize_map(&mut serde_json::Deserializer::from_str(json))
+  }
+
+  #[test]
+  fn test_deserialize_options_duplicate_policy() {
+    let json = r#"{"a":1,"b":2,"a":3}"#;
+    let pairs = |options| {
+      deserialize_json(options, json)
+        .unwrap()
+        .into_iter()
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+      pairs(DeserializeOptions::new()),
+      [('a', 1), ('b', 2), ('a', 3)]
+    );
+    assert_eq!(
+      pairs(DeserializeOptions::new().duplicate_policy(DuplicatePolicy::LastWins)),
+      [('b', 2), ('a', 3)]
+    );
+    assert_eq!(
+      pairs(DeserializeOptions::new().duplicate_policy(DuplicatePolicy::FirstWins)),
+      [('a', 1), ('b', 2)]
+    );
+    assert_eq!(
+      deserialize_json(
+        DeserializeOptions::new().duplicate_policy(DuplicatePolicy::Reject),
+        json
+      )
+      .unwrap_err()
+      .to_string(),
+      "duplicate key 'a' at line 1 column 19"
+    );
+  }
+
+  #[test]
+  fn test_deserialize_options_limits() {
+    let json = r#"{"a":1,"b":2,"a":3}"#;
+
+    assert!(deserialize_json(DeserializeOptions::new().max_pairs(3), json).is_ok());
+    assert_eq!(
+      deserialize_json(DeserializeOptions::new().max_pairs(2), json)
+        .unwrap_err()
+        .to_string(),
+      "more than 2 key-value pairs at line 1 column 19"
+    );
+
+    // Ignored key-value pairs count towards the limit of pairs, but not towards the limit of values per key.
+    let options = DeserializeOptions::new()
+      .duplicate_policy(DuplicatePolicy::FirstWins)
+      .max_values_per_key(1);
+    assert!(deserialize_json(options, json).is_ok());
+    assert!(deserialize_json(options.max_pairs(2), json).is_err());
+
+    assert_eq!(
+      deserialize_json(DeserializeOptions::new().max_values_per_key(1), json)
+        .unwrap_err()
+        .to_string(),
+      "more than 1 values for key 'a' at line 1 column 19"
+    );
+  }
+
+  #[test]
+  fn test_deserialize_options_pairs() {
+    let options = DeserializeOptions::new().duplicate_policy(DuplicatePolicy::LastWins);
+    let mut deserializer = serde_json::Deserializer::from_str(r#"[["a",1],["b",2],["a",3]]"#);
+    let map: ListOrderedMultimap<char, u32> = options.deserialize_pairs(&mut deserializer).unwrap();
+
+    assert_eq!(map.into_iter().collect::<Vec<_>>(), [('b', 2), ('a', 3)]);
+  }
+
+  #[test]
+  fn test_deserialize_options_size_hint() {
+    let map: ListOrderedMultimap<char, u32> = DeserializeOptions::new()
+      .max_pairs(2)
+      .deserialize_pairs(&mut serde_json::Deserializer::from_str("[]"))
+      .unwrap();
+    assert!(map.is_empty());
+
+    assert_eq!(
+      <DeserializeOptions as Insert<char, u32, RandomState>>::capacity(
+        &DeserializeOptions::new().max_pairs(2),
+        Some(usize::MAX)
+      ),
+      2
+    );
+  }
+
+  #[derive(Debug, Deserialize, PartialEq, Serialize)]
+  struct Borrowed<'a> {
+    #[serde(borrow)]
+    pairs: ListOrderedMultimap<&'a str, &'a str>,
+
+    #[serde(borrow, with = "as_map")]
+    map: ListOrderedMultimap<&'a str, &'a str>,
+
+    #[serde(borrow, with = "as_grouped")]
+    grouped: ListOrderedMultimap<&'a str, &'a str>,
+
+    #[serde(borrow, with = "as_grouped_lossless")]
+    grouped_lossless: ListOrderedMultimap<&'a str, &'a [u8]>,
+  }
+
+  fn borrowed() -> Borrowed<'static> {
+    let pairs: ListOrderedMultimap<_, _> =
+      [("a", "1"), ("b", "2"), ("a", "3")].into_iter().collect();
+
+    Borrowed {
+      map: pairs.clone(),
+      grouped: [("a", "1"), ("a", "3"), ("b", "2")].into_iter().collect(),
+      grouped_lossless: pairs
+        .iter()
+        .map(|(&key, value)| (key, value.as_bytes()))
+        .collect(),
+      pairs,
+    }
+  }
+
+  #[test]
+  fn test_borrowed_json() {
+    let json = serde_json::to_string(&Borrowed {
+      grouped_lossless: ListOrderedMultimap::new(),
+      ..borrowed()
+    })
+    .unwrap();
+    let deserialized: Borrowed<'_> = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(deserialized.pairs, borrowed().pairs);
+    assert_eq!(deserialized.map, borrowed().map);
+    assert_eq!(deserialized.grouped, borrowed().grouped);
+  }
+
+  #[test]
+  fn test_borrowed_postcard() {
+    let bytes = postcard::to_allocvec(&borrowed()).unwrap();
+    let deserialized: Borrowed<'_> = postcard::from_bytes(&bytes).unwrap();
+
+    assert_eq!(deserialized, borrowed());
+
+    // The keys and values point into the input.
+    let range = bytes.as_ptr_range();
+    let (key, value) = deserialized.grouped_lossless.front().unwrap();
+    assert!(range.contains(&key.as_ptr()));
+    assert!(range.contains(&value.as_ptr()));
+  }
+
+  #[test]
+  fn test_de_in_place() {
+    #[derive(Default)]
+    struct SeededState(u64);
+
+    impl BuildHasher for SeededState {
+      type Hasher = DefaultHasher;
+
+      fn build_hasher(&self) -> Self::Hasher {
+        let mut hasher = DefaultHasher::new();
+        hasher.write_u64(self.0);
+        hasher
+      }
+    }
+
+    let mut map = ListOrderedMultimap::with_capacity_and_hasher(16, 64, SeededState(7));
+    map.append('x', 0);
+    let (keys_capacity, values_capacity) = (map.keys_capacity(), map.values_capacity());
+
+    let mut deserializer = serde_json::Deserializer::from_str(r#"[["a",1],["b",2],["a",3]]"#);
+    ListOrderedMultimap::deserialize_in_place(&mut deserializer, &mut map).unwrap();
+
+    assert_eq!(
+      map.iter().collect::<Vec<_>>(),
+      [(&'a', &1), (&'b', &2), (&'a', &3)]
+    );
+    assert_eq!(map.get_all(&'a').collect::<Vec<_>>(), [&1, &3]);
+    assert_eq!(map.hasher().0, 7);
+    assert_eq!(map.keys_capacity(), keys_capacity);
+    assert_eq!(map.values_capacity(), values_capacity);
+
+    // The multimap is unchanged if the input is not a sequence.
+    let mut deserializer = serde_json::Deserializer::from_str("{}");
+    assert!(ListOrderedMultimap::deserialize_in_place(&mut deserializer, &mut map).is_err());
+    assert_eq!(map.values_len(), 3);
+  }
+
   #[test]
   fn test_de_error() {
     assert_de_tokens_error::<ListOrderedMultimap<char, u32>>(
@@ -132,4 +1059,81 @@ mod test {
       ],
     );
   }
+
+  #[test]
+  fn test_ser_entry_values() {
+    let mut map = ListOrderedMultimap::new();
+    map.append('a', 1);
+    map.append('b', 2);
+    map.append('a', 3);
+
+    let json = serde_json::to_string(&map.get_all(&'a')).unwrap();
+    assert_eq!(json, "[1,3]");
+
+    let json = serde_json::to_string(&map.get_all(&'c')).unwrap();
+    assert_eq!(json, "[]");
+
+    let json = serde_json::to_string(&map.remove_all(&'a').iter()).unwrap();
+    assert_eq!(json, "[1,3]");
+  }
+
+  #[test]
+  fn test_ser_iter() {
+    let mut map = ListOrderedMultimap::new();
+    map.append('a', 1);
+    map.append('b', 2);
+    map.append('a', 3);
+
+    let mut iter = map.iter();
+    iter.next();
+    let json = serde_json::to_string(&iter).unwrap();
+    assert_eq!(json, r#"[["b",2],["a",3]]"#);
+  }
+
+  #[test]
+  fn test_ser_key_values() {
+    let mut map = ListOrderedMultimap::new();
+    map.append('a', 1);
+    map.append('b', 2);
+    map.append('a', 3);
+
+    let json = serde_json::to_string(&map.pairs()).unwrap();
+    assert_eq!(json, r#"[["a",1],["a",3],["b",2]]"#);
+
+    let mut pairs = map.pairs();
+    pairs.next();
+    let bytes = postcard::to_allocvec(&pairs).unwrap();
+    let deserialized = postcard::from_bytes::<ListOrderedMultimap<char, i32>>(&bytes).unwrap();
+    assert_eq!(deserialized.iter().collect::<Vec<_>>(), [(&'b', &2)]);
+  }
+
+  #[test]
+  fn test_ser_view_for_keys() {
+    let mut map = ListOrderedMultimap::new();
+    map.append('a', 1);
+    map.append('b', 2);
+    map.append('c', 3);
+    map.append('a', 4);
+
+    let view = map.view_for_keys(&['c', 'a']);
+    assert_ser_tokens(
+      &view,
+      &[
+        Token::Seq { len: Some(3) },
+        Token::Tuple { len: 2 },
+        Token::Char('a'),
+        Token::I32(1),
+        Token::TupleEnd,
+        Token::Tuple { len: 2 },
+        Token::Char('c'),
+        Token::I32(3),
+        Token::TupleEnd,
+        Token::Tuple { len: 2 },
+        Token::Char('a'),
+        Token::I32(4),
+        Token::TupleEnd,
+        Token::SeqEnd,
+      ],
+    );
+  }
 }
@@ -0,0 +1,296 @@
+//! Serializes a multimap as a map from each key to a sequence of its values, for example `{"a": [1, 2], "b": [3]}`.
+//!
+//! The keys are in the order of [`ListOrderedMultimap::keys`] and the values of each key are in their order for that
+//! key. The global interleaving of the values of different keys is lost in this representation: deserializing a
+//! serialized multimap yields a multimap in which all values of a key are adjacent. See
+//! [`as_grouped_lossless`](super::as_grouped_lossless) for a grouped representation that preserves the interleaving.
+//!
+//! When deserializing, the values of a key that occurs more than once are appended to its values, and keys with an
+//! empty sequence of values are skipped, since a multimap cannot contain a key without values.
+//!
+//! Use this module with `#[serde(with = "ordered_multimap::serde::as_grouped")]`.
+//!
+//! # Examples
+//!
+//! ```
+//! use ordered_multimap::ListOrderedMultimap;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, Deserialize, PartialEq, Serialize)]
+//! struct Document {
+//!   #[serde(with = "ordered_multimap::serde::as_grouped")]
+//!   tags: ListOrderedMultimap<String, u32>,
+//! }
+//!
+//! let mut tags = ListOrderedMultimap::new();
+//! tags.append("a".to_string(), 1);
+//! tags.append("b".to_string(), 3);
+//! tags.append("a".to_string(), 2);
+//!
+//! let json = serde_json::to_string(&Document { tags }).unwrap();
+//! assert_eq!(json, r#"{"tags":{"a":[1,2],"b":[3]}}"#);
+//!
+//! let document: Document = serde_json::from_str(&json).unwrap();
+//! let mut iter = document.tags.iter().map(|(key, value)| (key.as_str(), *value));
+//! assert_eq!(iter.next(), Some(("a", 1)));
+//! assert_eq!(iter.next(), Some(("a", 2)));
+//! assert_eq!(iter.next(), Some(("b", 3)));
+//! assert_eq!(iter.next(), None);
+//! ```
+
+use core::{
+  fmt::{self, Formatter},
+  hash::{BuildHasher, Hash},
+  marker::PhantomData,
+};
+
+use serde::{
+  de::{Deserialize, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor},
+  ser::{Serialize, SerializeMap, Serializer},
+};
+
+use crate::{
+  list_ordered_multimap::{Entry, EntryValues},
+  ListOrderedMultimap,
+};
+
+/// Serializes the multimap as a map from each key to a sequence of its values.
+pub fn serialize<K, V, S, T>(
+  map: &ListOrderedMultimap<K, V, S>,
+  serializer: T,
+) -> Result<T::Ok, T::Error>
+where
+  K: Eq + Hash + Serialize,
+  S: BuildHasher,
+  T: Serializer,
+  V: Serialize,
+{
+  let mut serialize_map = serializer.serialize_map(Some(map.keys_len()))?;
+
+  for (key, values) in map.pairs() {
+    serialize_map.serialize_entry(key, &Values(values))?;
+  }
+
+  serialize_map.end()
+}
+
+/// Deserializes a multimap from a map from each key to a sequence of its values.
+pub fn deserialize<'de, K, V, S, D>(
+  deserializer: D,
+) -> Result<ListOrderedMultimap<K, V, S>, D::Error>
+where
+  D: Deserializer<'de>,
+  K: Deserialize<'de> + Eq + Hash,
+  S: BuildHasher + Default,
+  V: Deserialize<'de>,
+{
+  deserializer.deserialize_map(GroupedVisitor(PhantomData))
+}
+
+/// Serializes the values of a key as a sequence.
+pub(super) struct Values<'map, K, V>(pub(super) EntryValues<'map, K, V>);
+
+impl<K, V> Serialize for Values<'_, K, V>
+where
+  V: Serialize,
+{
+  fn serialize<T>(&self, serializer: T) -> Result<T::Ok, T::Error>
+  where
+    T: Serializer,
+  {
+    serializer.collect_seq(self.0.clone())
+  }
+}
+
+struct GroupedVisitor<K, V, S>(PhantomData<(K, V, S)>);
+
+impl<'de, K, V, S> Visitor<'de> for GroupedVisitor<K, V, S>
+where
+  K: Deserialize<'de> + Eq + Hash,
+  V: Deserialize<'de>,
+  S: BuildHasher + Default,
+{
+  type Value = ListOrderedMultimap<K, V, S>;
+
+  fn expecting(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    write!(formatter, "a map of sequences")
+  }
+
+  fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+  where
+    A: MapAccess<'de>,
+  {
+    let mut map = ListOrderedMultimap::with_capacity_and_hasher(
+      access.size_hint().unwrap_or_default(),
+      access.size_hint().unwrap_or_default(),
+      S::default(),
+    );
+
+    while let Some(key) = access.next_key()? {
+      access.next_value_seed(AppendValues { key, map: &mut map })?;
+    }
+
+    Ok(map)
+  }
+}
+
+/// Deserializes a sequence of values and appends them to the given key.
+struct AppendValues<'map, K, V, S> {
+  key: K,
+  map: &'map mut ListOrderedMultimap<K, V, S>,
+}
+
+impl<'de, K, V, S> DeserializeSeed<'de> for AppendValues<'_, K, V, S>
+where
+  K: Eq + Hash,
+  S: BuildHasher,
+  V: Deserialize<'de>,
+{
+  type Value = ();
+
+  fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    deserializer.deserialize_seq(self)
+  }
+}
+
+impl<'de, K, V, S> Visitor<'de> for AppendValues<'_, K, V, S>
+where
+  K: Eq + Hash,
+  S: BuildHasher,
+  V: Deserialize<'de>,
+{
+  type Value = ();
+
+  fn expecting(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    write!(formatter, "a sequence")
+  }
+
+  fn visit_seq<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+  where
+    A: SeqAccess<'de>,
+  {
+    let Some(value) = access.next_element()? else {
+      return Ok(());
+    };
+
+    let mut entry = match self.map.entry(self.key) {
+      Entry::Occupied(mut entry) => {
+        entry.append(value);
+        entry
+      }
+      Entry::Vacant(entry) => entry.insert_entry(value),
+    };
+
+    while let Some(value) = access.next_element()? {
+      entry.append(value);
+    }
+
+    Ok(())
+  }
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use serde::{Deserialize, Serialize};
+  use serde_test::{assert_de_tokens, assert_de_tokens_error, assert_ser_tokens, Token};
+
+  use super::*;
+
+  #[derive(Debug, Deserialize, PartialEq, Serialize)]
+  struct Wrapper(#[serde(with = "super")] ListOrderedMultimap<char, u32>);
+
+  #[test]
+  fn test_de() {
+    let mut map = ListOrderedMultimap::new();
+    map.append('b', 20);
+    map.append('b', 30);
+    map.append('a', 10);
+    map.append('b', 40);
+
+    assert_de_tokens(
+      &Wrapper(map),
+      &[
+        Token::NewtypeStruct { name: "Wrapper" },
+        Token::Map { len: Some(4) },
+        Token::Char('b'),
+        Token::Seq { len: Some(2) },
+        Token::U32(20),
+        Token::U32(30),
+        Token::SeqEnd,
+        Token::Char('c'),
+        Token::Seq { len: Some(0) },
+        Token::SeqEnd,
+        Token::Char('a'),
+        Token::Seq { len: Some(1) },
+        Token::U32(10),
+        Token::SeqEnd,
+        Token::Char('b'),
+        Token::Seq { len: Some(1) },
+        Token::U32(40),
+        Token::SeqEnd,
+        Token::MapEnd,
+      ],
+    );
+  }
+
+  #[test]
+  fn test_de_error() {
+    assert_de_tokens_error::<Wrapper>(
+      &[
+        Token::NewtypeStruct { name: "Wrapper" },
+        Token::Map { len: Some(1) },
+        Token::Char('a'),
+        Token::U32(10),
+      ],
+      "invalid type: integer `10`, expected a sequence",
+    );
+  }
+
+  #[test]
+  fn test_json() {
+    let mut map = ListOrderedMultimap::new();
+    map.append('b', 20);
+    map.append('a', 10);
+    map.append('b', 30);
+
+    let json = serde_json::to_string(&Wrapper(map)).unwrap();
+    assert_eq!(json, r#"{"b":[20,30],"a":[10]}"#);
+
+    let Wrapper(map) = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+      map.iter().collect::<Vec<_>>(),
+      [(&'b', &20), (&'b', &30), (&'a', &10)]
+    );
+  }
+
+  #[test]
+  fn test_ser() {
+    let mut map = ListOrderedMultimap::new();
+    map.append('b', 20);
+    map.append('a', 10);
+    map.append('b', 30);
+
+    assert_ser_tokens(
+      &Wrapper(map),
+      &[
+        Token::NewtypeStruct { name: "Wrapper" },
+        Token::Map { len: Some(2) },
+        Token::Char('b'),
+        Token::Seq { len: Some(2) },
+        Token::U32(20),
+        Token::U32(30),
+        Token::SeqEnd,
+        Token::Char('a'),
+        Token::Seq { len: Some(1) },
+        Token::U32(10),
+        Token::SeqEnd,
+        Token::MapEnd,
+      ],
+    );
+  }
+}
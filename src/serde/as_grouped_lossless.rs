@@ -0,0 +1,500 @@
+//! Serializes a multimap grouped by key like [`as_grouped`](super::as_grouped), together with the key ordinals that
+//! restore the global interleaving of the values.
+//!
+//! The multimap is serialized as a struct with two fields:
+//!
+//!  - `groups` is a map from each key to a sequence of its values, with the keys in the order of
+//!    [`ListOrderedMultimap::keys`].
+//!  - `order` is a sequence with the position of the key in `groups` for each value in the global order.
+//!
+//! For example, the multimap `[("a", 1), ("b", 3), ("a", 2)]` is serialized to JSON as
+//! `{"groups":{"a":[1,2],"b":[3]},"order":[0,1,0]}`. Unlike with [`as_grouped`](super::as_grouped),
+//! `deserialize(serialize(map)) == map`.
+//!
+//! Deserializing fails if `groups` contains a key more than once or if `order` does not contain the position of each
+//! key exactly as often as the key has values.
+//!
+//! Use this module with `#[serde(with = "ordered_multimap::serde::as_grouped_lossless")]`.
+//!
+//! # Examples
+//!
+//! ```
+//! use ordered_multimap::ListOrderedMultimap;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, Deserialize, PartialEq, Serialize)]
+//! struct Document {
+//!   #[serde(with = "ordered_multimap::serde::as_grouped_lossless")]
+//!   tags: ListOrderedMultimap<String, u32>,
+//! }
+//!
+//! let mut tags = ListOrderedMultimap::new();
+//! tags.append("a".to_string(), 1);
+//! tags.append("b".to_string(), 3);
+//! tags.append("a".to_string(), 2);
+//! let document = Document { tags };
+//!
+//! let json = serde_json::to_string(&document).unwrap();
+//! assert_eq!(json, r#"{"tags":{"groups":{"a":[1,2],"b":[3]},"order":[0,1,0]}}"#);
+//! assert_eq!(serde_json::from_str::<Document>(&json).unwrap(), document);
+//! ```
+
+use alloc::vec::Vec;
+use core::{
+  fmt::{self, Formatter},
+  hash::{BuildHasher, Hash},
+  marker::PhantomData,
+};
+
+use serde::{
+  de::{Deserializer, Error, MapAccess, SeqAccess, Unexpected, Visitor},
+  ser::{Serialize, SerializeStruct, Serializer},
+  Deserialize,
+};
+
+use super::as_grouped::Values;
+use crate::{list_ordered_multimap::Entry, ListOrderedMultimap};
+
+/// The fields of the serialized struct.
+const FIELDS: &[&str] = &["groups", "order"];
+
+/// Serializes the multimap grouped by key together with the key ordinals of the values.
+pub fn serialize<K, V, S, T>(
+  map: &ListOrderedMultimap<K, V, S>,
+  serializer: T,
+) -> Result<T::Ok, T::Error>
+where
+  K: Eq + Hash + Serialize,
+  S: BuildHasher,
+  T: Serializer,
+  V: Serialize,
+{
+  let mut serialize_struct = serializer.serialize_struct("ListOrderedMultimap", FIELDS.len())?;
+  serialize_struct.serialize_field("groups", &Groups(map))?;
+  serialize_struct.serialize_field("order", &Order(map))?;
+  serialize_struct.end()
+}
+
+/// Deserializes a multimap grouped by key together with the key ordinals of the values.
+pub fn deserialize<'de, K, V, S, D>(
+  deserializer: D,
+) -> Result<ListOrderedMultimap<K, V, S>, D::Error>
+where
+  D: Deserializer<'de>,
+  K: Deserialize<'de> + Eq + Hash,
+  S: BuildHasher + Default,
+  V: Deserialize<'de>,
+{
+  deserializer.deserialize_struct(
+    "ListOrderedMultimap",
+    FIELDS,
+    GroupedLosslessVisitor(PhantomData),
+  )
+}
+
+/// Serializes the keys of the multimap with their values.
+struct Groups<'map, K, V, S>(&'map ListOrderedMultimap<K, V, S>);
+
+impl<K, V, S> Serialize for Groups<'_, K, V, S>
+where
+  K: Eq + Hash + Serialize,
+  S: BuildHasher,
+  V: Serialize,
+{
+  fn serialize<T>(&self, serializer: T) -> Result<T::Ok, T::Error>
+  where
+    T: Serializer,
+  {
+    serializer.collect_map(self.0.pairs().map(|(key, values)| (key, Values(values))))
+  }
+}
+
+/// Serializes the key ordinals of the values of the multimap.
+struct Order<'map, K, V, S>(&'map ListOrderedMultimap<K, V, S>);
+
+impl<K, V, S> Serialize for Order<'_, K, V, S> {
+  fn serialize<T>(&self, serializer: T) -> Result<T::Ok, T::Error>
+  where
+    T: Serializer,
+  {
+    serializer.collect_seq(self.0.key_ordinals())
+  }
+}
+
+/// A field of the serialized struct.
+enum Field {
+  Groups,
+  Order,
+}
+
+impl<'de> Deserialize<'de> for Field {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    deserializer.deserialize_identifier(FieldVisitor)
+  }
+}
+
+struct FieldVisitor;
+
+impl Visitor<'_> for FieldVisitor {
+  type Value = Field;
+
+  fn expecting(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    write!(formatter, "`groups` or `order`")
+  }
+
+  fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+  where
+    E: Error,
+  {
+    match value {
+      "groups" => Ok(Field::Groups),
+      "order" => Ok(Field::Order),
+      _ => Err(Error::unknown_field(value, FIELDS)),
+    }
+  }
+}
+
+/// Deserializes the keys of a multimap with their values, in order.
+struct GroupsVisitor<K, V>(PhantomData<(K, V)>);
+
+impl<'de, K, V> Visitor<'de> for GroupsVisitor<K, V>
+where
+  K: Deserialize<'de>,
+  V: Deserialize<'de>,
+{
+  type Value = Vec<(K, Vec<V>)>;
+
+  fn expecting(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    write!(formatter, "a map of sequences")
+  }
+
+  fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+  where
+    A: MapAccess<'de>,
+  {
+    let mut groups = Vec::with_capacity(access.size_hint().unwrap_or_default());
+
+    while let Some(group) = access.next_entry()? {
+      groups.push(group);
+    }
+
+    Ok(groups)
+  }
+}
+
+/// Deserializes the keys of a multimap with their values, in order.
+struct DeserializeGroups<K, V>(Vec<(K, Vec<V>)>);
+
+impl<'de, K, V> Deserialize<'de> for DeserializeGroups<K, V>
+where
+  K: Deserialize<'de>,
+  V: Deserialize<'de>,
+{
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    deserializer
+      .deserialize_map(GroupsVisitor(PhantomData))
+      .map(DeserializeGroups)
+  }
+}
+
+struct GroupedLosslessVisitor<K, V, S>(PhantomData<(K, V, S)>);
+
+impl<'de, K, V, S> Visitor<'de> for GroupedLosslessVisitor<K, V, S>
+where
+  K: Deserialize<'de> + Eq + Hash,
+  V: Deserialize<'de>,
+  S: BuildHasher + Default,
+{
+  type Value = ListOrderedMultimap<K, V, S>;
+
+  fn expecting(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    write!(formatter, "a struct with groups and order")
+  }
+
+  fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+  where
+    A: MapAccess<'de>,
+  {
+    let mut groups = None;
+    let mut order = None;
+
+    while let Some(field) = access.next_key()? {
+      match field {
+        Field::Groups => {
+          if groups.is_some() {
+            return Err(Error::duplicate_field("groups"));
+          }
+
+          groups = Some(access.next_value::<DeserializeGroups<K, V>>()?.0);
+        }
+        Field::Order => {
+          if order.is_some() {
+            return Err(Error::duplicate_field("order"));
+          }
+
+          order = Some(access.next_value()?);
+        }
+      }
+    }
+
+    let groups = groups.ok_or_else(|| Error::missing_field("groups"))?;
+    let order = order.ok_or_else(|| Error::missing_field("order"))?;
+    build(groups, order)
+  }
+
+  fn visit_seq<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+  where
+    A: SeqAccess<'de>,
+  {
+    let groups = access
+      .next_element::<DeserializeGroups<K, V>>()?
+      .ok_or_else(|| Error::invalid_length(0, &self))?
+      .0;
+    let order = access
+      .next_element()?
+      .ok_or_else(|| Error::invalid_length(1, &self))?;
+    build(groups, order)
+  }
+}
+
+/// Builds the multimap from the keys with their values and the key ordinals of the values.
+fn build<K, V, S, E>(
+  groups: Vec<(K, Vec<V>)>,
+  order: Vec<usize>,
+) -> Result<ListOrderedMultimap<K, V, S>, E>
+where
+  E: Error,
+  K: Eq + Hash,
+  S: BuildHasher + Default,
+{
+  let keys_len = groups.len();
+
+  // A key without values cannot be in a multimap, and would otherwise be reported as a duplicate key below.
+  if let Some(ordinal) = groups.iter().position(|(_, values)| values.is_empty()) {
+    return Err(E::custom(format_args!(
+      "key ordinal {ordinal} has an empty group of values"
+    )));
+  }
+
+  let mut map = ListOrderedMultimap::with_capacity_and_hasher(keys_len, order.len(), S::default());
+  let mut groups: Vec<_> = groups
+    .into_iter()
+    .map(|(key, values)| (Some(key), None, values.into_iter()))
+    .collect();
+
+  for ordinal in order {
+    let (key, key_index, values) = groups.get_mut(ordinal).ok_or_else(|| {
+      E::invalid_value(
+        Unexpected::Unsigned(ordinal as u64),
+        &"a key ordinal less than the number of keys",
+      )
+    })?;
+    let value = values.next().ok_or_else(|| {
+      E::custom(format_args!(
+        "key ordinal {ordinal} occurs more often than its key has values"
+      ))
+    })?;
+
+    // The key is moved into the multimap with its first value. Later values are appended through the index of the key.
+    match key_index {
+      Some(key_index) => map.occupied_entry(*key_index).append(value),
+      None => {
+        *key_index = Some(match map.entry(key.take().unwrap()) {
+          Entry::Occupied(mut entry) => {
+            entry.append(value);
+            entry.key_index()
+          }
+          Entry::Vacant(entry) => entry.insert_entry(value).key_index(),
+        });
+      }
+    }
+  }
+
+  if let Some(ordinal) = groups.iter().position(|(_, _, values)| values.len() > 0) {
+    return Err(E::custom(format_args!(
+      "key ordinal {ordinal} occurs less often than its key has values"
+    )));
+  }
+
+  if map.keys_len() != keys_len {
+    return Err(E::custom("duplicate key in groups"));
+  }
+
+  Ok(map)
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use serde::Serialize;
+  use serde_test::{assert_de_tokens_error, assert_tokens, Token};
+
+  use super::*;
+
+  #[derive(Debug, Deserialize, PartialEq, Serialize)]
+  struct Wrapper(#[serde(with = "super")] ListOrderedMultimap<char, u32>);
+
+  #[test]
+  fn test_de_error() {
+    let tokens = |groups: &[Token], order: &[u64]| {
+      let mut tokens = vec![
+        Token::NewtypeStruct { name: "Wrapper" },
+        Token::Struct {
+          name: "ListOrderedMultimap",
+          len: 2,
+        },
+        Token::Str("groups"),
+      ];
+      tokens.extend_from_slice(groups);
+      tokens.push(Token::Str("order"));
+      tokens.push(Token::Seq {
+        len: Some(order.len()),
+      });
+      tokens.extend(order.iter().map(|&ordinal| Token::U64(ordinal)));
+      tokens.push(Token::SeqEnd);
+      tokens.push(Token::StructEnd);
+      tokens
+    };
+    let groups = [
+      Token::Map { len: Some(2) },
+      Token::Char('a'),
+      Token::Seq { len: Some(1) },
+      Token::U32(10),
+      Token::SeqEnd,
+      Token::Char('a'),
+      Token::Seq { len: Some(1) },
+      Token::U32(20),
+      Token::SeqEnd,
+      Token::MapEnd,
+    ];
+
+    assert_de_tokens_error::<Wrapper>(&tokens(&groups, &[0, 1]), "duplicate key in groups");
+    assert_de_tokens_error::<Wrapper>(
+      &tokens(&groups, &[0, 2]),
+      "invalid value: integer `2`, expected a key ordinal less than the number of keys",
+    );
+    assert_de_tokens_error::<Wrapper>(
+      &tokens(&groups, &[0, 0]),
+      "key ordinal 0 occurs more often than its key has values",
+    );
+    assert_de_tokens_error::<Wrapper>(
+      &tokens(&groups, &[0]),
+      "key ordinal 1 occurs less often than its key has values",
+    );
+    assert_de_tokens_error::<Wrapper>(
+      &tokens(
+        &[
+          Token::Map { len: Some(2) },
+          Token::Char('a'),
+          Token::Seq { len: Some(1) },
+          Token::U32(10),
+          Token::SeqEnd,
+          Token::Char('b'),
+          Token::Seq { len: Some(0) },
+          Token::SeqEnd,
+          Token::MapEnd,
+        ],
+        &[0],
+      ),
+      "key ordinal 1 has an empty group of values",
+    );
+    assert_de_tokens_error::<Wrapper>(
+      &[
+        Token::NewtypeStruct { name: "Wrapper" },
+        Token::Struct {
+          name: "ListOrderedMultimap",
+          len: 2,
+        },
+        Token::Str("order"),
+        Token::Seq { len: Some(0) },
+        Token::SeqEnd,
+        Token::StructEnd,
+      ],
+      "missing field `groups`",
+    );
+  }
+
+  #[test]
+  fn test_json() {
+    let mut map = ListOrderedMultimap::new();
+    map.append('b', 20);
+    map.append('a', 10);
+    map.append('b', 30);
+    map.remove(&'b');
+    map.append('b', 40);
+    map.append('a', 50);
+    map.append('b', 60);
+
+    let json = serde_json::to_string(&Wrapper(map.clone())).unwrap();
+    assert_eq!(
+      json,
+      r#"{"groups":{"a":[10,50],"b":[40,60]},"order":[0,1,0,1]}"#
+    );
+    assert_eq!(
+      serde_json::from_str::<Wrapper>(&json).unwrap(),
+      Wrapper(map)
+    );
+
+    // Keys inserted in front of existing values are still ordered after the existing keys.
+    let mut map = ListOrderedMultimap::new();
+    map.append('a', 10);
+    map.insert_at(0, 'c', 20);
+    assert_eq!(map.keys().collect::<Vec<_>>(), [&'a', &'c']);
+
+    let json = serde_json::to_string(&Wrapper(map.clone())).unwrap();
+    assert_eq!(json, r#"{"groups":{"a":[10],"c":[20]},"order":[1,0]}"#);
+    assert_eq!(
+      serde_json::from_str::<Wrapper>(&json).unwrap(),
+      Wrapper(map)
+    );
+
+    let json = r#"{"order":[1,0],"groups":{"a":[10],"b":[20]}}"#;
+    let Wrapper(map) = serde_json::from_str(json).unwrap();
+    assert_eq!(map.iter().collect::<Vec<_>>(), [(&'b', &20), (&'a', &10)]);
+  }
+
+  #[test]
+  fn test_ser_de() {
+    let mut map = ListOrderedMultimap::new();
+    map.append('b', 20);
+    map.append('a', 10);
+    map.append('b', 30);
+
+    assert_tokens(
+      &Wrapper(map),
+      &[
+        Token::NewtypeStruct { name: "Wrapper" },
+        Token::Struct {
+          name: "ListOrderedMultimap",
+          len: 2,
+        },
+        Token::Str("groups"),
+        Token::Map { len: Some(2) },
+        Token::Char('b'),
+        Token::Seq { len: Some(2) },
+        Token::U32(20),
+        Token::U32(30),
+        Token::SeqEnd,
+        Token::Char('a'),
+        Token::Seq { len: Some(1) },
+        Token::U32(10),
+        Token::SeqEnd,
+        Token::MapEnd,
+        Token::Str("order"),
+        Token::Seq { len: Some(3) },
+        Token::U64(0),
+        Token::U64(1),
+        Token::U64(0),
+        Token::SeqEnd,
+        Token::StructEnd,
+      ],
+    );
+  }
+}
@@ -0,0 +1,202 @@
+//! Serializes a multimap as a map with one entry per value, in which keys with multiple values are repeated.
+//!
+//! The entries are in the global order of the key-value pairs, so `deserialize(serialize(map)) == map`. Deserializing
+//! accepts any map and keeps every entry, including those with duplicate keys.
+//!
+//! Use this module with `#[serde(with = "ordered_multimap::serde::as_map")]`.
+//!
+//! # Examples
+//!
+//! ```
+//! use ordered_multimap::ListOrderedMultimap;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, Deserialize, PartialEq, Serialize)]
+//! struct Query {
+//!   #[serde(with = "ordered_multimap::serde::as_map")]
+//!   parameters: ListOrderedMultimap<String, String>,
+//! }
+//!
+//! let query: Query = serde_json::from_str(r#"{"parameters":{"tag":"a","page":"2","tag":"b"}}"#).unwrap();
+//!
+//! let mut iter = query.parameters.get_all("tag");
+//! assert_eq!(iter.next().map(String::as_str), Some("a"));
+//! assert_eq!(iter.next().map(String::as_str), Some("b"));
+//! assert_eq!(iter.next(), None);
+//!
+//! assert_eq!(
+//!   serde_json::to_string(&query).unwrap(),
+//!   r#"{"parameters":{"tag":"a","page":"2","tag":"b"}}"#
+//! );
+//! ```
+
+use core::{
+  fmt::{self, Formatter},
+  hash::{BuildHasher, Hash},
+  marker::PhantomData,
+};
+
+use serde::{
+  de::{Deserialize, Deserializer, MapAccess, Visitor},
+  ser::{Serialize, SerializeMap, Serializer},
+};
+
+use super::{Append, Insert};
+use crate::ListOrderedMultimap;
+
+/// Serializes the multimap as a map with one entry per value.
+pub fn serialize<K, V, S, T>(
+  map: &ListOrderedMultimap<K, V, S>,
+  serializer: T,
+) -> Result<T::Ok, T::Error>
+where
+  K: Serialize,
+  V: Serialize,
+  T: Serializer,
+{
+  let mut serialize_map = serializer.serialize_map(Some(map.values_len()))?;
+
+  for (key, value) in map.iter() {
+    serialize_map.serialize_entry(key, value)?;
+  }
+
+  serialize_map.end()
+}
+
+/// Deserializes a multimap from a map, keeping the entries of duplicate keys.
+pub fn deserialize<'de, K, V, S, D>(
+  deserializer: D,
+) -> Result<ListOrderedMultimap<K, V, S>, D::Error>
+where
+  D: Deserializer<'de>,
+  K: Deserialize<'de> + Eq + Hash,
+  S: BuildHasher + Default,
+  V: Deserialize<'de>,
+{
+  deserializer.deserialize_map(MapVisitor::new(Append))
+}
+
+/// Deserializes the keys and values as `KW` and `VW`, converts them into `K` and `V` and inserts them with `I`.
+pub(super) struct MapVisitor<K, V, S, I, KW = K, VW = V> {
+  inserter: I,
+  marker: PhantomData<(K, V, S, KW, VW)>,
+}
+
+impl<K, V, S, I> MapVisitor<K, V, S, I> {
+  pub(super) fn new(inserter: I) -> Self {
+    MapVisitor::converting(inserter)
+  }
+}
+
+impl<K, V, S, I, KW, VW> MapVisitor<K, V, S, I, KW, VW> {
+  pub(super) fn converting(inserter: I) -> Self {
+    MapVisitor {
+      inserter,
+      marker: PhantomData,
+    }
+  }
+}
+
+impl<'de, K, V, S, I, KW, VW> Visitor<'de> for MapVisitor<K, V, S, I, KW, VW>
+where
+  I: Insert<K, V, S>,
+  K: Eq + Hash,
+  KW: Deserialize<'de> + Into<K>,
+  S: BuildHasher + Default,
+  VW: Deserialize<'de> + Into<V>,
+{
+  type Value = ListOrderedMultimap<K, V, S>;
+
+  fn expecting(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    write!(formatter, "a map")
+  }
+
+  fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+  where
+    A: MapAccess<'de>,
+  {
+    let capacity = self.inserter.capacity(access.size_hint());
+    let mut map = ListOrderedMultimap::with_capacity_and_hasher(capacity, capacity, S::default());
+    let mut index = 0;
+
+    while let Some((key, value)) = access.next_entry::<KW, VW>()? {
+      self
+        .inserter
+        .insert(&mut map, index, key.into(), value.into())?;
+      index += 1;
+    }
+
+    Ok(map)
+  }
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use serde::{Deserialize, Serialize};
+  use serde_test::{assert_de_tokens_error, assert_tokens, Token};
+
+  use super::*;
+
+  #[derive(Debug, Deserialize, PartialEq, Serialize)]
+  struct Wrapper(#[serde(with = "super")] ListOrderedMultimap<char, u32>);
+
+  #[test]
+  fn test_de_error() {
+    assert_de_tokens_error::<Wrapper>(
+      &[
+        Token::NewtypeStruct { name: "Wrapper" },
+        Token::Seq { len: Some(0) },
+      ],
+      "invalid type: sequence, expected a map",
+    );
+  }
+
+  #[test]
+  fn test_json() {
+    let json = r#"{"b":20,"a":10,"b":30}"#;
+    let Wrapper(map) = serde_json::from_str(json).unwrap();
+
+    assert_eq!(
+      map.iter().collect::<Vec<_>>(),
+      [(&'b', &20), (&'a', &10), (&'b', &30)]
+    );
+    assert_eq!(serde_json::to_string(&Wrapper(map)).unwrap(), json);
+  }
+
+  #[test]
+  fn test_ser_de() {
+    let mut map = ListOrderedMultimap::new();
+    map.append('b', 20);
+    map.append('a', 10);
+    map.append('b', 30);
+
+    assert_tokens(
+      &Wrapper(map),
+      &[
+        Token::NewtypeStruct { name: "Wrapper" },
+        Token::Map { len: Some(3) },
+        Token::Char('b'),
+        Token::U32(20),
+        Token::Char('a'),
+        Token::U32(10),
+        Token::Char('b'),
+        Token::U32(30),
+        Token::MapEnd,
+      ],
+    );
+  }
+
+  #[test]
+  fn test_ser_de_empty() {
+    assert_tokens(
+      &Wrapper(ListOrderedMultimap::new()),
+      &[
+        Token::NewtypeStruct { name: "Wrapper" },
+        Token::Map { len: Some(0) },
+        Token::MapEnd,
+      ],
+    );
+  }
+}
@@ -0,0 +1,289 @@
+//! Serializes a multimap as a sequence of key-value tuples, for example `[["a", 1], ["b", 3], ["a", 2]]`.
+//!
+//! The tuples are in the global order of the key-value pairs, so `deserialize(serialize(map)) == map`. This is the
+//! representation used by the [`Serialize`] and [`Deserialize`] implementations of [`ListOrderedMultimap`].
+//!
+//! [`deserialize_into`] deserializes into an existing multimap in this representation. It clears the multimap and
+//! reuses its allocations instead of creating a new multimap, and it keeps the multimap's hasher, which therefore does
+//! not need to implement [`Default`]. If deserialization fails, the multimap contains the key-value pairs deserialized
+//! so far. [`Deserialize::deserialize_in_place`] of [`ListOrderedMultimap`] does the same.
+//!
+//! Use this module with `#[serde(with = "ordered_multimap::serde::as_pairs")]`.
+//!
+//! # Examples
+//!
+//! ```
+//! use ordered_multimap::ListOrderedMultimap;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, Deserialize, PartialEq, Serialize)]
+//! struct Document {
+//!   #[serde(with = "ordered_multimap::serde::as_pairs")]
+//!   tags: ListOrderedMultimap<String, u32>,
+//! }
+//!
+//! let mut tags = ListOrderedMultimap::new();
+//! tags.append("a".to_string(), 1);
+//! tags.append("b".to_string(), 3);
+//! tags.append("a".to_string(), 2);
+//!
+//! let json = serde_json::to_string(&Document { tags }).unwrap();
+//! assert_eq!(json, r#"{"tags":[["a",1],["b",3],["a",2]]}"#);
+//! ```
+
+use core::{
+  fmt::{self, Formatter},
+  hash::{BuildHasher, Hash},
+  marker::PhantomData,
+};
+
+use serde::{
+  de::{Deserialize, Deserializer, SeqAccess, Visitor},
+  ser::{Serialize, SerializeSeq, Serializer},
+};
+
+use super::{Append, Insert};
+use crate::ListOrderedMultimap;
+
+/// Serializes the multimap as a sequence of key-value tuples.
+pub fn serialize<K, V, S, T>(
+  map: &ListOrderedMultimap<K, V, S>,
+  serializer: T,
+) -> Result<T::Ok, T::Error>
+where
+  K: Serialize,
+  T: Serializer,
+  V: Serialize,
+{
+  serialize_iter(map.values_len(), map.iter(), serializer)
+}
+
+/// Serializes `len` key-value pairs as a sequence of key-value tuples.
+pub(super) fn serialize_iter<'a, K, V, I, T>(
+  len: usize,
+  pairs: I,
+  serializer: T,
+) -> Result<T::Ok, T::Error>
+where
+  I: IntoIterator<Item = (&'a K, &'a V)>,
+  K: 'a + Serialize,
+  T: Serializer,
+  V: 'a + Serialize,
+{
+  let mut seq = serializer.serialize_seq(Some(len))?;
+
+  for (key, value) in pairs {
+    seq.serialize_element(&(key, value))?;
+  }
+
+  seq.end()
+}
+
+/// Deserializes a multimap from a sequence of key-value tuples.
+pub fn deserialize<'de, K, V, S, D>(
+  deserializer: D,
+) -> Result<ListOrderedMultimap<K, V, S>, D::Error>
+where
+  D: Deserializer<'de>,
+  K: Deserialize<'de> + Eq + Hash,
+  S: BuildHasher + Default,
+  V: Deserialize<'de>,
+{
+  deserializer.deserialize_seq(PairsVisitor::new(Append))
+}
+
+/// Deserializes the keys and values as `KW` and `VW`, converts them into `K` and `V` and inserts them with `I`.
+pub(super) struct PairsVisitor<K, V, S, I, KW = K, VW = V> {
+  inserter: I,
+  marker: PhantomData<(K, V, S, KW, VW)>,
+}
+
+impl<K, V, S, I> PairsVisitor<K, V, S, I> {
+  pub(super) fn new(inserter: I) -> Self {
+    PairsVisitor::converting(inserter)
+  }
+}
+
+impl<K, V, S, I, KW, VW> PairsVisitor<K, V, S, I, KW, VW> {
+  pub(super) fn converting(inserter: I) -> Self {
+    PairsVisitor {
+      inserter,
+      marker: PhantomData,
+    }
+  }
+}
+
+impl<'de, K, V, S, I, KW, VW> Visitor<'de> for PairsVisitor<K, V, S, I, KW, VW>
+where
+  I: Insert<K, V, S>,
+  K: Eq + Hash,
+  KW: Deserialize<'de> + Into<K>,
+  S: BuildHasher + Default,
+  VW: Deserialize<'de> + Into<V>,
+{
+  type Value = ListOrderedMultimap<K, V, S>;
+
+  fn expecting(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    write!(formatter, "a sequence")
+  }
+
+  fn visit_seq<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+  where
+    A: SeqAccess<'de>,
+  {
+    let capacity = self.inserter.capacity(access.size_hint());
+    let mut map = ListOrderedMultimap::with_capacity_and_hasher(capacity, capacity, S::default());
+    let mut index = 0;
+
+    while let Some((key, value)) = access.next_element::<(KW, VW)>()? {
+      self
+        .inserter
+        .insert(&mut map, index, key.into(), value.into())?;
+      index += 1;
+    }
+
+    Ok(map)
+  }
+}
+
+/// Deserializes a multimap from a sequence of key-value tuples into an existing multimap, reusing its allocations and
+/// keeping its hasher.
+///
+/// # Errors
+///
+/// Fails if the input is not a sequence of key-value tuples. The multimap then contains the key-value pairs
+/// deserialized so far.
+///
+/// # Examples
+///
+/// ```
+/// use ordered_multimap::serde::as_pairs;
+/// use ordered_multimap::ListOrderedMultimap;
+///
+/// let mut map: ListOrderedMultimap<String, u32> = ListOrderedMultimap::new();
+/// map.append("x".to_string(), 0);
+///
+/// let mut deserializer = serde_json::Deserializer::from_str(r#"[["a",1],["b",2]]"#);
+/// as_pairs::deserialize_into(&mut deserializer, &mut map).unwrap();
+/// assert_eq!(map.values().collect::<Vec<_>>(), [&1, &2]);
+/// ```
+pub fn deserialize_into<'de, K, V, S, D>(
+  deserializer: D,
+  map: &mut ListOrderedMultimap<K, V, S>,
+) -> Result<(), D::Error>
+where
+  D: Deserializer<'de>,
+  K: Deserialize<'de> + Eq + Hash,
+  S: BuildHasher,
+  V: Deserialize<'de>,
+{
+  deserializer.deserialize_seq(PairsInPlaceVisitor(map))
+}
+
+/// Deserializes a sequence of key-value tuples into the given multimap, which is cleared first.
+struct PairsInPlaceVisitor<'map, K, V, S>(&'map mut ListOrderedMultimap<K, V, S>);
+
+impl<'de, K, V, S> Visitor<'de> for PairsInPlaceVisitor<'_, K, V, S>
+where
+  K: Deserialize<'de> + Eq + Hash,
+  S: BuildHasher,
+  V: Deserialize<'de>,
+{
+  type Value = ();
+
+  fn expecting(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    write!(formatter, "a sequence")
+  }
+
+  fn visit_seq<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+  where
+    A: SeqAccess<'de>,
+  {
+    self.0.clear();
+    self
+      .0
+      .reserve_values(access.size_hint().unwrap_or_default());
+
+    while let Some((key, value)) = access.next_element()? {
+      let _ = self.0.append(key, value);
+    }
+
+    Ok(())
+  }
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use std::{collections::hash_map::DefaultHasher, hash::Hasher};
+
+  use serde::{Deserialize, Serialize};
+  use serde_test::{assert_tokens, Token};
+
+  use super::*;
+
+  #[derive(Debug, Deserialize, PartialEq, Serialize)]
+  struct Wrapper(#[serde(with = "super")] ListOrderedMultimap<char, u32>);
+
+  #[test]
+  fn test_deserialize_into() {
+    struct SeededState(u64);
+
+    impl BuildHasher for SeededState {
+      type Hasher = DefaultHasher;
+
+      fn build_hasher(&self) -> Self::Hasher {
+        let mut hasher = DefaultHasher::new();
+        hasher.write_u64(self.0);
+        hasher
+      }
+    }
+
+    let mut map = ListOrderedMultimap::with_hasher(SeededState(7));
+    map.append('x', 0);
+
+    let mut deserializer = serde_json::Deserializer::from_str(r#"[["a",1],["b",2],["a",3]]"#);
+    deserialize_into(&mut deserializer, &mut map).unwrap();
+
+    assert_eq!(
+      map.iter().collect::<Vec<_>>(),
+      [(&'a', &1), (&'b', &2), (&'a', &3)]
+    );
+    assert_eq!(map.get_all(&'a').collect::<Vec<_>>(), [&1, &3]);
+    assert_eq!(map.hasher().0, 7);
+
+    let mut deserializer = serde_json::Deserializer::from_str("[[\"c\",4],5]");
+    assert!(deserialize_into(&mut deserializer, &mut map).is_err());
+    assert_eq!(map.iter().collect::<Vec<_>>(), [(&'c', &4)]);
+  }
+
+  #[test]
+  fn test_ser_de() {
+    let mut map = ListOrderedMultimap::new();
+    map.append('b', 20);
+    map.append('a', 10);
+    map.append('b', 30);
+
+    assert_tokens(
+      &Wrapper(map),
+      &[
+        Token::NewtypeStruct { name: "Wrapper" },
+        Token::Seq { len: Some(3) },
+        Token::Tuple { len: 2 },
+        Token::Char('b'),
+        Token::U32(20),
+        Token::TupleEnd,
+        Token::Tuple { len: 2 },
+        Token::Char('a'),
+        Token::U32(10),
+        Token::TupleEnd,
+        Token::Tuple { len: 2 },
+        Token::Char('b'),
+        Token::U32(30),
+        Token::TupleEnd,
+        Token::SeqEnd,
+      ],
+    );
+  }
+}
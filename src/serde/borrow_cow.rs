@@ -0,0 +1,206 @@
+//! Deserializes multimaps with `Cow<'de, str>` keys and values that borrow from the input where possible.
+//!
+//! Multimaps with `&'de str` or `&'de [u8]` keys and values borrow from the input with any representation. The
+//! [`Deserialize`] implementation of [`Cow`] however always allocates, even when the input could be
+//! borrowed. The functions of this module deserialize every string that the deserializer can borrow, for example one
+//! without escape sequences in JSON, as [`Cow::Borrowed`] and only allocate for the others.
+//!
+//! Use these functions with `#[serde(borrow, deserialize_with = "...")]`.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::borrow::Cow;
+//!
+//! use ordered_multimap::ListOrderedMultimap;
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Request<'a> {
+//!   #[serde(borrow, deserialize_with = "ordered_multimap::serde::borrow_cow::map")]
+//!   headers: ListOrderedMultimap<Cow<'a, str>, Cow<'a, str>>,
+//! }
+//!
+//! let json = r#"{"headers":{"accept":"text/html","x-quote":"\"quoted\""}}"#;
+//! let request: Request<'_> = serde_json::from_str(json).unwrap();
+//!
+//! let mut iter = request.headers.iter();
+//! let (key, value) = iter.next().unwrap();
+//! assert!(matches!((key, value), (Cow::Borrowed("accept"), Cow::Borrowed("text/html"))));
+//! let (key, value) = iter.next().unwrap();
+//! assert!(matches!(key, Cow::Borrowed("x-quote")));
+//! assert!(matches!(value, Cow::Owned(value) if value == "\"quoted\""));
+//! ```
+
+use alloc::{
+  borrow::{Cow, ToOwned},
+  string::String,
+};
+use core::{
+  fmt::{self, Formatter},
+  hash::BuildHasher,
+};
+
+use serde::de::{Deserialize, Deserializer, Error, Visitor};
+
+use super::{as_map::MapVisitor, as_pairs::PairsVisitor, Append};
+use crate::ListOrderedMultimap;
+
+/// Deserializes a multimap in the [`as_pairs`](super::as_pairs) representation, borrowing keys and values from the
+/// input where possible.
+pub fn pairs<'de, S, D>(
+  deserializer: D,
+) -> Result<ListOrderedMultimap<Cow<'de, str>, Cow<'de, str>, S>, D::Error>
+where
+  D: Deserializer<'de>,
+  S: BuildHasher + Default,
+{
+  deserializer
+    .deserialize_seq(PairsVisitor::<_, _, S, _, CowStr<'de>, CowStr<'de>>::converting(Append))
+}
+
+/// Deserializes a multimap in the [`as_map`](super::as_map) representation, borrowing keys and values from the input
+/// where possible.
+pub fn map<'de, S, D>(
+  deserializer: D,
+) -> Result<ListOrderedMultimap<Cow<'de, str>, Cow<'de, str>, S>, D::Error>
+where
+  D: Deserializer<'de>,
+  S: BuildHasher + Default,
+{
+  deserializer
+    .deserialize_map(MapVisitor::<_, _, S, _, CowStr<'de>, CowStr<'de>>::converting(Append))
+}
+
+/// A string that is deserialized as [`Cow::Borrowed`] if the deserializer can borrow it.
+struct CowStr<'de>(Cow<'de, str>);
+
+impl<'de> Deserialize<'de> for CowStr<'de> {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    deserializer.deserialize_str(CowStrVisitor)
+  }
+}
+
+impl<'de> From<CowStr<'de>> for Cow<'de, str> {
+  fn from(value: CowStr<'de>) -> Self {
+    value.0
+  }
+}
+
+struct CowStrVisitor;
+
+impl<'de> Visitor<'de> for CowStrVisitor {
+  type Value = CowStr<'de>;
+
+  fn expecting(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+    write!(formatter, "a string")
+  }
+
+  fn visit_borrowed_str<E>(self, value: &'de str) -> Result<Self::Value, E>
+  where
+    E: Error,
+  {
+    Ok(CowStr(Cow::Borrowed(value)))
+  }
+
+  fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+  where
+    E: Error,
+  {
+    Ok(CowStr(Cow::Owned(value.to_owned())))
+  }
+
+  fn visit_string<E>(self, value: String) -> Result<Self::Value, E>
+  where
+    E: Error,
+  {
+    Ok(CowStr(Cow::Owned(value)))
+  }
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use std::collections::hash_map::RandomState;
+
+  use serde::{Deserialize, Serialize};
+
+  use super::*;
+
+  #[derive(Debug, Deserialize, Serialize)]
+  struct Request<'a> {
+    #[serde(borrow, deserialize_with = "pairs")]
+    pairs: ListOrderedMultimap<Cow<'a, str>, Cow<'a, str>>,
+
+    #[serde(
+      borrow,
+      deserialize_with = "map",
+      serialize_with = "crate::serde::as_map::serialize"
+    )]
+    map: ListOrderedMultimap<Cow<'a, str>, Cow<'a, str>>,
+  }
+
+  fn is_borrowed(map: &ListOrderedMultimap<Cow<'_, str>, Cow<'_, str>>) -> bool {
+    map
+      .iter()
+      .all(|(key, value)| matches!(key, Cow::Borrowed(_)) && matches!(value, Cow::Borrowed(_)))
+  }
+
+  #[test]
+  fn test_json() {
+    let json = r#"{"pairs":[["a","1"],["b","2"],["a","3"]],"map":{"a":"1","b":"2\n"}}"#;
+    let request: Request<'_> = serde_json::from_str(json).unwrap();
+
+    assert!(is_borrowed(&request.pairs));
+    assert_eq!(
+      request.pairs.iter().collect::<Vec<_>>(),
+      [
+        (&"a".into(), &"1".into()),
+        (&"b".into(), &"2".into()),
+        (&"a".into(), &"3".into())
+      ]
+    );
+
+    let mut iter = request.map.iter();
+    assert!(matches!(
+      iter.next(),
+      Some((Cow::Borrowed("a"), Cow::Borrowed("1")))
+    ));
+    assert!(matches!(iter.next(), Some((Cow::Borrowed("b"), Cow::Owned(value))) if value == "2\n"));
+    assert!(iter.next().is_none());
+  }
+
+  #[test]
+  fn test_owned() {
+    let mut deserializer = serde_json::Deserializer::from_reader(&br#"[["a","1"]]"#[..]);
+    let map: ListOrderedMultimap<_, _, RandomState> = pairs(&mut deserializer).unwrap();
+
+    assert!(
+      matches!(map.iter().next(), Some((Cow::Owned(key), Cow::Owned(value))) if key == "a" && value == "1")
+    );
+  }
+
+  #[test]
+  fn test_postcard() {
+    let mut request = Request {
+      pairs: ListOrderedMultimap::new(),
+      map: ListOrderedMultimap::new(),
+    };
+    request.pairs.append("a".into(), "1".into());
+    request.pairs.append("b".into(), "2".into());
+    request.map.append("a".into(), "1".into());
+    request.map.append("a".into(), "3".into());
+
+    let bytes = postcard::to_allocvec(&request).unwrap();
+    let deserialized: Request<'_> = postcard::from_bytes(&bytes).unwrap();
+
+    assert!(is_borrowed(&deserialized.pairs));
+    assert!(is_borrowed(&deserialized.map));
+    assert_eq!(deserialized.pairs, request.pairs);
+    assert_eq!(deserialized.map, request.map);
+  }
+}
@@ -0,0 +1,160 @@
+//! Provides parsing and serialization of `application/x-www-form-urlencoded` data, such as URL query strings and HTML
+//! form submissions.
+//!
+//! Query strings often repeat keys, as in `tag=a&tag=b`, and the order of their key-value pairs can be significant, so
+//! they are naturally represented by a [`ListOrderedMultimap`]. [`parse_query`] keeps all key-value pairs in their
+//! global order and [`to_query_string`] writes them back in that order, so that the key-value pairs round-trip.
+//!
+//! Parsing percent-decodes the keys and values and decodes `+` as a space. Invalid UTF-8 is replaced by `U+FFFD`. A key
+//! without `=` gets an empty value, and empty key-value pairs such as in `a=1&&b=2` are skipped. Serialization always
+//! encodes spaces as `+` and percent-encodes every byte other than ASCII alphanumerics and `*-._`, so the serialized
+//! string may differ textually from the parsed one even though it contains the same key-value pairs.
+//!
+//! # Examples
+//!
+//! ```
+//! use ordered_multimap::urlencoded;
+//!
+//! let map = urlencoded::parse_query("tag=a&q=hello+world&tag=b%26c");
+//! assert_eq!(map.get_all("tag").collect::<Vec<_>>(), ["a", "b&c"]);
+//! assert_eq!(map.get("q").unwrap(), "hello world");
+//!
+//! assert_eq!(urlencoded::to_query_string(&map), "tag=a&q=hello+world&tag=b%26c");
+//! ```
+
+use alloc::{borrow::Cow, string::String};
+
+use crate::ListOrderedMultimap;
+
+/// Parses an `application/x-www-form-urlencoded` string, such as a URL query string without the leading `?`, into a
+/// multimap of its key-value pairs in global order.
+///
+/// # Examples
+///
+/// ```
+/// use ordered_multimap::urlencoded::parse_query;
+///
+/// let map = parse_query("a=1&b=2&a=3&c");
+///
+/// let mut iter = map.iter();
+/// assert_eq!(iter.next(), Some((&"a".to_string(), &"1".to_string())));
+/// assert_eq!(iter.next(), Some((&"b".to_string(), &"2".to_string())));
+/// assert_eq!(iter.next(), Some((&"a".to_string(), &"3".to_string())));
+/// assert_eq!(iter.next(), Some((&"c".to_string(), &"".to_string())));
+/// assert_eq!(iter.next(), None);
+/// ```
+#[must_use]
+pub fn parse_query(query: &str) -> ListOrderedMultimap<String, String> {
+  form_urlencoded::parse(query.as_bytes())
+    .into_owned()
+    .collect()
+}
+
+/// Parses an `application/x-www-form-urlencoded` string like [`parse_query`], but keys and values that need no
+/// decoding borrow from the input instead of allocating.
+///
+/// # Examples
+///
+/// ```
+/// use std::borrow::Cow;
+///
+/// use ordered_multimap::urlencoded::parse_query_cow;
+///
+/// let map = parse_query_cow("a=1&b=hello+world");
+///
+/// assert!(matches!(map.get("a"), Some(Cow::Borrowed("1"))));
+/// assert!(matches!(map.get("b"), Some(Cow::Owned(value)) if value == "hello world"));
+/// ```
+#[must_use]
+pub fn parse_query_cow(query: &str) -> ListOrderedMultimap<Cow<'_, str>, Cow<'_, str>> {
+  form_urlencoded::parse(query.as_bytes()).collect()
+}
+
+/// Serializes the key-value pairs of the multimap in global order into an `application/x-www-form-urlencoded` string,
+/// without a leading `?`.
+///
+/// # Examples
+///
+/// ```
+/// use ordered_multimap::{urlencoded::to_query_string, ListOrderedMultimap};
+///
+/// let mut map = ListOrderedMultimap::new();
+/// map.append("a", "1");
+/// map.append("b", "x y");
+/// map.append("a", "2");
+///
+/// assert_eq!(to_query_string(&map), "a=1&b=x+y&a=2");
+/// ```
+#[must_use]
+pub fn to_query_string<Key, Value, State>(map: &ListOrderedMultimap<Key, Value, State>) -> String
+where
+  Key: AsRef<str>,
+  Value: AsRef<str>,
+{
+  form_urlencoded::Serializer::new(String::new())
+    .extend_pairs(map.iter())
+    .finish()
+}
+
+#[allow(unused_results)]
+#[cfg(all(test, feature = "std"))]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_parse_query() {
+    let map = parse_query("a=1&b=%C3%A9+x&a=&&c&=4&a=5%");
+
+    assert_eq!(
+      map.iter().collect::<Vec<_>>(),
+      [
+        (&"a".to_string(), &"1".to_string()),
+        (&"b".to_string(), &"é x".to_string()),
+        (&"a".to_string(), &"".to_string()),
+        (&"c".to_string(), &"".to_string()),
+        (&"".to_string(), &"4".to_string()),
+        (&"a".to_string(), &"5%".to_string()),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_parse_query_cow() {
+    let map = parse_query_cow("a=1&b=%41");
+
+    assert!(matches!(map.get("a"), Some(Cow::Borrowed("1"))));
+    assert!(matches!(map.get("b"), Some(Cow::Owned(value)) if value == "A"));
+  }
+
+  #[test]
+  fn test_parse_query_empty() {
+    assert!(parse_query("").is_empty());
+    assert!(parse_query_cow("").is_empty());
+  }
+
+  #[test]
+  fn test_round_trip() {
+    let query = "x=%2B1&y=a+b&x=%E2%9C%93&z=%3D%26";
+    let map = parse_query(query);
+
+    assert_eq!(to_query_string(&map), query);
+    assert_eq!(parse_query(&to_query_string(&map)), map);
+  }
+
+  #[test]
+  fn test_to_query_string() {
+    let mut map = ListOrderedMultimap::new();
+    map.append("b", "1");
+    map.append("a", "~ &=");
+    map.append("b", "");
+
+    assert_eq!(to_query_string(&map), "b=1&a=%7E+%26%3D&b=");
+  }
+
+  #[test]
+  fn test_to_query_string_empty() {
+    let map: ListOrderedMultimap<String, String> = ListOrderedMultimap::new();
+    assert_eq!(to_query_string(&map), "");
+  }
+}